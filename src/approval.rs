@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::BrowserClient;
+use crate::agent::AgentPlan;
+use crate::jobs::BrowserJob;
+use crate::js;
+use crate::types::BrowserError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    Approve,
+    // Run these jobs instead of the proposed ones.
+    Edit(Vec<BrowserJob>),
+    Reject(String),
+}
+
+// A human gate between planning and execution. The agent blocks on `review` for every plan,
+// including corrected plans after a failure.
+#[async_trait]
+pub trait Approver: Send + Sync + std::fmt::Debug {
+    async fn review(
+        &self,
+        plan: &AgentPlan,
+        client: &mut BrowserClient,
+    ) -> Result<Approval, BrowserError>;
+}
+
+// Asks on the terminal: approve, edit (paste a replacement JSON job array) or reject.
+#[derive(Debug, Clone, Default)]
+pub struct CliApprover;
+
+#[async_trait]
+impl Approver for CliApprover {
+    async fn review(
+        &self,
+        plan: &AgentPlan,
+        _client: &mut BrowserClient,
+    ) -> Result<Approval, BrowserError> {
+        println!("📋 Proposed plan:\n{}", plan.markdown_todo);
+        for (i, job) in plan.jobs.iter().enumerate() {
+            println!(
+                "   {}. {}",
+                i + 1,
+                serde_json::to_string(job).unwrap_or_default()
+            );
+        }
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            println!("Run it? [y]es / [e]dit / [n]o");
+            let answer = read_line(&mut lines).await?;
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Ok(Approval::Approve),
+                "n" | "no" => {
+                    println!("Reason (optional):");
+                    let reason = read_line(&mut lines).await?;
+                    return Ok(Approval::Reject(reason.trim().to_string()));
+                }
+                "e" | "edit" => {
+                    println!("Paste the jobs as a JSON array on one line:");
+                    let json = read_line(&mut lines).await?;
+                    match serde_json::from_str(&json) {
+                        Ok(jobs) => return Ok(Approval::Edit(jobs)),
+                        Err(e) => println!("Invalid jobs JSON: {e}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn read_line(
+    lines: &mut tokio::io::Lines<BufReader<tokio::io::Stdin>>,
+) -> Result<String, BrowserError> {
+    lines
+        .next_line()
+        .await
+        .map_err(|e| BrowserError::OperationError(e.to_string()))?
+        .ok_or_else(|| BrowserError::OperationError("stdin closed while awaiting approval".into()))
+}
+
+// Shows the plan in the browser, with an editable job list and Approve/Reject buttons, for
+// the in-browser workflow. The panel gets a tab of its own rather than sitting on the page
+// under review: that page's scripts have no handle on a tab WebDriver opened, so they can
+// neither press the buttons nor forge the decision. Closing or navigating the tab, or
+// leaving it longer than `timeout`, rejects the plan.
+#[derive(Debug, Clone)]
+pub struct OverlayApprover {
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for OverlayApprover {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(300),
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+impl OverlayApprover {
+    async fn await_decision(
+        &self,
+        plan: &AgentPlan,
+        client: &BrowserClient,
+    ) -> Result<Approval, BrowserError> {
+        let jobs = serde_json::to_string_pretty(&plan.jobs).unwrap_or_default();
+        client
+            .client
+            .execute(
+                js::APPROVAL_PANEL,
+                vec![plan.markdown_todo.clone().into(), jobs.clone().into()],
+            )
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if Instant::now() >= deadline {
+                return Ok(Approval::Reject(format!(
+                    "no decision within {}s",
+                    self.timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+            let Ok(decision) = client
+                .client
+                .execute("return window.__iuApproval || null;", vec![])
+                .await
+            else {
+                return Ok(Approval::Reject("the approval tab was closed".into()));
+            };
+            if decision.is_null() {
+                return Ok(Approval::Reject(
+                    "the approval tab navigated away from the panel".into(),
+                ));
+            }
+            let Some(choice) = decision["decision"].as_str() else {
+                continue;
+            };
+            if choice != "approve" {
+                return Ok(Approval::Reject("rejected in the approval tab".into()));
+            }
+            let edited = decision["jobs"].as_str().unwrap_or_default();
+            if edited.trim() == jobs.trim() {
+                return Ok(Approval::Approve);
+            }
+            return serde_json::from_str(edited)
+                .map(Approval::Edit)
+                .map_err(|e| BrowserError::OperationError(format!("Invalid edited jobs: {e}")));
+        }
+    }
+}
+
+#[async_trait]
+impl Approver for OverlayApprover {
+    async fn review(
+        &self,
+        plan: &AgentPlan,
+        client: &mut BrowserClient,
+    ) -> Result<Approval, BrowserError> {
+        let home = client
+            .client
+            .window()
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        let tab = client
+            .client
+            .new_window(true)
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?
+            .handle;
+        client.switch_to_handle(&tab).await?;
+        let approval = self.await_decision(plan, client).await;
+
+        if client.list_tabs().await?.contains(&tab) {
+            client.switch_to_handle(&tab).await?;
+            client
+                .client
+                .close_window()
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        client.switch_to_handle(&home).await?;
+        approval
+    }
+}
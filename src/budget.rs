@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::cl100k_base_singleton;
+
+// Counts use OpenAI's cl100k vocabulary. Other models split text differently, but the
+// difference is small next to `reserve`, which also absorbs it.
+pub fn count_tokens(text: &str) -> usize {
+    cl100k_base_singleton().encode_ordinary(text).len()
+}
+
+// Cuts `text` to at most `max` tokens, noting how much was dropped.
+pub fn truncate_tokens(text: &str, max: usize) -> String {
+    let bpe = cl100k_base_singleton();
+    let tokens = bpe.encode_ordinary(text);
+    if tokens.len() <= max {
+        return text.to_string();
+    }
+    let bytes = bpe.decode_bytes(&tokens[..max]).unwrap_or_default();
+    format!(
+        "{}\n... [{} more tokens omitted]",
+        String::from_utf8_lossy(&bytes),
+        tokens.len() - max
+    )
+}
+
+// How the model's context window is split between the sections of a planning prompt. The
+// instructions, action list and examples are counted first; what remains after `reserve`
+// (room for the response) is shared out by the section weights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextBudget {
+    pub window: usize,
+    pub reserve: usize,
+    pub memory: f32,
+    pub interactive: f32,
+    pub text: f32,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            window: 8192,
+            reserve: 1024,
+            memory: 0.2,
+            interactive: 0.45,
+            text: 0.35,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionBudgets {
+    pub memory: usize,
+    pub interactive: usize,
+    pub text: usize,
+}
+
+impl ContextBudget {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    // `fixed` is the token count of everything in the prompt besides the three sections.
+    pub fn sections(&self, fixed: usize) -> SectionBudgets {
+        let free = self.window.saturating_sub(self.reserve + fixed) as f32;
+        let total = (self.memory + self.interactive + self.text).max(f32::EPSILON);
+        let share = |weight: f32| (free * weight / total) as usize;
+        SectionBudgets {
+            memory: share(self.memory),
+            interactive: share(self.interactive),
+            text: share(self.text),
+        }
+    }
+}
+
+// Indices of the items whose JSON fits in `max` tokens, taken in `order`.
+pub fn fit_items<T: Serialize>(
+    items: &[T],
+    order: impl IntoIterator<Item = usize>,
+    max: usize,
+) -> Vec<usize> {
+    let mut used = 2;
+    let mut kept = vec![];
+    for i in order {
+        let json = serde_json::to_string_pretty(&items[i]).unwrap_or_default();
+        let tokens = count_tokens(&json) + 1;
+        if used + tokens > max {
+            break;
+        }
+        used += tokens;
+        kept.push(i);
+    }
+    kept.sort_unstable();
+    kept
+}
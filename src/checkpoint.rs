@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::agent::{Subtask, SubtaskReport, TaskStep};
+use crate::jobs::{BrowserJob, RunContext};
+use crate::types::{AgentMemory, BrowserError};
+
+// Everything needed to pick an interrupted `run_task` / `run_decomposed` back up. The agent
+// rewrites it after every job and every round when `Agent::checkpoint` is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Checkpoint {
+    // Shared by the checkpoint, trajectory lines and resumed continuations of one run.
+    pub run_id: String,
+    pub goal: String,
+    // The decomposition of `goal`; empty for a plain task.
+    pub subtasks: Vec<Subtask>,
+    pub completed: Vec<SubtaskReport>,
+    // The task (or subtask prompt) being worked on and its rounds so far.
+    pub task: String,
+    pub steps: Vec<TaskStep>,
+    // Jobs of the current plan that have not run yet.
+    pub pending_jobs: Vec<BrowserJob>,
+    pub url: String,
+    pub memory: Option<AgentMemory>,
+    pub executed_jobs: Vec<BrowserJob>,
+    // Values captured by jobs with a `store_as` name. Presets are left out, so credentials
+    // passed in are not written to disk; the resuming agent supplies its own again.
+    pub variables: RunContext,
+}
+
+impl Checkpoint {
+    pub fn new_run_id() -> String {
+        chrono::Local::now().format("%Y%m%dT%H%M%S%.3f").to_string()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BrowserError> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+        serde_json::from_str(&data).map_err(|e| {
+            BrowserError::ConfigError(format!("Invalid checkpoint {}: {e}", path.display()))
+        })
+    }
+
+    // Written to a sibling file and renamed, so a crash mid-write keeps the previous one.
+    pub fn save(&self, path: &Path) -> Result<(), BrowserError> {
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| BrowserError::OperationError(format!("{}: {e}", path.display())))
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::types::{InteractiveElement, InteractiveElementType, TextElement};
+
+// Rough budget estimate; good enough to keep prompts under the model's context window.
+const CHARS_PER_TOKEN: usize = 4;
+
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "with", "that", "this", "from", "into", "then", "than", "are", "was",
+    "you", "your", "all", "any", "can", "how", "what", "when", "where", "which", "page", "open",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextChunk {
+    pub heading: Option<String>,
+    pub elements: Vec<TextElement>,
+    pub score: f32,
+}
+
+impl TextChunk {
+    pub fn text(&self) -> String {
+        self.elements
+            .iter()
+            .map(|el| el.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn tokens(&self) -> usize {
+        self.elements
+            .iter()
+            .map(|el| el.text.len() / CHARS_PER_TOKEN + 1)
+            .sum()
+    }
+}
+
+// Task words that mean something will be typed, so text fields deserve a boost.
+const TYPING_WORDS: [&str; 10] = [
+    "type", "search", "enter", "fill", "write", "login", "log", "sign", "email", "password",
+];
+
+// Attributes that name an element when its visible text doesn't.
+const NAMING_ATTRIBUTES: [&str; 6] = ["aria-label", "title", "name", "id", "alt", "value"];
+
+fn is_heading(el: &TextElement) -> bool {
+    matches!(el.tag.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+pub(crate) fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|t| t.len() > 2 && !STOPWORDS.contains(&t.as_str()))
+        .collect()
+}
+
+// Splits the page into sections, each starting at a heading and running to the next one.
+pub fn chunk_by_section(elements: &[TextElement]) -> Vec<TextChunk> {
+    let mut chunks: Vec<TextChunk> = vec![];
+    for el in elements {
+        if is_heading(el) || chunks.is_empty() {
+            chunks.push(TextChunk {
+                heading: is_heading(el).then(|| el.text.clone()),
+                elements: vec![],
+                score: 0.0,
+            });
+        }
+        if let Some(chunk) = chunks.last_mut() {
+            chunk.elements.push(el.clone());
+        }
+    }
+    chunks
+}
+
+// Fraction of task terms found in the chunk, with heading hits counted double and a small
+// length penalty so one huge section can't win on volume alone.
+pub fn score(chunk: &TextChunk, task: &str) -> f32 {
+    let wanted = terms(task);
+    if wanted.is_empty() {
+        return 0.0;
+    }
+    let body = terms(&chunk.text());
+    let heading = chunk.heading.as_deref().map(terms).unwrap_or_default();
+    let hits: f32 = wanted
+        .iter()
+        .map(|t| {
+            if heading.contains(t) {
+                2.0
+            } else if body.contains(t) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    hits / wanted.len() as f32 / (1.0 + (chunk.tokens() as f32).ln().max(0.0) / 10.0)
+}
+
+// The most task-relevant sections that fit in `max_tokens`, returned in page order. The first
+// section is always kept when it fits, since it usually carries the page title.
+pub fn context_slice(elements: &[TextElement], task: &str, max_tokens: usize) -> Vec<TextElement> {
+    let mut chunks: Vec<(usize, TextChunk)> = chunk_by_section(elements)
+        .into_iter()
+        .map(|mut chunk| {
+            chunk.score = score(&chunk, task);
+            chunk
+        })
+        .enumerate()
+        .collect();
+    chunks.sort_by(|(ia, a), (ib, b)| {
+        let first = |i: &usize| *i == 0;
+        first(ib)
+            .cmp(&first(ia))
+            .then(b.score.total_cmp(&a.score))
+            .then(ia.cmp(ib))
+    });
+
+    let mut budget = max_tokens;
+    let mut picked: Vec<(usize, TextChunk)> = vec![];
+    for (i, chunk) in chunks {
+        let tokens = chunk.tokens();
+        if tokens <= budget {
+            budget -= tokens;
+            picked.push((i, chunk));
+        }
+    }
+    picked.sort_by_key(|(i, _)| *i);
+    picked
+        .into_iter()
+        .flat_map(|(_, chunk)| chunk.elements)
+        .collect()
+}
+
+// How well an interactive element matches the task: task terms in its text, placeholder or
+// role, at half weight in its naming attributes and selector, plus small boosts for text
+// fields when the task involves typing and for elements near the top of the viewport.
+pub fn element_score(el: &InteractiveElement, task: &str) -> f32 {
+    let wanted = terms(task);
+    let mut score = 0.0;
+    if !wanted.is_empty() {
+        let label = terms(&format!("{} {} {}", el.text, el.placeholder, el.role));
+        let named = NAMING_ATTRIBUTES
+            .iter()
+            .filter_map(|name| el.attributes.get(*name))
+            .cloned()
+            .chain([el.selector.clone()])
+            .collect::<Vec<_>>()
+            .join(" ");
+        let named = terms(&named);
+        let hits: f32 = wanted
+            .iter()
+            .map(|t| {
+                if label.contains(t) {
+                    1.0
+                } else if named.contains(t) {
+                    0.5
+                } else {
+                    0.0
+                }
+            })
+            .sum();
+        score += hits / wanted.len() as f32;
+    }
+    if el.kind() == InteractiveElementType::Typable
+        && TYPING_WORDS.iter().any(|w| wanted.contains(*w))
+    {
+        score += 0.3;
+    }
+    if el.in_viewport {
+        score += 0.1 + 0.1 / (1.0 + el.bbox.y.max(0.0) as f32 / 1000.0);
+    }
+    if el.state.iter().any(|s| s == "disabled") {
+        score -= 0.5;
+    }
+    score
+}
+
+// Indices of `elements` from most to least useful for `task`: visible ones first, then by
+// `element_score`, ties kept in page order.
+pub fn rank_elements(elements: &[InteractiveElement], task: &str) -> Vec<usize> {
+    let scores: Vec<f32> = elements.iter().map(|el| element_score(el, task)).collect();
+    let mut order: Vec<usize> = (0..elements.len()).collect();
+    order.sort_by(|&a, &b| {
+        elements[b]
+            .visible
+            .cmp(&elements[a].visible)
+            .then(scores[b].total_cmp(&scores[a]))
+            .then(a.cmp(&b))
+    });
+    order
+}
@@ -16,6 +16,8 @@ pub struct Cli {
     pub command: Commands,
 }
 
+// Parsed once at startup, so the size of the `Run` variant does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Run a job using a URL and prompt
@@ -23,5 +25,430 @@ pub enum Commands {
         /// The starting URL
         #[arg(short, long)]
         url: String,
+
+        /// Fail immediately on selectors missing from the page instead of waiting
+        #[arg(long)]
+        strict: bool,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+
+        /// Refuse purchases, payment details and account deletion
+        #[arg(long)]
+        safe: bool,
+
+        /// Domain the agent may not visit or act on (repeatable)
+        #[arg(long = "block-domain")]
+        block_domains: Vec<String>,
+
+        /// Show each plan for approval, editing or rejection before it runs
+        #[arg(long)]
+        approve: bool,
+
+        /// Model used for planning
+        #[arg(short, long, default_value = "llama3")]
+        model: String,
+
+        /// Base URL of an OpenAI-compatible API (e.g. https://api.openai.com/v1) to plan with instead of Ollama
+        #[arg(long)]
+        openai_url: Option<String>,
+
+        /// Attach a Set-of-Marks annotated screenshot when planning (needs a vision model)
+        #[arg(long)]
+        vision: bool,
+
+        /// Plan with Anthropic's Claude API instead of Ollama
+        #[arg(long, conflicts_with = "openai_url")]
+        anthropic: bool,
+
+        /// API key for --openai-url or --anthropic (defaults to $OPENAI_API_KEY / $ANTHROPIC_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+
+    /// Work on a goal autonomously: observe, plan, act and repeat until done
+    Run {
+        /// The starting URL
+        #[arg(short, long, required_unless_present = "resume")]
+        url: Option<String>,
+
+        /// What the agent should accomplish
+        #[arg(short, long, required_unless_present = "resume")]
+        goal: Option<String>,
+
+        /// TOML agent config (model, backend, temperature, budgets, guardrails, prompts, memory);
+        /// the flags it covers cannot be given with it
+        #[arg(long)]
+        config: Option<std::path::PathBuf>,
+
+        /// Save progress to this file after every job, so an interrupted run can be resumed (single worker only)
+        #[arg(long, conflicts_with = "workers")]
+        checkpoint: Option<std::path::PathBuf>,
+
+        /// Append every plan-act round (observation, thought, actions, results) to this JSONL file; with --workers, one file per worker (run.worker1.jsonl, ...)
+        #[arg(long)]
+        trajectory: Option<std::path::PathBuf>,
+
+        /// Continue the run saved in this checkpoint file (and keep saving to it)
+        #[arg(long, conflicts_with_all = ["url", "goal", "decompose", "workers"])]
+        resume: Option<std::path::PathBuf>,
+
+        /// Maximum plan-act rounds before giving up
+        #[arg(long, default_value_t = 8, conflicts_with = "config")]
+        max_steps: usize,
+
+        /// Stop after this many browser jobs in total
+        #[arg(long, conflicts_with = "config")]
+        max_jobs: Option<usize>,
+
+        /// Stop after this many seconds of wall-clock time
+        #[arg(long, conflicts_with = "config")]
+        max_seconds: Option<u64>,
+
+        /// Stop before visiting more than this many distinct domains
+        #[arg(long, conflicts_with = "config")]
+        max_domains: Option<usize>,
+
+        /// Corrected plans to request after a job fails, before giving up on a round
+        #[arg(long, default_value_t = 2, conflicts_with = "config")]
+        replan_budget: usize,
+
+        /// Have the model check its plan against the page's elements and revise it before running
+        #[arg(long)]
+        reflect: bool,
+
+        /// After the first round on a page, send the model only what changed since its last look plus a compact element list
+        #[arg(long)]
+        diff_observations: bool,
+
+        /// Show the model at most this many interactive elements, ranked by relevance to the task
+        #[arg(long)]
+        max_elements: Option<usize>,
+
+        /// Plan through the model's native tool calling, one tool per job type
+        #[arg(long)]
+        tools: bool,
+
+        /// JSON prompt template (system, instructions, few_shots, vars) replacing the built-in planning prompt
+        #[arg(long, conflicts_with = "config")]
+        prompt_template: Option<std::path::PathBuf>,
+
+        /// JSON file of task -> plan exemplars to pick few-shot examples from
+        #[arg(long, conflicts_with = "config")]
+        examples: Option<std::path::PathBuf>,
+
+        /// Context window of the planning model in tokens; page context is trimmed to fit
+        #[arg(long, default_value_t = 8192, conflicts_with = "config")]
+        context_window: usize,
+
+        /// Context window to load an Ollama model with (also used as --context-window)
+        #[arg(long)]
+        num_ctx: Option<u64>,
+
+        /// Nucleus sampling: only sample from the most likely tokens covering this probability
+        #[arg(long)]
+        top_p: Option<f32>,
+
+        /// Only sample from this many most likely tokens
+        #[arg(long)]
+        top_k: Option<u32>,
+
+        /// Sampling seed, for repeatable plans (pair with a low temperature)
+        #[arg(long)]
+        seed: Option<i32>,
+
+        /// Stop generating at this sequence (repeatable)
+        #[arg(long)]
+        stop: Vec<String>,
+
+        /// Split the goal into subtasks, each planned, run and checked on its own
+        #[arg(long)]
+        decompose: bool,
+
+        /// Browser sessions to split independent parts of the goal across (implies decomposition)
+        #[arg(long, default_value_t = 1)]
+        workers: usize,
+
+        /// Model calls allowed in flight at once, shared by all workers
+        #[arg(long)]
+        llm_concurrency: Option<usize>,
+
+        /// Retries, with exponential backoff, for model calls rejected as rate limited or overloaded (defaults to 3, or llm_retries from --config)
+        #[arg(long)]
+        llm_retries: Option<u32>,
+
+        /// Longest a Sleep job may pause, in milliseconds
+        #[arg(long, default_value_t = 10_000)]
+        max_sleep_ms: u64,
+
+        /// Value for {{NAME}} placeholders in jobs, as NAME=VALUE (repeatable; added to the config's)
+        #[arg(long = "var", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+
+        /// Directory UploadFile jobs may read files from (repeatable); without one, uploads are refused
+        #[arg(long)]
+        upload_dir: Vec<std::path::PathBuf>,
+
+        /// Let plans run ExecuteJs jobs, which execute arbitrary JavaScript in the page
+        #[arg(long)]
+        allow_js_jobs: bool,
+
+        /// Refuse purchases, payment details and account deletion
+        #[arg(long, conflicts_with = "config")]
+        safe: bool,
+
+        /// Domain the agent may not visit or act on (repeatable)
+        #[arg(long = "block-domain", conflicts_with = "config")]
+        block_domains: Vec<String>,
+
+        /// Show each plan for approval, editing or rejection before it runs
+        #[arg(long)]
+        approve: bool,
+
+        /// Have the model screen every page for prompt injection and stop if it finds any
+        #[arg(long)]
+        detect_injection: bool,
+
+        /// Jobs the planner is less sure of than this (0-1) are handled per --low-confidence
+        #[arg(long, default_value_t = 0.0, conflicts_with = "config")]
+        min_confidence: f32,
+
+        /// What to do before a low-confidence job: verify, screenshot or confirm
+        #[arg(long, default_value = "verify", conflicts_with = "config")]
+        low_confidence: String,
+
+        /// Decide one action at a time, re-reading the page after each, instead of planning ahead
+        #[arg(long)]
+        react: bool,
+
+        /// Export tracing spans to this OTLP/HTTP traces endpoint (e.g. http://localhost:4318/v1/traces)
+        #[cfg(feature = "otel")]
+        #[arg(long)]
+        otlp_endpoint: Option<String>,
+
+        /// Model used for planning
+        #[arg(short, long, default_value = "llama3", conflicts_with = "config")]
+        model: String,
+
+        /// Base URL of an OpenAI-compatible API (e.g. https://api.openai.com/v1) to plan with instead of Ollama
+        #[arg(long, conflicts_with = "config")]
+        openai_url: Option<String>,
+
+        /// Plan with Anthropic's Claude API instead of Ollama
+        #[arg(long, conflicts_with_all = ["openai_url", "config"])]
+        anthropic: bool,
+
+        /// API key for --openai-url or --anthropic (defaults to $OPENAI_API_KEY / $ANTHROPIC_API_KEY)
+        #[arg(long, conflicts_with = "config")]
+        api_key: Option<String>,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+
+        /// Plan with this GGUF model in-process through llama.cpp instead of Ollama
+        #[cfg(feature = "llama-cpp")]
+        #[arg(long, conflicts_with_all = ["openai_url", "anthropic"])]
+        gguf: Option<std::path::PathBuf>,
+
+        /// Model layers to offload to the GPU with --gguf
+        #[cfg(feature = "llama-cpp")]
+        #[arg(long, default_value_t = 0)]
+        gpu_layers: u32,
+    },
+
+    /// Open a browser, record your clicks, typing and navigation, and save them as a job script on Ctrl+C
+    Record {
+        /// The starting URL
+        #[arg(short, long)]
+        url: String,
+
+        /// Where to save the script (.yaml/.yml for YAML, anything else for JSON)
+        #[arg(short, long)]
+        out: std::path::PathBuf,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+    },
+
+    /// Run a saved job script (YAML or JSON) without a model and print the values its jobs stored
+    Replay {
+        /// The job script
+        #[arg(short, long)]
+        file: std::path::PathBuf,
+
+        /// Value for {{NAME}} placeholders, as NAME=VALUE (repeatable; overrides the script's)
+        #[arg(long = "var", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+
+        /// Longest a Sleep job may pause, in milliseconds
+        #[arg(long, default_value_t = 10_000)]
+        max_sleep_ms: u64,
+
+        /// Directory UploadFile jobs may read files from (repeatable); without one, uploads are refused
+        #[arg(long)]
+        upload_dir: Vec<std::path::PathBuf>,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+
+        /// Let the script run ExecuteJs jobs
+        #[arg(long)]
+        allow_js_jobs: bool,
+
+        /// Only check the script: print its problems, with selectors resolved on its start page, and run nothing
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Run the agent over a JSON task suite and print per-task results and aggregate metrics
+    Eval {
+        /// Suite file: a JSON array of tasks (id, url, goal, assertions), WebArena/Mind2Web field names accepted
+        #[arg(short, long)]
+        suite: std::path::PathBuf,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+
+        /// Maximum plan-act rounds per task
+        #[arg(long, default_value_t = 8)]
+        max_steps: usize,
+
+        /// Decide one action at a time instead of planning ahead
+        #[arg(long)]
+        react: bool,
+
+        /// Plan through the model's native tool calling
+        #[arg(long)]
+        tools: bool,
+
+        /// Plan at temperature 0 with this seed, so runs of the suite are repeatable
+        #[arg(long)]
+        seed: Option<i32>,
+
+        /// JSON prompt template replacing the built-in planning prompt
+        #[arg(long)]
+        prompt_template: Option<std::path::PathBuf>,
+
+        /// JSON file of task -> plan exemplars to pick few-shot examples from
+        #[arg(long)]
+        examples: Option<std::path::PathBuf>,
+
+        /// Model used for planning
+        #[arg(short, long, default_value = "llama3")]
+        model: String,
+
+        /// Base URL of an OpenAI-compatible API (e.g. https://api.openai.com/v1) to plan with instead of Ollama
+        #[arg(long)]
+        openai_url: Option<String>,
+
+        /// Plan with Anthropic's Claude API instead of Ollama
+        #[arg(long, conflicts_with = "openai_url")]
+        anthropic: bool,
+
+        /// API key for --openai-url or --anthropic (defaults to $OPENAI_API_KEY / $ANTHROPIC_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+
+    /// Turn finished, human-approved runs from a trajectory file into fine-tuning pairs
+    Dataset {
+        /// Trajectory JSONL written by `run --trajectory`
+        #[arg(short, long)]
+        trajectory: std::path::PathBuf,
+
+        /// Where to write the training pairs (JSONL)
+        #[arg(short, long)]
+        out: std::path::PathBuf,
+
+        /// Run id to include as approved even if its plans were not gated (repeatable)
+        #[arg(long = "approve")]
+        approved_runs: Vec<String>,
+
+        /// Write chat-style {"messages": [...]} lines instead of {"prompt", "completion"}
+        #[arg(long)]
+        chat: bool,
+    },
+
+    /// Print the text of a page as JSON, without navigation, footer, ads and cookie banners
+    Extract {
+        /// The page URL
+        #[arg(short, long)]
+        url: String,
+
+        /// Keep boilerplate regions, tagged with their region
+        #[arg(long)]
+        all_regions: bool,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+    },
+
+    /// Start the bundled demo shop and run a guided sample task against it
+    Demo {
+        /// Port for the local demo server (0 picks a free port)
+        #[arg(short, long, default_value_t = 8787)]
+        port: u16,
+
+        /// Block all network traffic except WebDriver, the local LLM and allowlisted hosts
+        #[arg(long)]
+        offline_llm_only: bool,
+
+        /// Host reachable in offline mode (repeatable, supports *.domain wildcards)
+        #[arg(long = "allow-host")]
+        allow_hosts: Vec<String>,
+
+        /// Model used for planning
+        #[arg(short, long, default_value = "llama3")]
+        model: String,
+
+        /// Task for the agent to perform on the demo shop
+        #[arg(short, long, default_value = internet_use::demo::DEMO_TASK)]
+        task: String,
+
+        /// Base URL of an OpenAI-compatible API (e.g. https://api.openai.com/v1) to plan with instead of Ollama
+        #[arg(long)]
+        openai_url: Option<String>,
+
+        /// Attach a Set-of-Marks annotated screenshot when planning (needs a vision model)
+        #[arg(long)]
+        vision: bool,
+
+        /// Plan with Anthropic's Claude API instead of Ollama
+        #[arg(long, conflicts_with = "openai_url")]
+        anthropic: bool,
+
+        /// API key for --openai-url or --anthropic (defaults to $OPENAI_API_KEY / $ANTHROPIC_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
     },
 }
@@ -1,42 +1,124 @@
-use crate::types::{BrowserError, BrowserOptions, InteractiveElement, TextElement};
+use crate::content::{ContentFetcher, ContentKind, FetchedContent};
+use crate::crawler::host_of;
+use crate::extraction::ExtractionCache;
+use crate::js;
+use crate::types::{
+    BrowserError, BrowserOptions, ClientCertificate, InteractiveElement, LOOPBACK_HOSTS,
+    MarkedScreenshot, ScrollBy, SelectChoice,
+};
 
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use chrono::Local;
 use fantoccini::{
     Client, ClientBuilder,
+    actions::{InputSource, KeyAction, KeyActions, MOUSE_BUTTON_LEFT, MouseActions, PointerAction},
+    elements::Element,
+    key::Key,
     wd::{Capabilities, WindowHandle},
 };
 use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use tokio::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tokio::time::{Duration, Instant};
 
 pub struct BrowserClient {
     pub client: Client,
     pub options: BrowserOptions,
     pub current_tab: Option<WindowHandle>,
+    pub(crate) fetcher: ContentFetcher,
+    pub(crate) last_snapshot: Vec<InteractiveElement>,
+    pub(crate) cache: ExtractionCache,
+    // Profile made for a client certificate when none was given, removed with the client.
+    temp_profile: Option<tempfile::TempDir>,
+    // Path of the latest capture_screenshot, until the agent files it with a job result.
+    pub(crate) last_screenshot: Option<PathBuf>,
+    // Downloads older than this belong to earlier sessions.
+    connected_at: SystemTime,
+    // Downloads already handed out by wait_for_download, so a later wait for the same
+    // pattern gets the next file rather than the same one again.
+    claimed_downloads: HashSet<PathBuf>,
 }
 
 impl BrowserClient {
     pub async fn connect(options: BrowserOptions) -> Result<Self, BrowserError> {
         let mut caps = Capabilities::new();
 
-        let mut firefox_options = json!({
-            "args": if options.headless {
-                vec!["-headless"]
-            } else {
-                vec![]
+        let mut args: Vec<String> = vec![];
+        if options.headless {
+            args.push("-headless".into());
+        }
+
+        let mut options = options;
+        let mut temp_profile = None;
+        if options.client_certificate.is_some() && options.profile_dir.is_none() {
+            // Owner-only, since it holds the certificate's private key unencrypted.
+            let dir = tempfile::Builder::new()
+                .prefix("iu-profile-")
+                .tempdir()
+                .map_err(|e| BrowserError::ConfigError(format!("Temporary profile: {e}")))?;
+            options.profile_dir = Some(dir.path().to_string_lossy().into_owned());
+            temp_profile = Some(dir);
+        }
+        if let Some(profile) = &options.profile_dir {
+            if let Some(cert) = &options.client_certificate {
+                import_client_certificate(Path::new(profile), cert)?;
             }
-        });
+            args.push("-profile".into());
+            args.push(profile.clone());
+        }
+
+        let mut firefox_options = json!({ "args": args });
 
+        let mut prefs = serde_json::Map::new();
         if let Some(ua) = &options.user_agent {
-            firefox_options["prefs"] = json!({
-                "general.useragent.override": ua
-            });
+            prefs.insert("general.useragent.override".into(), json!(ua));
+        }
+        if options.client_certificate.is_some() {
+            prefs.insert(
+                "security.default_personal_cert".into(),
+                json!("Select Automatically"),
+            );
+        }
+        if !options.trusted_auth_uris.is_empty() {
+            let uris = options.trusted_auth_uris.join(",");
+            prefs.insert("network.negotiate-auth.trusted-uris".into(), json!(uris));
+            prefs.insert("network.negotiate-auth.delegation-uris".into(), json!(uris));
+            prefs.insert(
+                "network.automatic-ntlm-auth.trusted-uris".into(),
+                json!(uris),
+            );
+            prefs.insert(
+                "network.automatic-ntlm-auth.allow-non-fqdn".into(),
+                json!(true),
+            );
+            prefs.insert("network.negotiate-auth.allow-non-fqdn".into(), json!(true));
+        }
+        // Save downloads straight to `download_dir`, where WaitForDownload looks for them.
+        fs::create_dir_all(&options.download_dir)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", options.download_dir)))?;
+        if let Ok(dir) = fs::canonicalize(&options.download_dir) {
+            prefs.insert("browser.download.folderList".into(), json!(2));
+            prefs.insert("browser.download.dir".into(), json!(dir));
+            prefs.insert("browser.download.useDownloadDir".into(), json!(true));
+            prefs.insert(
+                "browser.download.always_ask_before_handling_new_types".into(),
+                json!(false),
+            );
+        }
+        if options.offline {
+            prefs.extend(offline_prefs(&options));
+        }
+        if !prefs.is_empty() {
+            firefox_options["prefs"] = Value::Object(prefs);
         }
 
         caps.insert("moz:firefoxOptions".to_string(), firefox_options);
 
-        if let Some(proxy) = &options.proxy {
+        // Offline mode routes traffic through a PAC script instead, see `offline_prefs`.
+        if let Some(proxy) = options.proxy.as_ref().filter(|_| !options.offline) {
             caps.insert(
                 "proxy".to_string(),
                 json!({
@@ -67,23 +149,76 @@ impl BrowserClient {
 
         let current_tab = handles.first().cloned();
 
+        let fetcher = ContentFetcher::new(&options)?;
+
         Ok(Self {
             client,
             options,
             current_tab,
+            fetcher,
+            last_snapshot: vec![],
+            temp_profile,
+            cache: ExtractionCache::default(),
+            last_screenshot: None,
+            connected_at: SystemTime::now(),
+            claimed_downloads: HashSet::new(),
         })
     }
 
-    pub async fn navigate(&mut self, url: &str) -> Result<(), BrowserError> {
+    // Loads pages in the tab as usual. URLs whose extension says PDF, image, JSON or CSV are
+    // downloaded with the tab's cookies and parsed directly instead of leaving the browser on
+    // a viewer page; when the server refuses that request or serves HTML after all, the
+    // browser loads the URL with its own session.
+    pub async fn navigate(&mut self, url: &str) -> Result<FetchedContent, BrowserError> {
+        self.ensure_allowed(url)?;
+        if ContentKind::from_extension(url).is_some() {
+            let cookies = self.cookie_header(url).await;
+            if let Some(content) = self.fetcher.fetch(url, cookies.as_deref()).await? {
+                return Ok(content);
+            }
+        }
         self.client
             .goto(url)
             .await
-            .map_err(|e| BrowserError::OperationError(e.to_string()))
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(FetchedContent::Html)
+    }
+
+    // The tab's cookies that apply to `url`, as a Cookie header value.
+    async fn cookie_header(&self, url: &str) -> Option<String> {
+        let host = host_of(url)?;
+        let page_host = self
+            .current_url()
+            .await
+            .ok()
+            .and_then(|page| host_of(&page));
+        let cookies = self.client.get_all_cookies().await.ok()?;
+        let pairs: Vec<String> = cookies
+            .iter()
+            .filter(|cookie| match cookie.domain() {
+                Some(domain) => {
+                    let domain = domain.trim_start_matches('.');
+                    host == domain || host.ends_with(&format!(".{domain}"))
+                }
+                // Host-only cookies belong to the page's own host.
+                None => page_host.as_deref() == Some(host.as_str()),
+            })
+            .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    pub fn ensure_allowed(&self, url: &str) -> Result<(), BrowserError> {
+        if self.options.allows_url(url) {
+            Ok(())
+        } else {
+            Err(BrowserError::NetworkBlocked(url.to_string()))
+        }
     }
 
     pub async fn search_duckduckgo(&mut self, query: &str) -> Result<(), BrowserError> {
         let url = format!("https://duckduckgo.com/?q={}", query);
-        self.navigate(&url).await
+        self.navigate(&url).await.map(|_| ())
     }
 
     pub async fn back(&mut self) -> Result<(), BrowserError> {
@@ -93,54 +228,521 @@ impl BrowserClient {
             .map_err(|e| BrowserError::OperationError(e.to_string()))
     }
 
-    pub async fn wait_for_element(&mut self, element: &str) -> Result<bool, BrowserError> {
-        match self
-            .client
-            .wait()
-            .for_element(fantoccini::Locator::Css(element))
+    pub async fn forward(&mut self) -> Result<(), BrowserError> {
+        self.client
+            .forward()
             .await
-        {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
-        }
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
     }
 
-    pub async fn click_element(&mut self, selector: &str) -> Result<(), BrowserError> {
-        self.wait_for_element(selector).await?;
+    pub async fn refresh(&mut self) -> Result<(), BrowserError> {
+        self.client
+            .refresh()
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
 
-        let el = self
-            .client
+    pub async fn wait_for_element(&mut self, element: &str) -> Result<bool, BrowserError> {
+        let element = self.target(element)?;
+        let css = self.enter_frames(&element).await?;
+
+        let found = match self.check_selector(&element).await {
+            Ok(()) => Ok(self
+                .client
+                .wait()
+                .at_most(self.options.timeout)
+                .for_element(fantoccini::Locator::Css(css))
+                .await
+                .is_ok()),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(&element).await?;
+        found
+    }
+
+    // Leaves the session inside the element's frame; callers must `leave_frames` when done.
+    async fn find_element(&mut self, selector: &str) -> Result<Element, BrowserError> {
+        let selector = self.target(selector)?;
+        let css = self.enter_frames(&selector).await?;
+        self.check_selector(&selector).await?;
+
+        self.client
             .wait()
-            .for_element(fantoccini::Locator::Css(selector))
+            .at_most(self.options.timeout)
+            .for_element(fantoccini::Locator::Css(css))
             .await
             .map_err(|e| {
                 BrowserError::OperationError(format!("Failed to find '{}': {}", selector, e))
+            })
+    }
+
+    async fn enter_frames<'a>(&self, selector: &'a str) -> Result<&'a str, BrowserError> {
+        let (frames, css) = split_frames(selector);
+        if !frames.is_empty() {
+            self.client
+                .enter_frame(None)
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        for index in frames {
+            self.client.enter_frame(Some(index)).await.map_err(|e| {
+                BrowserError::OperationError(format!("Failed to enter frame {index}: {e}"))
             })?;
+        }
+        Ok(css)
+    }
+
+    async fn leave_frames(&self, selector: &str) -> Result<(), BrowserError> {
+        let framed = self
+            .target(selector)
+            .is_ok_and(|s| !split_frames(&s).0.is_empty());
+        if framed {
+            self.client
+                .enter_frame(None)
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // In strict mode a selector must be in the latest snapshot or present in the DOM right
+    // now; otherwise fail fast with the closest known element as a hint.
+    async fn check_selector(&self, selector: &str) -> Result<(), BrowserError> {
+        if !self.options.strict || self.last_snapshot.iter().any(|el| el.selector == selector) {
+            return Ok(());
+        }
+
+        let present = self
+            .client
+            .find_all(fantoccini::Locator::Css(split_frames(selector).1))
+            .await
+            .map(|found| !found.is_empty())
+            .unwrap_or(false);
+        if present {
+            return Ok(());
+        }
 
-        el.click().await.map_err(|e| {
-            BrowserError::OperationError(format!("Click failed '{}': {}", selector, e))
+        Err(BrowserError::SelectorNotFound {
+            selector: selector.to_string(),
+            suggestion: self.closest_element(selector).map(|el| el.selector.clone()),
         })
     }
 
+    // Whether the selector (CSS, "@label" or framed) matches anything right now, without waiting.
+    pub async fn selector_exists(&self, selector: &str) -> Result<bool, BrowserError> {
+        let Ok(selector) = self.target(selector) else {
+            return Ok(false);
+        };
+        let css = self.enter_frames(&selector).await?;
+        let found = self
+            .client
+            .find_all(fantoccini::Locator::Css(css))
+            .await
+            .map(|found| !found.is_empty())
+            .unwrap_or(false);
+        self.leave_frames(&selector).await?;
+        Ok(found)
+    }
+
+    // One selector per element matching `selector`, each tied to its element by a data
+    // attribute so it keeps addressing the same element while others come and go.
+    pub async fn match_each(&mut self, selector: &str) -> Result<Vec<String>, BrowserError> {
+        static LOOPS: AtomicU64 = AtomicU64::new(0);
+        let selector = self.target(selector)?;
+        let css = self.enter_frames(&selector).await?;
+        let prefix = &selector[..selector.len() - css.len()];
+        let tag = r#"
+        const [css, run] = arguments;
+        return Array.from(document.querySelectorAll(css)).map((el, i) => {
+            const id = `${run}-${i}`;
+            el.setAttribute("data-iu-item", id);
+            return id;
+        });
+        "#;
+        let run = LOOPS.fetch_add(1, Ordering::Relaxed);
+        let tagged = self
+            .client
+            .execute(tag, vec![json!(css), json!(run)])
+            .await
+            .map_err(|e| {
+                BrowserError::OperationError(format!("Matching '{selector}' failed: {e}"))
+            });
+        self.leave_frames(&selector).await?;
+        Ok(tagged?
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|id| format!("{prefix}[data-iu-item='{id}']"))
+            .collect())
+    }
+
+    // The snapshot element whose selector or text is nearest `selector`, if it is near enough
+    // to be the same target: at most a third of the characters differ.
+    pub fn closest_element(&self, selector: &str) -> Option<&InteractiveElement> {
+        let needle = selector.to_lowercase();
+        let threshold = needle.chars().count() / 3;
+        self.last_snapshot
+            .iter()
+            .map(|el| {
+                let by_selector = edit_distance(&needle, &el.selector.to_lowercase());
+                let by_text = edit_distance(&needle, &el.text.to_lowercase());
+                (by_selector.min(by_text), el)
+            })
+            .filter(|(distance, _)| *distance <= threshold)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, el)| el)
+    }
+
+    pub fn last_snapshot(&self) -> &[InteractiveElement] {
+        &self.last_snapshot
+    }
+
+    pub fn resolve(&self, label: usize) -> Result<String, BrowserError> {
+        self.last_snapshot
+            .iter()
+            .find(|el| el.label == label)
+            .map(|el| el.selector.clone())
+            .ok_or_else(|| BrowserError::SelectorNotFound {
+                selector: format!("@{label}"),
+                suggestion: None,
+            })
+    }
+
+    // Jobs may address an element by its snapshot label ("@3") instead of a CSS selector.
+    fn target(&self, selector: &str) -> Result<String, BrowserError> {
+        match selector.strip_prefix('@').and_then(|l| l.parse().ok()) {
+            Some(label) => self.resolve(label),
+            None => Ok(selector.to_string()),
+        }
+    }
+
+    pub async fn click_element(&mut self, selector: &str) -> Result<(), BrowserError> {
+        let clicked = match self.find_element(selector).await {
+            Ok(el) => el.click().await.map_err(|e| {
+                BrowserError::OperationError(format!("Click failed '{}': {}", selector, e))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        clicked
+    }
+
+    pub async fn select_option(
+        &mut self,
+        selector: &str,
+        choice: &SelectChoice,
+    ) -> Result<(), BrowserError> {
+        let selected = match self.find_element(selector).await {
+            Ok(el) => match choice {
+                SelectChoice::Value(value) => el.select_by_value(value).await,
+                SelectChoice::Label(label) => el.select_by_label(label).await,
+                SelectChoice::Index(index) => el.select_by_index(*index).await,
+            }
+            .map_err(|e| {
+                BrowserError::OperationError(format!("Select {choice:?} failed '{selector}': {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        selected
+    }
+
+    // Presses `key` (a name such as "Enter" or "ArrowDown", or a single character) in the
+    // element matching `selector`, or in whatever has focus when there is none.
+    pub async fn press_key(
+        &mut self,
+        selector: Option<&str>,
+        key: &str,
+    ) -> Result<(), BrowserError> {
+        let code = key_code(key)?;
+        let Some(selector) = selector else {
+            let keys = KeyActions::new("keyboard".to_string())
+                .then(KeyAction::Down { value: code })
+                .then(KeyAction::Up { value: code });
+            return self
+                .client
+                .perform_actions(keys)
+                .await
+                .map_err(|e| BrowserError::OperationError(format!("Pressing {key} failed: {e}")));
+        };
+        let pressed = match self.find_element(selector).await {
+            Ok(el) => el.send_keys(&code.to_string()).await.map_err(|e| {
+                BrowserError::OperationError(format!("Pressing {key} failed '{selector}': {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        pressed
+    }
+
+    // Waits `ms` milliseconds, at most `options.max_sleep`.
+    pub async fn sleep(&self, ms: u64) {
+        tokio::time::sleep(Duration::from_millis(ms).min(self.options.max_sleep)).await;
+    }
+
+    // The rendered text of the element matching `selector`, or its value for form fields.
+    pub async fn element_text(&mut self, selector: &str) -> Result<String, BrowserError> {
+        let text = match self.find_element(selector).await {
+            Ok(el) => match el.text().await {
+                Ok(text) if text.trim().is_empty() => {
+                    el.prop("value").await.map(Option::unwrap_or_default)
+                }
+                other => other,
+            }
+            .map(|text| text.trim().to_string())
+            .map_err(|e| {
+                BrowserError::OperationError(format!("Reading text failed '{selector}': {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        text
+    }
+
+    // An attribute of the element matching `selector`. For href and src the browser's
+    // resolved, absolute URL is returned.
+    pub async fn element_attribute(
+        &mut self,
+        selector: &str,
+        name: &str,
+    ) -> Result<Option<String>, BrowserError> {
+        let value = match self.find_element(selector).await {
+            Ok(el) => match name {
+                "href" | "src" | "value" => el.prop(name).await,
+                _ => el.attr(name).await,
+            }
+            .map_err(|e| {
+                BrowserError::OperationError(format!("Reading {name} failed '{selector}': {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        value
+    }
+
+    // Attaches a local file to the file input matching `selector`. The file must lie inside
+    // one of `options.upload_dirs`.
+    pub async fn upload_file(&mut self, selector: &str, path: &str) -> Result<(), BrowserError> {
+        let file = self.options.upload_path(path)?;
+        let uploaded = match self.find_element(selector).await {
+            Ok(el) => el.send_keys(&file.to_string_lossy()).await.map_err(|e| {
+                BrowserError::OperationError(format!("Upload failed '{selector}': {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        uploaded
+    }
+
+    // Waits up to `timeout` for a file whose name matches `pattern` (`*` and `?` wildcards) to
+    // finish downloading into `options.download_dir` during this session, and returns its path.
+    // Each file is returned once; repeated waits on one pattern pick up successive downloads.
+    pub async fn wait_for_download(
+        &mut self,
+        pattern: &str,
+        timeout: Duration,
+    ) -> Result<PathBuf, BrowserError> {
+        let glob = regex::escape(pattern)
+            .replace("\\*", ".*")
+            .replace("\\?", ".");
+        let name = regex::Regex::new(&format!("^{glob}$")).map_err(|e| {
+            BrowserError::OperationError(format!("Invalid download pattern '{pattern}': {e}"))
+        })?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(path) = self.finished_download(&name) {
+                self.claimed_downloads.insert(path.clone());
+                return Ok(path);
+            }
+            if Instant::now() >= deadline {
+                return Err(BrowserError::OperationError(format!(
+                    "No download matching '{pattern}' finished within {}s",
+                    timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    // Firefox writes to `<name>.part` and moves it into place once the download is complete.
+    fn finished_download(&self, name: &regex::Regex) -> Option<PathBuf> {
+        let dir = Path::new(&self.options.download_dir);
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                let file = entry.file_name().to_string_lossy().into_owned();
+                name.is_match(&file)
+                    && !file.ends_with(".part")
+                    && !dir.join(format!("{file}.part")).exists()
+                    && entry
+                        .metadata()
+                        .and_then(|m| m.modified())
+                        .is_ok_and(|modified| modified >= self.connected_at)
+            })
+            .map(|entry| entry.path())
+            .filter(|path| !self.claimed_downloads.contains(path))
+            // The earliest unclaimed one, so successive waits take downloads in order.
+            .min_by_key(|path| path.metadata().and_then(|m| m.modified()).ok())
+    }
+
+    pub async fn hover(&mut self, selector: &str) -> Result<(), BrowserError> {
+        let hovered = match self.find_element(selector).await {
+            Ok(element) => {
+                let mouse =
+                    MouseActions::new("mouse".to_string()).then(PointerAction::MoveToElement {
+                        element,
+                        duration: None,
+                        x: 0,
+                        y: 0,
+                    });
+                self.client.perform_actions(mouse).await.map_err(|e| {
+                    BrowserError::OperationError(format!("Hover failed '{selector}': {e}"))
+                })
+            }
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        hovered
+    }
+
+    pub async fn click_at(&mut self, x: i64, y: i64) -> Result<(), BrowserError> {
+        let mouse = MouseActions::new("mouse".to_string())
+            .then(PointerAction::MoveTo {
+                duration: None,
+                x,
+                y,
+            })
+            .then(PointerAction::Down {
+                button: MOUSE_BUTTON_LEFT,
+            })
+            .then(PointerAction::Up {
+                button: MOUSE_BUTTON_LEFT,
+            });
+        self.client
+            .perform_actions(mouse)
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Click at ({x}, {y}) failed: {e}")))
+    }
+
     pub async fn send_keys_to_element(
         &mut self,
         selector: &str,
         text: &str,
     ) -> Result<(), BrowserError> {
-        self.wait_for_element(selector).await?;
+        let typed = match self.find_element(selector).await {
+            Ok(el) => self.type_into(&el, text).await.map_err(|e| {
+                BrowserError::OperationError(format!("Send keys failed '{}': {}", selector, e))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        typed
+    }
 
-        let el = self
+    // Sets a form field to `value` the way its kind expects: an option label or value for a
+    // <select>, "true"/"false" (or yes/no, on/off, 1/0) for a checkbox or radio button, and
+    // replaced text for anything else.
+    pub async fn fill_field(&mut self, selector: &str, value: &str) -> Result<(), BrowserError> {
+        let filled = match self.find_element(selector).await {
+            Ok(el) => self.fill_element(&el, value).await.map_err(|e| {
+                BrowserError::OperationError(format!("Filling '{selector}' failed: {e}"))
+            }),
+            Err(e) => Err(e),
+        };
+        self.leave_frames(selector).await?;
+        filled
+    }
+
+    async fn fill_element(&self, el: &Element, value: &str) -> Result<(), String> {
+        let kind = self
             .client
-            .wait()
-            .for_element(fantoccini::Locator::Css(selector))
+            .execute(
+                "const el = arguments[0]; return el.tagName === 'SELECT' ? 'select' : (el.type || '').toLowerCase();",
+                vec![json!(el)],
+            )
             .await
-            .map_err(|e| {
-                BrowserError::OperationError(format!("Failed to find '{}': {}", selector, e))
-            })?;
+            .map_err(|e| e.to_string())?;
+        match kind.as_str() {
+            Some("select") => {
+                if el.select_by_label(value).await.is_err() {
+                    el.select_by_value(value).await.map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+            Some("checkbox" | "radio") => {
+                let wanted = match value.trim().to_lowercase().as_str() {
+                    "true" | "yes" | "on" | "1" | "checked" => true,
+                    "false" | "no" | "off" | "0" | "unchecked" | "" => false,
+                    _ => return Err(format!("'{value}' is not a checked state")),
+                };
+                let checked = el.prop("checked").await.map_err(|e| e.to_string())?;
+                if (checked.as_deref() == Some("true")) != wanted {
+                    el.click().await.map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+            _ => {
+                el.clear().await.ok();
+                self.type_into(el, value).await.map_err(|e| e.to_string())
+            }
+        }
+    }
 
-        el.send_keys(text).await.map_err(|e| {
-            BrowserError::OperationError(format!("Send keys failed '{}': {}", selector, e))
-        })
+    // Required fields left empty in the form the element matching `selector` belongs to, by
+    // name, id or placeholder.
+    pub async fn missing_required(&mut self, selector: &str) -> Result<Vec<String>, BrowserError> {
+        let selector = &self.target(selector)?;
+        let css = self.enter_frames(selector).await?;
+        let js = r#"
+        const el = document.querySelector(arguments[0]);
+        const form = el && (el.form || el.closest("form"));
+        if (!form) return [];
+        return [...form.querySelectorAll("[required]")]
+            .filter(f => (f.type === "checkbox" || f.type === "radio") ? !f.checked : !f.value)
+            .map(f => f.name || f.id || f.placeholder || f.tagName.toLowerCase());
+        "#;
+        let missing = self
+            .client
+            .execute(js, vec![json!(css)])
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()));
+        self.leave_frames(selector).await?;
+        Ok(missing?
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect())
+    }
+
+    // Rich-text editors (contenteditable) often ignore synthetic key events from send_keys, so
+    // text is inserted at the end of the region through the editing command path instead.
+    async fn type_into(&self, el: &Element, text: &str) -> Result<(), fantoccini::error::CmdError> {
+        let insert = r#"
+        const [el, text] = arguments;
+        if (!el.isContentEditable) return false;
+        const doc = el.ownerDocument;
+        el.focus();
+        const selection = doc.getSelection();
+        selection.selectAllChildren(el);
+        selection.collapseToEnd();
+        if (!doc.execCommand("insertText", false, text)) {
+            el.dispatchEvent(new InputEvent("beforeinput", { inputType: "insertText", data: text, bubbles: true, cancelable: true }));
+            el.append(text);
+            el.dispatchEvent(new InputEvent("input", { inputType: "insertText", data: text, bubbles: true }));
+        }
+        return true;
+        "#;
+        let handled = self
+            .client
+            .execute(insert, vec![json!(el), json!(text)])
+            .await?;
+        if handled.as_bool() == Some(true) {
+            return Ok(());
+        }
+        el.send_keys(text).await
     }
 
     pub async fn source(&mut self) -> Result<String, BrowserError> {
@@ -151,6 +753,64 @@ impl BrowserClient {
     }
 
     pub async fn scroll_to(&mut self, selector: &str) -> Result<(), BrowserError> {
+        let selector = &self.target(selector)?;
+        let css = self.enter_frames(selector).await?;
+        let scrolled = self.scroll_in_frame(selector, css).await;
+        self.leave_frames(selector).await?;
+        scrolled
+    }
+
+    // Submits the form the element matching `selector` is in (or is), through requestSubmit so
+    // validation and submit handlers run as they would for a click.
+    pub async fn submit_form(&mut self, selector: &str) -> Result<(), BrowserError> {
+        let selector = &self.target(selector)?;
+        let css = self.enter_frames(selector).await?;
+        let js = r#"
+        const el = document.querySelector(arguments[0]);
+        const form = el && (el.form || el.closest("form"));
+        if (!form) return false;
+        if (form.requestSubmit) form.requestSubmit(); else form.submit();
+        return true;
+        "#;
+        let submitted = self
+            .client
+            .execute(js, vec![json!(css)])
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()));
+        self.leave_frames(selector).await?;
+        match submitted? {
+            Value::Bool(true) => Ok(()),
+            _ => Err(BrowserError::OperationError(format!(
+                "No form found at or around '{selector}'"
+            ))),
+        }
+    }
+
+    pub async fn scroll_by(&mut self, by: ScrollBy) -> Result<(), BrowserError> {
+        let js = r#"
+        const [kind, amount] = arguments;
+        const root = document.scrollingElement || document.documentElement;
+        if (kind === "pixels") window.scrollBy(0, amount);
+        else if (kind === "pages") window.scrollBy(0, amount * window.innerHeight);
+        else if (kind === "top") window.scrollTo(0, 0);
+        else window.scrollTo(0, root.scrollHeight);
+        "#;
+        let (kind, amount) = match by {
+            ScrollBy::Pixels(px) => ("pixels", json!(px)),
+            ScrollBy::Pages(pages) => ("pages", json!(pages)),
+            ScrollBy::Top => ("top", json!(0)),
+            ScrollBy::Bottom => ("bottom", json!(0)),
+        };
+        self.client
+            .execute(js, vec![json!(kind), amount])
+            .await
+            .map(|_| ())
+            .map_err(|e| BrowserError::OperationError(format!("Scroll {by:?} failed: {e}")))
+    }
+
+    async fn scroll_in_frame(&self, selector: &str, css: &str) -> Result<(), BrowserError> {
+        self.check_selector(selector).await?;
+
         let js = r#"
         const el = document.querySelector(arguments[0]);
         if (el) {
@@ -162,7 +822,7 @@ impl BrowserClient {
 
         let res = self
             .client
-            .execute(js, vec![serde_json::to_value(selector).unwrap()])
+            .execute(js, vec![serde_json::to_value(css).unwrap()])
             .await
             .map_err(|e| BrowserError::OperationError(e.to_string()))?;
 
@@ -191,9 +851,65 @@ impl BrowserClient {
 
         fs::write(&path, &png_data).map_err(|e| BrowserError::OperationError(e.to_string()))?;
 
+        self.last_screenshot = Some(path.clone());
         Ok(path)
     }
 
+    // PNG of the viewport for vision models; with `marked`, Set-of-Marks boxes numbered by
+    // element label are drawn for the capture and removed afterwards.
+    pub async fn screenshot_png(&mut self, marked: bool) -> Result<Vec<u8>, BrowserError> {
+        if marked {
+            self.annotate().await?;
+        }
+        let png = self
+            .client
+            .screenshot()
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()));
+        if marked {
+            self.clear_annotations().await?;
+        }
+        png
+    }
+
+    // Numbers the actionable elements in view with boxes drawn in a separate overlay layer
+    // outside <body>, so the elements themselves and extracted text stay unchanged.
+    pub async fn annotate(&mut self) -> Result<BTreeMap<usize, String>, BrowserError> {
+        let marks: BTreeMap<usize, String> = self
+            .extract_interactive_elements()
+            .await?
+            .into_iter()
+            .filter(|el| el.in_viewport && el.is_actionable())
+            .map(|el| (el.label, el.selector))
+            .collect();
+
+        let pairs: Vec<(usize, &String)> = marks.iter().map(|(l, s)| (*l, s)).collect();
+        self.client
+            .execute(js::DRAW_MARKS, vec![json!(pairs)])
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(marks)
+    }
+
+    pub async fn clear_annotations(&self) -> Result<(), BrowserError> {
+        self.client
+            .execute(r#"document.getElementById("iu-marks")?.remove();"#, vec![])
+            .await
+            .map(|_| ())
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    pub async fn capture_marked_screenshot(
+        &mut self,
+        output_dir: &Path,
+        prefix: &str,
+    ) -> Result<MarkedScreenshot, BrowserError> {
+        let marks = self.annotate().await?;
+        let shot = self.capture_screenshot(output_dir, prefix).await;
+        self.clear_annotations().await?;
+        Ok(MarkedScreenshot { path: shot?, marks })
+    }
+
     pub async fn open_tab(&mut self) -> Result<(), BrowserError> {
         self.client
             .execute("window.open('about:blank', '_blank');", vec![])
@@ -258,6 +974,12 @@ impl BrowserClient {
             .await
             .map_err(|e| BrowserError::OperationError(e.to_string()))?;
 
+        if let Some(handle) = remaining.first() {
+            self.client
+                .switch_to_window(handle.clone())
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
         self.current_tab = remaining.first().cloned();
         Ok(())
     }
@@ -285,6 +1007,15 @@ impl BrowserClient {
         }
     }
 
+    pub async fn switch_to_handle(&mut self, handle: &WindowHandle) -> Result<(), BrowserError> {
+        self.client
+            .switch_to_window(handle.clone())
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        self.current_tab = Some(handle.clone());
+        Ok(())
+    }
+
     pub async fn list_tabs(&mut self) -> Result<Vec<WindowHandle>, BrowserError> {
         self.client
             .windows()
@@ -297,78 +1028,22 @@ impl BrowserClient {
     }
 
     pub async fn shutdown(self) -> Result<(), BrowserError> {
-        self.client
+        let closed = self
+            .client
             .close()
             .await
-            .map_err(|e| BrowserError::OperationError(e.to_string()))
+            .map_err(|e| BrowserError::OperationError(e.to_string()));
+        // Removed only once Firefox has quit, so it cannot write the profile back.
+        drop(self.temp_profile);
+        closed
     }
 
-    pub async fn extract_interactive_elements(
-        &self,
-    ) -> Result<Vec<InteractiveElement>, BrowserError> {
-        let js = r##"
-        const interactive = [];
-        const elements = document.querySelectorAll("button, a, input, textarea, [onclick]");
-
-        for (const el of elements) {
-            if (!(el instanceof Element)) continue;
-            let selector = el.tagName.toLowerCase();
-            if (el.id) selector += "#" + el.id;
-            interactive.push({
-                selector,
-                tag: el.tagName,
-                text: el.innerText.trim(),
-                type: el.getAttribute("type") || "",
-                placeholder: el.getAttribute("placeholder") || ""
-            });
-        }
-        return interactive;
-        "##;
-
-        let result = self
-            .client
-            .execute(js, vec![])
-            .await
-            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
-        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
-    }
-
-    pub async fn extract_text_elements(&self) -> Result<Vec<TextElement>, BrowserError> {
-        let js = r##"
-        const texts = [];
-        const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT, {
-            acceptNode: node => {
-                if (node.parentNode &&
-                    node.parentNode.nodeName !== "SCRIPT" &&
-                    node.parentNode.nodeName !== "STYLE" &&
-                    node.textContent.trim().length > 0) {
-                    return NodeFilter.FILTER_ACCEPT;
-                }
-                return NodeFilter.FILTER_REJECT;
-            }
-        });
-
-        let index = 1;
-        let node = walker.nextNode();
-        while (node) {
-            const parent = node.parentNode;
-            const selector = parent.tagName.toLowerCase() + (parent.id ? "#" + parent.id : "");
-            texts.push({
-                selector,
-                text: node.textContent.trim(),
-                index: index++
-            });
-            node = walker.nextNode();
-        }
-        return texts;
-        "##;
-
-        let result = self
-            .client
-            .execute(js, vec![])
+    pub async fn current_url(&self) -> Result<String, BrowserError> {
+        self.client
+            .current_url()
             .await
-            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
-        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+            .map(|url| url.to_string())
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
     }
 
     pub async fn inject_js(&mut self, script: &str) -> Result<serde_json::Value, BrowserError> {
@@ -378,6 +1053,18 @@ impl BrowserClient {
             .map_err(|e| BrowserError::OperationError(format!("JS injection failed: {}", e)))
     }
 
+    // Runs `script` as a function body with `args` as `arguments`, returning what it returns.
+    pub async fn execute_js(
+        &mut self,
+        script: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, BrowserError> {
+        self.client
+            .execute(script, args)
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Script failed: {e}")))
+    }
+
     pub async fn save_local_storage(&self) -> Result<Value, BrowserError> {
         let script = r#"(() => {
             const data = {};
@@ -433,3 +1120,165 @@ impl BrowserClient {
         self.restore_local_storage(&data).await
     }
 }
+
+// Firefox reads client certificates from the profile's NSS database, so the PKCS#12 bundle is
+// imported with NSS's own tools (`certutil`/`pk12util`, shipped with nss-tools).
+fn import_client_certificate(profile: &Path, cert: &ClientCertificate) -> Result<(), BrowserError> {
+    fs::create_dir_all(profile).map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+    let db = format!("sql:{}", profile.display());
+
+    let run = |program: &str, args: &[&str]| -> Result<(), BrowserError> {
+        let output = std::process::Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| BrowserError::ConfigError(format!("Failed to run {program}: {e}")))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(BrowserError::ConfigError(format!(
+                "{program} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    };
+
+    if !profile.join("cert9.db").exists() {
+        run("certutil", &["-N", "--empty-password", "-d", &db])?;
+    }
+    // Passed in a file only we can read rather than on the command line, where `ps` shows it.
+    let mut password = tempfile::NamedTempFile::new()
+        .map_err(|e| BrowserError::ConfigError(format!("Password file: {e}")))?;
+    std::io::Write::write_all(&mut password, cert.password.as_bytes())
+        .map_err(|e| BrowserError::ConfigError(format!("Password file: {e}")))?;
+    let password_file = password.path().to_string_lossy().into_owned();
+    run(
+        "pk12util",
+        &["-i", &cert.pkcs12_path, "-d", &db, "-w", &password_file],
+    )
+}
+
+// Silences Firefox's own background traffic and blackholes every request whose host isn't
+// allowlisted, including subresources the page itself tries to load.
+fn offline_prefs(options: &BrowserOptions) -> serde_json::Map<String, Value> {
+    let route = match &options.proxy {
+        Some(proxy) => format!("PROXY {}", proxy.trim_start_matches("http://")),
+        None => "DIRECT".to_string(),
+    };
+    let pac = format!(
+        r#"function FindProxyForURL(url, host) {{
+            var loopback = {loopback};
+            var allowed = {allowed};
+            if (loopback.indexOf(host) !== -1) return "DIRECT";
+            for (var i = 0; i < allowed.length; i++) {{
+                var a = allowed[i];
+                if (host === a) return "{route}";
+                if (a.indexOf("*.") === 0 && (host === a.substring(2) || dnsDomainIs(host, a.substring(1)))) return "{route}";
+            }}
+            return "PROXY 127.0.0.1:9";
+        }}"#,
+        loopback = json!(LOOPBACK_HOSTS),
+        allowed = json!(options.allowed_hosts),
+    );
+    let pac_url = format!(
+        "data:application/x-ns-proxy-autoconfig;base64,{}",
+        BASE64.encode(pac)
+    );
+
+    let prefs = json!({
+        "network.proxy.type": 2,
+        "network.proxy.autoconfig_url": pac_url,
+        "toolkit.telemetry.enabled": false,
+        "toolkit.telemetry.unified": false,
+        "toolkit.telemetry.archive.enabled": false,
+        "datareporting.healthreport.uploadEnabled": false,
+        "datareporting.policy.dataSubmissionEnabled": false,
+        "app.normandy.enabled": false,
+        "app.shield.optoutstudies.enabled": false,
+        "app.update.enabled": false,
+        "app.update.auto": false,
+        "browser.safebrowsing.malware.enabled": false,
+        "browser.safebrowsing.phishing.enabled": false,
+        "browser.safebrowsing.downloads.enabled": false,
+        "browser.safebrowsing.downloads.remote.enabled": false,
+        "network.captive-portal-service.enabled": false,
+        "network.connectivity-service.enabled": false,
+        "network.prefetch-next": false,
+        "network.dns.disablePrefetch": true,
+        "network.http.speculative-parallel-limit": 0,
+        "browser.newtabpage.activity-stream.feeds.telemetry": false,
+        "browser.newtabpage.activity-stream.feeds.snippets": false,
+        "browser.ping-centre.telemetry": false,
+        "extensions.update.enabled": false,
+        "extensions.getAddons.cache.enabled": false,
+        "dom.push.enabled": false,
+        "geo.enabled": false,
+        // WebRTC's STUN/TURN traffic bypasses the proxy, and with it the PAC blackhole.
+        "media.peerconnection.enabled": false,
+        "media.peerconnection.ice.default_address_only": true,
+        "media.gmp-manager.url": "",
+        "services.settings.server": "",
+        "browser.search.update": false
+    });
+    prefs.as_object().cloned().unwrap_or_default()
+}
+
+// WebDriver's code for a key name, matched case-insensitively. Single characters stand for
+// themselves.
+fn key_code(name: &str) -> Result<char, BrowserError> {
+    let key = match name.to_lowercase().replace([' ', '_', '-'], "").as_str() {
+        "enter" => Key::Enter,
+        "return" => Key::Return,
+        "escape" | "esc" => Key::Escape,
+        "tab" => Key::Tab,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "space" | "spacebar" => Key::Space,
+        "arrowup" | "up" => Key::Up,
+        "arrowdown" | "down" => Key::Down,
+        "arrowleft" | "left" => Key::Left,
+        "arrowright" | "right" => Key::Right,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "home" => Key::Home,
+        "end" => Key::End,
+        _ => {
+            let mut chars = name.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(BrowserError::OperationError(format!(
+                    "Unknown key '{name}'"
+                ))),
+            };
+        }
+    };
+    Ok(key.into())
+}
+
+// "frame[0] >> frame[2] >> button#ok" -> ([0, 2], "button#ok")
+fn split_frames(selector: &str) -> (Vec<u16>, &str) {
+    let mut frames = vec![];
+    let mut rest = selector;
+    while let Some((index, tail)) = rest
+        .strip_prefix("frame[")
+        .and_then(|r| r.split_once("] >> "))
+        .and_then(|(i, tail)| Some((i.parse().ok()?, tail)))
+    {
+        frames.push(index);
+        rest = tail;
+    }
+    (frames, rest)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,319 @@
+use ollama_rs::Ollama;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::agent::Agent;
+use crate::injection::InjectionGuard;
+use crate::limits::RunLimits;
+use crate::llm::{AnthropicClient, LlmBackend, OllamaBackend, OpenAiClient, RetryPolicy};
+use crate::policy::Policy;
+use crate::prompts::{ExampleLibrary, PromptTemplate};
+use crate::types::{AgentMemory, BrowserError, MemoryOptions};
+
+// Agent settings read from a TOML file by `Agent::from_config`. Anything left out keeps the
+// `Agent::new` default. Relative paths are resolved against the file's directory.
+//
+//     model = "llama3"
+//     temperature = 0.2
+//     seed = 42
+//
+//     [backend]
+//     kind = "openai"
+//     url = "https://api.openai.com/v1"
+//     api_key_env = "OPENAI_API_KEY"
+//
+//     [[fallbacks]]
+//     model = "llama3"
+//     kind = "ollama"
+//
+//     [budgets]
+//     max_steps = 12
+//     max_jobs = 60
+//
+//     [guardrails]
+//     safe = true
+//     blocked_domains = ["paypal.com"]
+//     injection = { detect = true }
+//
+//     [prompts]
+//     template = "prompt.json"
+//
+//     [memory]
+//     max_entries = 100
+//
+//     [variables]
+//     query = "rust"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub seed: Option<i32>,
+    pub num_ctx: Option<u64>,
+    pub stop: Vec<String>,
+    pub backend: BackendConfig,
+    // Models tried in order when the main one fails, times out or returns unparseable output.
+    pub fallbacks: Vec<FallbackConfig>,
+    pub budgets: BudgetConfig,
+    pub guardrails: GuardrailConfig,
+    pub prompts: PromptConfig,
+    pub memory: MemoryOptions,
+    // Values for `{{name}}` placeholders in jobs.
+    pub variables: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Ollama,
+    OpenAi,
+    Anthropic,
+    // A GGUF file run in-process; needs the `llama-cpp` feature.
+    #[serde(rename = "llama-cpp")]
+    LlamaCpp,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackendConfig {
+    pub kind: BackendKind,
+    // Ollama endpoint or OpenAI-compatible base URL.
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+    // Environment variable holding the API key, so it need not be written to the file.
+    pub api_key_env: Option<String>,
+    // GGUF model file for kind = "llama-cpp", and how many of its layers to put on the GPU.
+    pub model_path: Option<PathBuf>,
+    pub gpu_layers: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub model: String,
+    #[serde(flatten)]
+    pub backend: BackendConfig,
+}
+
+impl BackendConfig {
+    pub fn backend(&self) -> Result<Box<dyn LlmBackend>, BrowserError> {
+        let api_key = self.api_key.clone().or_else(|| {
+            let var = self.api_key_env.as_deref().unwrap_or(match self.kind {
+                BackendKind::Anthropic => "ANTHROPIC_API_KEY",
+                _ => "OPENAI_API_KEY",
+            });
+            std::env::var(var).ok()
+        });
+        Ok(match self.kind {
+            BackendKind::Ollama => match &self.url {
+                Some(url) => Box::new(OllamaBackend::new(
+                    Ollama::try_new(url).map_err(|e| BrowserError::ConfigError(e.to_string()))?,
+                )),
+                None => Box::new(OllamaBackend::default()),
+            },
+            BackendKind::OpenAi => {
+                let url = self.url.as_deref().ok_or_else(|| {
+                    BrowserError::ConfigError("url is required for kind = \"openai\"".into())
+                })?;
+                Box::new(OpenAiClient::new(url, api_key.as_deref()))
+            }
+            BackendKind::Anthropic => {
+                let key = api_key.ok_or_else(|| {
+                    BrowserError::ConfigError(
+                        "kind = \"anthropic\" needs api_key or $ANTHROPIC_API_KEY".into(),
+                    )
+                })?;
+                let client = AnthropicClient::new(&key);
+                Box::new(match &self.url {
+                    Some(url) => client.with_base_url(url),
+                    None => client,
+                })
+            }
+            #[cfg(feature = "llama-cpp")]
+            BackendKind::LlamaCpp => {
+                let path = self.model_path.as_ref().ok_or_else(|| {
+                    BrowserError::ConfigError(
+                        "model_path is required for kind = \"llama-cpp\"".into(),
+                    )
+                })?;
+                Box::new(crate::llama_cpp::LlamaCppBackend::load(
+                    path,
+                    self.gpu_layers,
+                )?)
+            }
+            #[cfg(not(feature = "llama-cpp"))]
+            BackendKind::LlamaCpp => {
+                return Err(BrowserError::ConfigError(
+                    "kind = \"llama-cpp\" needs a build with the llama-cpp feature".into(),
+                ));
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    pub context_window: Option<usize>,
+    // Seconds a single model call may take before the next fallback is tried.
+    pub llm_timeout_seconds: Option<u64>,
+    // Retries for calls rejected as rate limited or overloaded.
+    pub llm_retries: Option<u32>,
+    pub max_steps: Option<usize>,
+    pub replan_budget: Option<usize>,
+    pub max_jobs: Option<usize>,
+    pub max_seconds: Option<u64>,
+    pub max_domains: Option<usize>,
+    // Send diffs of the page instead of full dumps after the first round; see
+    // `Agent::observation_diffs`.
+    pub observation_diffs: bool,
+    // Interactive elements shown per prompt, the most task-relevant first.
+    pub max_elements: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardrailConfig {
+    // Turns on all of the built-in guards of `Policy::safe`.
+    pub safe: bool,
+    #[serde(flatten)]
+    pub policy: Policy,
+    pub min_confidence: Option<f32>,
+    // verify, screenshot or confirm.
+    pub low_confidence: Option<String>,
+    pub injection: InjectionGuard,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    pub template: Option<PathBuf>,
+    pub examples: Option<PathBuf>,
+    pub example_count: Option<usize>,
+}
+
+impl AgentConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BrowserError> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+        let mut config: Self = toml::from_str(&data).map_err(|e| {
+            BrowserError::ConfigError(format!("Invalid config {}: {e}", path.display()))
+        })?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let model_paths = config
+            .fallbacks
+            .iter_mut()
+            .map(|fallback| &mut fallback.backend.model_path);
+        for file in [
+            &mut config.prompts.template,
+            &mut config.prompts.examples,
+            &mut config.backend.model_path,
+        ]
+        .into_iter()
+        .chain(model_paths)
+        .flatten()
+        {
+            *file = dir.join(&*file);
+        }
+        Ok(config)
+    }
+
+    pub fn into_agent(self) -> Result<Agent, BrowserError> {
+        let model = self.model.as_deref().unwrap_or("llama3");
+        let mut agent = Agent::new(model, AgentMemory::new(self.memory))
+            .with_boxed_backend(self.backend.backend()?);
+        for fallback in &self.fallbacks {
+            agent = agent.with_boxed_fallback(&fallback.model, fallback.backend.backend()?);
+        }
+
+        if let Some(temperature) = self.temperature {
+            agent = agent.with_temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            agent = agent.with_top_p(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            agent = agent.with_top_k(top_k);
+        }
+        if let Some(seed) = self.seed {
+            agent = agent.with_seed(seed);
+        }
+        if !self.stop.is_empty() {
+            agent = agent.with_stop(self.stop);
+        }
+
+        let budgets = self.budgets;
+        if let Some(tokens) = self.num_ctx {
+            agent = agent.with_num_ctx(tokens);
+        }
+        if let Some(tokens) = budgets.context_window {
+            agent = agent.with_context_window(tokens);
+        }
+        if let Some(seconds) = budgets.llm_timeout_seconds {
+            agent = agent.with_llm_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(retries) = budgets.llm_retries {
+            agent = agent.with_retry(RetryPolicy {
+                max_retries: retries,
+                ..RetryPolicy::default()
+            });
+        }
+        if let Some(steps) = budgets.max_steps {
+            agent = agent.with_max_steps(steps);
+        }
+        if let Some(replans) = budgets.replan_budget {
+            agent = agent.with_replan_budget(replans);
+        }
+        agent = agent.with_observation_diffs(budgets.observation_diffs);
+        if let Some(max) = budgets.max_elements {
+            agent = agent.with_max_elements(max);
+        }
+        agent = agent.with_limits(RunLimits {
+            max_jobs: budgets.max_jobs,
+            max_duration: budgets.max_seconds.map(Duration::from_secs),
+            max_domains: budgets.max_domains,
+        });
+
+        let guardrails = self.guardrails;
+        let policy = if guardrails.safe {
+            Policy {
+                block_purchases: true,
+                block_payment_forms: true,
+                block_account_deletion: true,
+                ..guardrails.policy
+            }
+        } else {
+            guardrails.policy
+        };
+        agent = agent
+            .with_policy(policy)
+            .with_injection_guard(guardrails.injection);
+        if guardrails.min_confidence.is_some() || guardrails.low_confidence.is_some() {
+            let action = match &guardrails.low_confidence {
+                Some(action) => action.parse()?,
+                None => Default::default(),
+            };
+            agent = agent.with_confidence_gate(guardrails.min_confidence.unwrap_or(0.0), action);
+        }
+
+        let prompts = self.prompts;
+        if let Some(path) = &prompts.template {
+            agent = agent.with_prompt_template(PromptTemplate::from_file(path)?);
+        }
+        if let Some(path) = &prompts.examples {
+            agent = agent.with_examples(ExampleLibrary::from_file(path)?);
+        }
+        if let Some(n) = prompts.example_count {
+            agent = agent.with_example_count(n);
+        }
+        for (name, value) in self.variables {
+            agent = agent.with_variable(&name, value);
+        }
+        Ok(agent)
+    }
+}
@@ -0,0 +1,302 @@
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_TYPE, COOKIE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::crawler::fnv1a;
+use crate::documents::{self, Document, DocumentOptions};
+use crate::types::{BrowserError, BrowserOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentKind {
+    Html,
+    Pdf,
+    Image,
+    Json,
+    Csv,
+    Text,
+    Other,
+}
+
+impl ContentKind {
+    pub fn from_mime(mime: &str) -> Self {
+        let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => ContentKind::Html,
+            "application/pdf" => ContentKind::Pdf,
+            "application/json" | "text/json" => ContentKind::Json,
+            "text/csv" | "application/csv" => ContentKind::Csv,
+            m if m.starts_with("image/") => ContentKind::Image,
+            m if m.ends_with("+json") => ContentKind::Json,
+            m if m.starts_with("text/") => ContentKind::Text,
+            _ => ContentKind::Other,
+        }
+    }
+
+    pub fn from_extension(url: &str) -> Option<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let ext = path.rsplit('.').next()?.to_lowercase();
+        match ext.as_str() {
+            "pdf" => Some(ContentKind::Pdf),
+            "json" => Some(ContentKind::Json),
+            "csv" => Some(ContentKind::Csv),
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "bmp" => Some(ContentKind::Image),
+            "txt" => Some(ContentKind::Text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FetchedContent {
+    Html,
+    Pdf {
+        path: PathBuf,
+        document: Option<Document>,
+    },
+    Image {
+        path: PathBuf,
+        mime: String,
+    },
+    Json(Value),
+    Csv {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    Text(String),
+    Binary {
+        path: PathBuf,
+        mime: String,
+    },
+}
+
+impl FetchedContent {
+    pub fn kind(&self) -> ContentKind {
+        match self {
+            FetchedContent::Html => ContentKind::Html,
+            FetchedContent::Pdf { .. } => ContentKind::Pdf,
+            FetchedContent::Image { .. } => ContentKind::Image,
+            FetchedContent::Json(_) => ContentKind::Json,
+            FetchedContent::Csv { .. } => ContentKind::Csv,
+            FetchedContent::Text(_) => ContentKind::Text,
+            FetchedContent::Binary { .. } => ContentKind::Other,
+        }
+    }
+}
+
+pub struct ContentFetcher {
+    http: reqwest::Client,
+    download_dir: PathBuf,
+}
+
+impl ContentFetcher {
+    pub fn new(options: &BrowserOptions) -> Result<Self, BrowserError> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(ua) = &options.user_agent {
+            builder = builder.user_agent(ua);
+        }
+        if let Some(proxy) = &options.proxy {
+            let proxy =
+                reqwest::Proxy::all(proxy).map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(cert) = &options.client_certificate {
+            let der = fs::read(&cert.pkcs12_path)
+                .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", cert.pkcs12_path)))?;
+            let identity = reqwest::Identity::from_pkcs12_der(&der, &cert.password)
+                .map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+            builder = builder.identity(identity);
+        }
+        let http = builder
+            .build()
+            .map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+
+        Ok(Self {
+            http,
+            download_dir: PathBuf::from(&options.download_dir),
+        })
+    }
+
+    // Downloads `url`, sending `cookies` as the Cookie header, and parses it by its
+    // Content-Type, or by the URL's extension without one. None means the browser should load
+    // it instead: the server refused the request (401/403, a login wall or NTLM) or answered
+    // with an HTML page.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        cookies: Option<&str>,
+    ) -> Result<Option<FetchedContent>, BrowserError> {
+        let mut request = self.http.get(url);
+        if let Some(cookies) = cookies {
+            request = request.header(COOKIE, cookies);
+        }
+        let res = request
+            .send()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Download failed '{url}': {e}")))?;
+        if matches!(
+            res.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            return Ok(None);
+        }
+        let res = res
+            .error_for_status()
+            .map_err(|e| BrowserError::OperationError(format!("Download failed '{url}': {e}")))?;
+        let mime = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let kind = match mime.as_str() {
+            "" => ContentKind::from_extension(url).unwrap_or(ContentKind::Other),
+            mime => ContentKind::from_mime(mime),
+        };
+        if kind == ContentKind::Html {
+            return Ok(None);
+        }
+        let mime = mime.as_str();
+
+        let bytes = res
+            .bytes()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Download failed '{url}': {e}")))?;
+
+        let content = match kind {
+            ContentKind::Json => serde_json::from_slice(&bytes)
+                .map(FetchedContent::Json)
+                .map_err(|e| BrowserError::OperationError(format!("Invalid JSON at '{url}': {e}"))),
+            ContentKind::Csv => parse_csv(&bytes),
+            ContentKind::Text => Ok(FetchedContent::Text(
+                String::from_utf8_lossy(&bytes).into_owned(),
+            )),
+            ContentKind::Pdf => {
+                let path = self.save(url, "pdf", &bytes)?;
+                let document = documents::parse_pdf(&bytes, &DocumentOptions::default())
+                    .ok()
+                    .map(|document| Document {
+                        source: Some(path.clone()),
+                        ..document
+                    });
+                Ok(FetchedContent::Pdf { path, document })
+            }
+            ContentKind::Image => {
+                let path = self.save(url, &extension_for(mime), &bytes)?;
+                Ok(FetchedContent::Image {
+                    path,
+                    mime: mime.to_string(),
+                })
+            }
+            ContentKind::Html | ContentKind::Other => {
+                let path = self.save(url, "bin", &bytes)?;
+                Ok(FetchedContent::Binary {
+                    path,
+                    mime: mime.to_string(),
+                })
+            }
+        };
+        content.map(Some)
+    }
+
+    pub async fn download(&self, url: &str) -> Result<PathBuf, BrowserError> {
+        let (bytes, mime) = self.fetch_bytes(url).await?;
+        self.save(url, &extension_for(&mime), &bytes)
+    }
+
+    pub async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, String), BrowserError> {
+        let res = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| BrowserError::OperationError(format!("Download failed '{url}': {e}")))?;
+        let mime = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let bytes = res
+            .bytes()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Download failed '{url}': {e}")))?;
+        Ok((bytes.to_vec(), mime))
+    }
+
+    // Named after the URL's last path segment plus a hash of the whole URL, so same-named
+    // files from different paths or queries (`/a/thumb.jpg`, `/b/thumb.jpg`) don't overwrite
+    // each other.
+    fn save(&self, url: &str, fallback_ext: &str, bytes: &[u8]) -> Result<PathBuf, BrowserError> {
+        fs::create_dir_all(&self.download_dir)
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+
+        let last = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .unwrap_or_default();
+        let (stem, ext) = match last.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => (stem, ext),
+            _ if !last.is_empty() => (last, fallback_ext),
+            _ => ("download", fallback_ext),
+        };
+        let clean = |part: &str| {
+            part.chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>()
+        };
+        let hash = fnv1a(url.as_bytes(), 0xcbf2_9ce4_8422_2325) >> 32;
+        let name = format!("{}-{hash:08x}.{}", clean(stem), clean(ext));
+
+        let path = self.download_dir.join(name);
+        fs::write(&path, bytes).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(path)
+    }
+}
+
+// File extension for a MIME type: "image/svg+xml" is svg, "image/jpeg" jpg.
+fn extension_for(mime: &str) -> String {
+    let subtype = mime
+        .split(';')
+        .next()
+        .and_then(|mime| mime.trim().rsplit_once('/'))
+        .map(|(_, subtype)| subtype.to_lowercase())
+        .unwrap_or_default();
+    let subtype = subtype.split('+').next().unwrap_or_default();
+    let subtype = subtype.strip_prefix("x-").unwrap_or(subtype);
+    match subtype {
+        "" | "octet-stream" => "bin",
+        "jpeg" | "pjpeg" => "jpg",
+        "plain" => "txt",
+        "vnd.microsoft.icon" => "ico",
+        "tiff" => "tif",
+        other => other,
+    }
+    .to_string()
+}
+
+fn parse_csv(bytes: &[u8]) -> Result<FetchedContent, BrowserError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(bytes);
+    let headers = reader
+        .headers()
+        .map_err(|e| BrowserError::OperationError(e.to_string()))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let rows = reader
+        .records()
+        .filter_map(Result::ok)
+        .map(|record| record.iter().map(str::to_string).collect())
+        .collect();
+    Ok(FetchedContent::Csv { headers, rows })
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::BrowserJob;
+
+// One exchange in a chat session: what the user asked and what the agent did about it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    pub user: String,
+    pub url: String,
+    pub todo: String,
+    pub jobs: Vec<BrowserJob>,
+    pub error: Option<String>,
+}
+
+// Chat history for follow-up prompts ("now sort by price", "go back and try the second
+// result"). Kept apart from `AgentMemory`, which records individual jobs rather than requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub turns: Vec<Turn>,
+    // Only the latest turns go into the prompt.
+    pub max_turns: usize,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            turns: vec![],
+            max_turns: 10,
+        }
+    }
+}
+
+impl Conversation {
+    pub fn push(&mut self, turn: Turn) {
+        self.turns.push(turn);
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    pub fn transcript(&self) -> String {
+        let skip = self.turns.len().saturating_sub(self.max_turns);
+        self.turns
+            .iter()
+            .skip(skip)
+            .map(|turn| {
+                let outcome = match &turn.error {
+                    Some(e) => format!("failed: {e}"),
+                    None => "done".to_string(),
+                };
+                format!(
+                    "User: {}\nAgent (on {}):\n{}\nOutcome: {outcome}",
+                    turn.user, turn.url, turn.todo
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    // The task to plan for `message`, with earlier turns as context when there are any.
+    pub fn prompt_for(&self, message: &str) -> String {
+        if self.is_empty() {
+            return message.to_string();
+        }
+        format!(
+            "Earlier in this conversation:\n{}\n\nThe user now asks: {message}\nResolve references like \"it\" or \"the second result\" using the earlier turns.",
+            self.transcript()
+        )
+    }
+}
@@ -0,0 +1,126 @@
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::BrowserClient;
+use crate::agent::{Agent, Subtask, TaskReport};
+use crate::budget;
+use crate::types::{BrowserError, TextRegion};
+
+// Page text kept per subtask for merging.
+const FINDINGS_TOKENS: usize = 800;
+
+// An agent with a browser session of its own.
+pub struct Worker {
+    pub agent: Agent,
+    pub client: BrowserClient,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkerResult {
+    pub subtask: Subtask,
+    pub report: Option<TaskReport>,
+    pub error: Option<String>,
+    // Main text of the page the worker finished on.
+    pub findings: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CoordinatedReport {
+    pub goal: String,
+    pub results: Vec<WorkerResult>,
+    pub answer: String,
+}
+
+// Fans independent subtasks out over a pool of workers (e.g. checking three retailers at
+// once) and merges what they found. The first worker's agent decomposes and merges.
+pub struct Coordinator {
+    pub workers: Vec<Worker>,
+}
+
+impl Coordinator {
+    pub fn new(workers: Vec<Worker>) -> Self {
+        Self { workers }
+    }
+
+    pub async fn run(&mut self, goal: &str) -> Result<CoordinatedReport, BrowserError> {
+        let lead = &self
+            .workers
+            .first()
+            .ok_or_else(|| BrowserError::ConfigError("Coordinator has no workers".into()))?
+            .agent;
+        let subtasks = lead.decompose_independent(goal).await?;
+
+        let results = self.run_subtasks(subtasks).await;
+
+        let findings: Vec<(Subtask, String)> = results
+            .iter()
+            .map(|r| (r.subtask.clone(), r.findings.clone()))
+            .collect();
+        let answer = self.workers[0]
+            .agent
+            .merge_findings(goal, &findings)
+            .await?;
+        Ok(CoordinatedReport {
+            goal: goal.to_string(),
+            results,
+            answer,
+        })
+    }
+
+    // Each idle worker takes the next subtask; results come back in subtask order. A failing
+    // subtask is reported on its result rather than stopping the others.
+    pub async fn run_subtasks(&mut self, subtasks: Vec<Subtask>) -> Vec<WorkerResult> {
+        let mut pending = subtasks.into_iter().enumerate();
+        let mut idle: Vec<&mut Worker> = self.workers.iter_mut().collect();
+        let mut in_flight = FuturesUnordered::new();
+        let mut results = vec![];
+
+        loop {
+            while !idle.is_empty() {
+                let Some((i, subtask)) = pending.next() else {
+                    break;
+                };
+                let worker = idle.pop().expect("idle worker available");
+                in_flight.push(run_subtask(worker, i, subtask));
+            }
+            let Some((worker, i, result)) = in_flight.next().await else {
+                break;
+            };
+            idle.push(worker);
+            results.push((i, result));
+        }
+
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+async fn run_subtask(
+    worker: &mut Worker,
+    index: usize,
+    subtask: Subtask,
+) -> (&mut Worker, usize, WorkerResult) {
+    let task = format!("{}\n(Done when: {})", subtask.goal, subtask.success_check);
+    let (report, error) = match worker.agent.run_task(&task, &mut worker.client).await {
+        Ok(report) => (Some(report), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let findings = match worker.client.extract_text_elements().await {
+        Ok(text) => {
+            let text: Vec<&str> = text
+                .iter()
+                .filter(|el| el.region == TextRegion::Content)
+                .map(|el| el.text.as_str())
+                .collect();
+            budget::truncate_tokens(&text.join("\n"), FINDINGS_TOKENS)
+        }
+        Err(e) => format!("(page text unavailable: {e})"),
+    };
+    let result = WorkerResult {
+        subtask,
+        report,
+        error,
+        findings,
+    };
+    (worker, index, result)
+}
@@ -0,0 +1,659 @@
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::BrowserClient;
+use crate::content::{ContentKind, FetchedContent};
+use crate::types::BrowserError;
+
+//
+// ---------- Visited Tracking ----------
+//
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    pub fn with_capacity(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 16.0) as u32;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        for bit in self.bit_positions(item) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.bit_positions(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bit_positions(&self, item: &str) -> impl Iterator<Item = u64> + use<> {
+        // Kirsch-Mitzenmacher double hashing over two independent FNV seeds.
+        let h1 = fnv1a(item.as_bytes(), 0xcbf2_9ce4_8422_2325);
+        let h2 = fnv1a(item.as_bytes(), 0x8422_2325_cbf2_9ce4) | 1;
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    bytes.iter().fold(seed, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisitedSet {
+    bloom: BloomFilter,
+    // URLs are marked when enqueued, so the frontier never holds duplicates. Their exact
+    // fingerprints live on disk, in one file per leading byte, and are only read when the bloom
+    // filter reports a hit, so memory stays at the filter's size however long the crawl runs.
+    dir: Option<PathBuf>,
+    len: usize,
+    // Owns `dir` when the crawl is not persisted, removing it with the set.
+    #[serde(skip)]
+    temp: Option<Arc<tempfile::TempDir>>,
+}
+
+impl VisitedSet {
+    pub fn new(expected_urls: usize) -> Self {
+        Self {
+            bloom: BloomFilter::with_capacity(expected_urls, 0.01),
+            dir: None,
+            len: 0,
+            temp: None,
+        }
+    }
+
+    // Keeps the fingerprints in `dir`, so they outlive the process along with a checkpoint.
+    pub fn store_in(&mut self, dir: &Path) {
+        self.dir = Some(dir.to_path_buf());
+        self.temp = None;
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        if !self.bloom.contains(url) {
+            return false;
+        }
+        // A disk error leaves the bloom filter's answer, which may skip a page but never loops.
+        self.stored(fingerprint(url)).unwrap_or(true)
+    }
+
+    pub fn insert(&mut self, url: &str) -> bool {
+        if self.contains(url) {
+            return false;
+        }
+        if let Err(e) = self.store(fingerprint(url)) {
+            eprintln!("Failed to record visited URL {url}: {e}");
+        }
+        self.bloom.insert(url);
+        self.len += 1;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn bucket(dir: &Path, fingerprint: u64) -> PathBuf {
+        dir.join(format!("{:02x}", fingerprint >> 56))
+    }
+
+    fn stored(&self, fingerprint: u64) -> std::io::Result<bool> {
+        let Some(dir) = &self.dir else {
+            return Ok(false);
+        };
+        let data = match fs::read(Self::bucket(dir, fingerprint)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let needle = fingerprint.to_le_bytes();
+        Ok(data.chunks_exact(8).any(|chunk| chunk == needle))
+    }
+
+    fn store(&mut self, fingerprint: u64) -> std::io::Result<()> {
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let temp = tempfile::Builder::new().prefix("iu-visited-").tempdir()?;
+                let dir = temp.path().to_path_buf();
+                self.temp = Some(Arc::new(temp));
+                self.dir = Some(dir.clone());
+                dir
+            }
+        };
+        fs::create_dir_all(&dir)?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::bucket(&dir, fingerprint))?;
+        std::io::Write::write_all(&mut file, &fingerprint.to_le_bytes())
+    }
+}
+
+fn fingerprint(url: &str) -> u64 {
+    fnv1a(url.as_bytes(), 0x1000_0000_01b3)
+}
+
+//
+// ---------- Frontier ----------
+//
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FrontierEntry {
+    pub url: String,
+    pub depth: usize,
+}
+
+// Entries are sharded per host and served round-robin, so one dominant site cannot starve
+// the rest of the frontier.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frontier {
+    shards: HashMap<String, VecDeque<FrontierEntry>>,
+    rotation: VecDeque<String>,
+    len: usize,
+}
+
+impl Frontier {
+    pub fn push(&mut self, entry: FrontierEntry) {
+        let host = host_of(&entry.url).unwrap_or_default();
+        let shard = self.shards.entry(host.clone()).or_default();
+        if shard.is_empty() {
+            self.rotation.push_back(host);
+        }
+        shard.push_back(entry);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<FrontierEntry> {
+        self.pop_where(|_| true)
+    }
+
+    // Pops from the first host in rotation order that `is_ready` accepts.
+    pub fn pop_where<F>(&mut self, is_ready: F) -> Option<FrontierEntry>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let position = self.rotation.iter().position(|host| is_ready(host))?;
+        let host = self.rotation.remove(position)?;
+        let shard = self.shards.get_mut(&host)?;
+        let entry = shard.pop_front();
+
+        if shard.is_empty() {
+            self.shards.remove(&host);
+        } else {
+            self.rotation.push_back(host);
+        }
+        if entry.is_some() {
+            self.len -= 1;
+        }
+        entry
+    }
+
+    pub fn hosts(&self) -> impl Iterator<Item = &String> {
+        self.rotation.iter()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &FrontierEntry> {
+        self.shards.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HostScheduler {
+    per_host_limit: usize,
+    politeness_delay: Duration,
+    in_flight: HashMap<String, usize>,
+    last_fetch: HashMap<String, Instant>,
+}
+
+impl HostScheduler {
+    pub fn new(per_host_limit: usize, politeness_delay: Duration) -> Self {
+        Self {
+            per_host_limit: per_host_limit.max(1),
+            politeness_delay,
+            in_flight: HashMap::new(),
+            last_fetch: HashMap::new(),
+        }
+    }
+
+    pub fn is_ready(&self, host: &str) -> bool {
+        self.ready_in(host) == Some(Duration::ZERO)
+    }
+
+    // `None` while the host is at its concurrency limit, otherwise the remaining politeness wait.
+    pub fn ready_in(&self, host: &str) -> Option<Duration> {
+        if self.in_flight.get(host).copied().unwrap_or(0) >= self.per_host_limit {
+            return None;
+        }
+        Some(match self.last_fetch.get(host) {
+            Some(last) => self.politeness_delay.saturating_sub(last.elapsed()),
+            None => Duration::ZERO,
+        })
+    }
+
+    pub fn acquire(&mut self, host: &str) {
+        *self.in_flight.entry(host.to_string()).or_default() += 1;
+        self.last_fetch.insert(host.to_string(), Instant::now());
+    }
+
+    pub fn release(&mut self, host: &str) {
+        if let Some(count) = self.in_flight.get_mut(host) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlState {
+    pub frontier: Frontier,
+    pub visited: VisitedSet,
+    pub pages_crawled: usize,
+    // Entries still being fetched when the state was saved; a resumed crawl fetches them again.
+    #[serde(default)]
+    pub in_flight: Vec<FrontierEntry>,
+}
+
+impl CrawlState {
+    pub fn new(expected_urls: usize) -> Self {
+        Self {
+            frontier: Frontier::default(),
+            visited: VisitedSet::new(expected_urls),
+            pages_crawled: 0,
+            in_flight: vec![],
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), BrowserError> {
+        let json =
+            serde_json::to_string(self).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        // Write-then-rename so an interrupted checkpoint never corrupts the previous one.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        fs::rename(&tmp, path).map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BrowserError> {
+        let data =
+            fs::read_to_string(path).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        serde_json::from_str(&data).map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+}
+
+//
+// ---------- Crawler ----------
+//
+#[derive(Debug, Clone)]
+pub struct CrawlOptions {
+    pub max_pages: usize,
+    pub max_depth: usize,
+    pub same_host_only: bool,
+    pub expected_urls: usize,
+    pub state_path: Option<PathBuf>,
+    pub checkpoint_every: usize,
+    pub per_host_limit: usize,
+    pub politeness_delay: Duration,
+    // Leave out links marked rel=nofollow.
+    pub skip_nofollow: bool,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: 100,
+            max_depth: 3,
+            same_host_only: true,
+            expected_urls: 50_000,
+            state_path: None,
+            checkpoint_every: 25,
+            per_host_limit: 1,
+            politeness_delay: Duration::from_millis(1000),
+            skip_nofollow: false,
+        }
+    }
+}
+
+impl CrawlOptions {
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn same_host_only(mut self, enabled: bool) -> Self {
+        self.same_host_only = enabled;
+        self
+    }
+
+    pub fn expected_urls(mut self, expected_urls: usize) -> Self {
+        self.expected_urls = expected_urls;
+        self
+    }
+
+    pub fn state_path(mut self, path: &str) -> Self {
+        self.state_path = Some(PathBuf::from(path));
+        self
+    }
+
+    pub fn checkpoint_every(mut self, pages: usize) -> Self {
+        self.checkpoint_every = pages.max(1);
+        self
+    }
+
+    pub fn per_host_limit(mut self, limit: usize) -> Self {
+        self.per_host_limit = limit.max(1);
+        self
+    }
+
+    pub fn politeness_delay(mut self, millis: u64) -> Self {
+        self.politeness_delay = Duration::from_millis(millis);
+        self
+    }
+
+    pub fn skip_nofollow(mut self, enabled: bool) -> Self {
+        self.skip_nofollow = enabled;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub depth: usize,
+    pub title: String,
+    pub links: Vec<String>,
+    pub content: FetchedContent,
+}
+
+pub struct Crawler {
+    pub options: CrawlOptions,
+    pub state: CrawlState,
+    seed_hosts: HashSet<String>,
+}
+
+impl Crawler {
+    pub fn new(options: CrawlOptions) -> Self {
+        let state = fresh_state(&options);
+        Self {
+            options,
+            state,
+            seed_hosts: HashSet::new(),
+        }
+    }
+
+    // Picks up an interrupted crawl from `state_path` if a checkpoint exists there.
+    pub fn resume(options: CrawlOptions) -> Result<Self, BrowserError> {
+        let state = match &options.state_path {
+            Some(path) if path.exists() => CrawlState::load(path)?,
+            _ => fresh_state(&options),
+        };
+        Ok(Self {
+            options,
+            state,
+            seed_hosts: HashSet::new(),
+        })
+    }
+
+    pub fn seed(&mut self, url: &str) -> Result<(), BrowserError> {
+        let url = normalize_url(url)
+            .ok_or_else(|| BrowserError::ConfigError(format!("Invalid seed URL: {url}")))?;
+        if let Some(host) = host_of(&url) {
+            self.seed_hosts.insert(host);
+        }
+        if self.state.visited.insert(&url) {
+            self.state.frontier.push(FrontierEntry { url, depth: 0 });
+        }
+        Ok(())
+    }
+
+    pub fn checkpoint(&self) -> Result<(), BrowserError> {
+        match &self.options.state_path {
+            Some(path) => self.state.save(path),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn run<F>(
+        &mut self,
+        client: &mut BrowserClient,
+        on_page: F,
+    ) -> Result<usize, BrowserError>
+    where
+        F: FnMut(&CrawledPage),
+    {
+        self.run_pool(std::slice::from_mut(client), on_page).await
+    }
+
+    // Each client is an independent WebDriver session; fetches are spread across hosts
+    // subject to `per_host_limit` and `politeness_delay`.
+    pub async fn run_pool<F>(
+        &mut self,
+        clients: &mut [BrowserClient],
+        mut on_page: F,
+    ) -> Result<usize, BrowserError>
+    where
+        F: FnMut(&CrawledPage),
+    {
+        if clients.is_empty() {
+            return Err(BrowserError::ConfigError(
+                "Crawl needs at least one browser session".into(),
+            ));
+        }
+        for entry in std::mem::take(&mut self.state.in_flight) {
+            self.state.frontier.push(entry);
+        }
+        // Hosts of resumed frontier entries count as seeds too.
+        let resumed: Vec<String> = self.state.frontier.hosts().cloned().collect();
+        self.seed_hosts.extend(resumed);
+
+        let mut scheduler =
+            HostScheduler::new(self.options.per_host_limit, self.options.politeness_delay);
+        let mut idle: Vec<&mut BrowserClient> = clients.iter_mut().collect();
+        let mut in_flight = FuturesUnordered::new();
+        // Pages crawled plus pages being fetched, so `max_pages` is never overshot.
+        let mut dispatched = 0;
+        let mut crawled = 0;
+
+        loop {
+            while dispatched < self.options.max_pages && !idle.is_empty() {
+                let Some(entry) = self
+                    .state
+                    .frontier
+                    .pop_where(|host| scheduler.is_ready(host))
+                else {
+                    break;
+                };
+                let client = idle.pop().expect("idle client available");
+                scheduler.acquire(&host_of(&entry.url).unwrap_or_default());
+                self.state.in_flight.push(entry.clone());
+                in_flight.push(fetch_page(client, entry, self.options.skip_nofollow));
+                dispatched += 1;
+            }
+
+            if in_flight.is_empty() {
+                if dispatched >= self.options.max_pages || self.state.frontier.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(self.next_ready_in(&scheduler)).await;
+                continue;
+            }
+
+            let finished = if idle.is_empty() || self.state.frontier.is_empty() {
+                in_flight.next().await
+            } else {
+                tokio::select! {
+                    finished = in_flight.next() => finished,
+                    _ = tokio::time::sleep(self.next_ready_in(&scheduler)) => continue,
+                }
+            };
+            let Some((client, entry, result)) = finished else {
+                continue;
+            };
+            scheduler.release(&host_of(&entry.url).unwrap_or_default());
+            idle.push(client);
+            self.state.in_flight.retain(|e| *e != entry);
+
+            let (title, links, content) = match result {
+                Ok(page) => page,
+                Err(e) => {
+                    eprintln!("Crawl failed for {}: {e}", entry.url);
+                    dispatched -= 1;
+                    continue;
+                }
+            };
+
+            if entry.depth < self.options.max_depth {
+                for link in &links {
+                    if self.in_scope(link) && self.state.visited.insert(link) {
+                        self.state.frontier.push(FrontierEntry {
+                            url: link.clone(),
+                            depth: entry.depth + 1,
+                        });
+                    }
+                }
+            }
+
+            on_page(&CrawledPage {
+                url: entry.url,
+                depth: entry.depth,
+                title,
+                links,
+                content,
+            });
+
+            crawled += 1;
+            self.state.pages_crawled += 1;
+            if self
+                .state
+                .pages_crawled
+                .is_multiple_of(self.options.checkpoint_every)
+            {
+                self.checkpoint()?;
+            }
+        }
+
+        self.checkpoint()?;
+        Ok(crawled)
+    }
+
+    fn next_ready_in(&self, scheduler: &HostScheduler) -> Duration {
+        self.state
+            .frontier
+            .hosts()
+            .filter_map(|host| scheduler.ready_in(host))
+            .min()
+            .unwrap_or(Duration::from_millis(50))
+            .max(Duration::from_millis(10))
+    }
+
+    fn in_scope(&self, url: &str) -> bool {
+        !self.options.same_host_only
+            || host_of(url).is_some_and(|host| self.seed_hosts.contains(&host))
+    }
+}
+
+// A new crawl's state; a persisted one keeps its visited fingerprints next to the checkpoint,
+// clearing out those of any earlier crawl there.
+fn fresh_state(options: &CrawlOptions) -> CrawlState {
+    let mut state = CrawlState::new(options.expected_urls);
+    if let Some(path) = &options.state_path {
+        let dir = path.with_extension("visited");
+        let _ = fs::remove_dir_all(&dir);
+        state.visited.store_in(&dir);
+    }
+    state
+}
+
+async fn fetch_page(
+    client: &mut BrowserClient,
+    entry: FrontierEntry,
+    skip_nofollow: bool,
+) -> (
+    &mut BrowserClient,
+    FrontierEntry,
+    Result<(String, Vec<String>, FetchedContent), BrowserError>,
+) {
+    let result = async {
+        let content = client.navigate(&entry.url).await?;
+        if content.kind() != ContentKind::Html {
+            return Ok((String::new(), vec![], content));
+        }
+        let title = client.get_title().await.unwrap_or_default();
+        let links = collect_links(client, skip_nofollow)
+            .await
+            .unwrap_or_default();
+        Ok((title, links, content))
+    }
+    .await;
+    (client, entry, result)
+}
+
+async fn collect_links(
+    client: &BrowserClient,
+    skip_nofollow: bool,
+) -> Result<Vec<String>, BrowserError> {
+    let mut seen = HashSet::new();
+    Ok(client
+        .extract_links()
+        .await?
+        .iter()
+        .filter(|link| !(skip_nofollow && link.rel.iter().any(|rel| rel == "nofollow")))
+        .filter_map(|link| normalize_url(&link.href))
+        .filter(|url| seen.insert(url.clone()))
+        .collect())
+}
+
+pub fn normalize_url(raw: &str) -> Option<String> {
+    let mut url = Url::parse(raw).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    url.set_fragment(None);
+    let mut normalized = url.to_string();
+    if normalized.ends_with('/') && url.path() != "/" {
+        normalized.pop();
+    }
+    Some(normalized)
+}
+
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::agent::JobStatus;
+use crate::trajectory::TrajectoryStep;
+use crate::types::BrowserError;
+
+// One supervised example: the exact planning prompt and the plan the agent should have
+// answered with, in the checklist + ```json format the text parser reads back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingPair {
+    pub run_id: String,
+    pub step: usize,
+    pub prompt: String,
+    pub completion: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairFormat {
+    // {"prompt": ..., "completion": ...}
+    #[default]
+    PromptCompletion,
+    // {"messages": [{"role": "user", ...}, {"role": "assistant", ...}]}
+    Chat,
+}
+
+// Turns recorded trajectories into training pairs, keeping only runs that are worth
+// imitating: the agent declared the task done, no job failed, and a human approved the run -
+// either by listing its id in `approved_runs` or by approving every plan as it ran.
+pub fn training_pairs(steps: &[TrajectoryStep], approved_runs: &[String]) -> Vec<TrainingPair> {
+    let mut runs: BTreeMap<&str, Vec<&TrajectoryStep>> = BTreeMap::new();
+    for step in steps {
+        runs.entry(step.run_id.as_str()).or_default().push(step);
+    }
+
+    let mut pairs = vec![];
+    for (run_id, mut rounds) in runs {
+        rounds.sort_by_key(|step| step.step);
+        let finished = rounds.last().is_some_and(|step| step.actions.is_empty());
+        let clean = rounds.iter().all(|step| {
+            step.error.is_none()
+                && step
+                    .results
+                    .iter()
+                    .all(|r| r.status == JobStatus::Succeeded)
+        });
+        let acting: Vec<_> = rounds
+            .iter()
+            .filter(|step| !step.actions.is_empty())
+            .collect();
+        let approved = approved_runs.iter().any(|id| id == run_id)
+            || (!acting.is_empty() && acting.iter().all(|step| step.approved));
+        if !(finished && clean && approved) {
+            continue;
+        }
+
+        for step in rounds.iter().filter(|step| !step.prompt.is_empty()) {
+            let jobs = serde_json::to_string_pretty(&step.actions).unwrap_or_default();
+            pairs.push(TrainingPair {
+                run_id: run_id.to_string(),
+                step: step.step,
+                prompt: step.prompt.clone(),
+                completion: format!("{}\n\n```json\n{jobs}\n```", step.thought.trim()),
+            });
+        }
+    }
+    pairs
+}
+
+pub fn write_jsonl(
+    pairs: &[TrainingPair],
+    path: &Path,
+    format: PairFormat,
+) -> Result<(), BrowserError> {
+    let mut file = std::fs::File::create(path)
+        .map_err(|e| BrowserError::OperationError(format!("{}: {e}", path.display())))?;
+    for pair in pairs {
+        let line = match format {
+            PairFormat::PromptCompletion => json!({
+                "prompt": pair.prompt,
+                "completion": pair.completion,
+            }),
+            PairFormat::Chat => json!({
+                "messages": [
+                    { "role": "user", "content": pair.prompt },
+                    { "role": "assistant", "content": pair.completion },
+                ],
+            }),
+        };
+        writeln!(file, "{line}")
+            .map_err(|e| BrowserError::OperationError(format!("{}: {e}", path.display())))?;
+    }
+    Ok(())
+}
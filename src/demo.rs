@@ -0,0 +1,318 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::types::BrowserError;
+
+pub const DEMO_TASK: &str =
+    "Search the shop for 'keyboard', open the first product and add it to the cart";
+
+const PRODUCTS: [(u32, &str, &str, &str); 4] = [
+    (
+        1,
+        "Mechanical Keyboard",
+        "89.00",
+        "Tactile switches, aluminium frame.",
+    ),
+    (
+        2,
+        "Wireless Mouse",
+        "39.50",
+        "Ergonomic, 2.4GHz and Bluetooth.",
+    ),
+    (
+        3,
+        "Keyboard Wrist Rest",
+        "19.99",
+        "Memory foam, fits full-size boards.",
+    ),
+    (4, "USB-C Hub", "45.00", "7-in-1 with HDMI and card reader."),
+];
+
+// Self-contained demo shop (login, search, product pages, cart, contact form) for trying the
+// agent end-to-end without touching external websites. The cart lives in localStorage so the
+// server stays stateless.
+pub struct DemoServer {
+    port: u16,
+    handle: JoinHandle<()>,
+}
+
+impl DemoServer {
+    pub async fn start(port: u16) -> Result<Self, BrowserError> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| BrowserError::ConfigError(format!("Demo server bind failed: {e}")))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| BrowserError::ConfigError(e.to_string()))?
+            .port();
+
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream));
+            }
+        });
+
+        Ok(Self { port, handle })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+
+    pub fn shutdown(self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream) {
+    let mut buf = vec![0u8; 8192];
+    let Ok(n) = stream.read(&mut buf).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let target = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, body) = route(path, query);
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+fn route(path: &str, query: &str) -> (&'static str, String) {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| decode(v))
+            .unwrap_or_default()
+    };
+
+    match path {
+        "/" => ("200 OK", home_page()),
+        "/login" => ("200 OK", login_page(&param("user"))),
+        "/search" => ("200 OK", search_page(&param("q"))),
+        "/cart" => ("200 OK", cart_page()),
+        "/contact" => ("200 OK", contact_page(!param("email").is_empty())),
+        p if p.starts_with("/product/") => {
+            match p["/product/".len()..]
+                .parse::<u32>()
+                .ok()
+                .and_then(|id| PRODUCTS.iter().find(|(pid, ..)| *pid == id))
+            {
+                Some(product) => ("200 OK", product_page(product)),
+                None => (
+                    "404 Not Found",
+                    layout("Not found", "<p>No such product.</p>"),
+                ),
+            }
+        }
+        _ => (
+            "404 Not Found",
+            layout("Not found", "<p>Page not found.</p>"),
+        ),
+    }
+}
+
+fn decode(value: &str) -> String {
+    url::form_urlencoded::parse(format!("v={value}").as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn layout(title: &str, content: &str) -> String {
+    format!(
+        r##"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title} - Demo Shop</title>
+<style>
+  body {{ font-family: sans-serif; max-width: 860px; margin: 0 auto; padding: 0 16px; }}
+  nav {{ display: flex; gap: 16px; align-items: center; padding: 12px 0; border-bottom: 1px solid #ddd; }}
+  nav form {{ margin-left: auto; }}
+  .product {{ border: 1px solid #ddd; border-radius: 6px; padding: 12px; margin: 12px 0; }}
+  label {{ display: block; margin-top: 8px; }}
+  footer {{ margin-top: 40px; color: #777; font-size: 12px; }}
+</style>
+</head>
+<body>
+<nav>
+  <a id="home-link" href="/">Demo Shop</a>
+  <a id="cart-link" href="/cart">Cart (<span id="cart-count">0</span>)</a>
+  <a id="login-link" href="/login">Log in</a>
+  <a id="contact-link" href="/contact">Contact</a>
+  <form id="search-form" action="/search" method="get">
+    <input id="search-input" name="q" type="search" placeholder="Search products" aria-label="Search products">
+    <button id="search-button" type="submit">Search</button>
+  </form>
+</nav>
+<main>
+<h1>{title}</h1>
+{content}
+</main>
+<footer>Demo Shop is a local fixture bundled with internet-use.</footer>
+<script>
+  const cart = () => JSON.parse(localStorage.getItem("demo-cart") || "[]");
+  document.getElementById("cart-count").textContent = cart().length;
+</script>
+</body>
+</html>"##
+    )
+}
+
+fn home_page() -> String {
+    let items: String = PRODUCTS
+        .iter()
+        .map(|(id, name, price, _)| {
+            format!(
+                r##"<li><a class="product-link" href="/product/{id}">{name}</a> - ${price}</li>"##
+            )
+        })
+        .collect();
+    layout(
+        "Welcome",
+        &format!(
+            "<p>Browse our featured products or use the search box.</p><ul id=\"featured\">{items}</ul>"
+        ),
+    )
+}
+
+fn login_page(user: &str) -> String {
+    if !user.is_empty() {
+        return layout(
+            "Logged in",
+            &format!(
+                r##"<p id="welcome">Welcome back, {}!</p><a href="/">Continue shopping</a>"##,
+                escape(user)
+            ),
+        );
+    }
+    layout(
+        "Log in",
+        r##"<form id="login-form" action="/login" method="get">
+  <label for="user">Username</label>
+  <input id="user" name="user" type="text" required>
+  <label for="password">Password</label>
+  <input id="password" name="password" type="password" required>
+  <button id="login-button" type="submit">Log in</button>
+</form>"##,
+    )
+}
+
+fn search_page(query: &str) -> String {
+    let needle = query.to_lowercase();
+    let results: String = PRODUCTS
+        .iter()
+        .filter(|(_, name, _, description)| {
+            name.to_lowercase().contains(&needle) || description.to_lowercase().contains(&needle)
+        })
+        .map(|(id, name, price, description)| {
+            format!(
+                r##"<div class="product result"><a class="result-link" href="/product/{id}">{name}</a><p>{description}</p><span class="price">${price}</span></div>"##
+            )
+        })
+        .collect();
+    let body = if results.is_empty() {
+        format!(
+            "<p id=\"no-results\">No results for \"{}\".</p>",
+            escape(query)
+        )
+    } else {
+        format!("<div id=\"results\">{results}</div>")
+    };
+    layout(&format!("Results for \"{}\"", escape(query)), &body)
+}
+
+fn product_page((id, name, price, description): &(u32, &str, &str, &str)) -> String {
+    layout(
+        name,
+        &format!(
+            r##"<div class="product" data-id="{id}">
+  <p class="description">{description}</p>
+  <p>Price: <span id="price">${price}</span></p>
+  <label for="quantity">Quantity</label>
+  <select id="quantity" name="quantity"><option>1</option><option>2</option><option>3</option></select>
+  <button id="add-to-cart" type="button">Add to cart</button>
+  <p id="cart-status" role="status"></p>
+</div>
+<script>
+  document.getElementById("add-to-cart").addEventListener("click", () => {{
+    const items = JSON.parse(localStorage.getItem("demo-cart") || "[]");
+    const qty = parseInt(document.getElementById("quantity").value, 10);
+    for (let i = 0; i < qty; i++) items.push({{ id: {id}, name: "{name}", price: "{price}" }});
+    localStorage.setItem("demo-cart", JSON.stringify(items));
+    document.getElementById("cart-count").textContent = items.length;
+    document.getElementById("cart-status").textContent = "Added {name} to your cart.";
+  }});
+</script>"##
+        ),
+    )
+}
+
+fn cart_page() -> String {
+    layout(
+        "Your cart",
+        r##"<table id="cart-table"><thead><tr><th>Item</th><th>Price</th></tr></thead><tbody></tbody></table>
+<p id="cart-empty" hidden>Your cart is empty.</p>
+<button id="clear-cart" type="button">Clear cart</button>
+<script>
+  const render = () => {
+    const items = JSON.parse(localStorage.getItem("demo-cart") || "[]");
+    const body = document.querySelector("#cart-table tbody");
+    body.innerHTML = items.map(i => `<tr><td>${i.name}</td><td>$${i.price}</td></tr>`).join("");
+    document.getElementById("cart-empty").hidden = items.length > 0;
+    document.getElementById("cart-count").textContent = items.length;
+  };
+  document.getElementById("clear-cart").addEventListener("click", () => {
+    localStorage.removeItem("demo-cart");
+    render();
+  });
+  render();
+</script>"##,
+    )
+}
+
+fn contact_page(submitted: bool) -> String {
+    if submitted {
+        return layout(
+            "Thanks!",
+            r##"<p id="confirmation">Your message was sent. Reference: DEMO-4821</p>"##,
+        );
+    }
+    layout(
+        "Contact us",
+        r##"<form id="contact-form" action="/contact" method="get">
+  <label for="name">Name</label>
+  <input id="name" name="name" type="text" required>
+  <label for="email">Email</label>
+  <input id="email" name="email" type="email" required>
+  <label for="topic">Topic</label>
+  <select id="topic" name="topic">
+    <option value="order">Order question</option>
+    <option value="returns">Returns</option>
+    <option value="other">Other</option>
+  </select>
+  <label><input id="newsletter" name="newsletter" type="checkbox"> Subscribe to newsletter</label>
+  <label for="message">Message</label>
+  <textarea id="message" name="message"></textarea>
+  <button id="send-button" type="submit">Send</button>
+</form>"##,
+    )
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::{InteractiveElement, TextElement};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DomSnapshot {
+    pub url: String,
+    pub title: String,
+    pub interactive: Vec<InteractiveElement>,
+    pub text: Vec<TextElement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ElementChange {
+    pub before: InteractiveElement,
+    pub after: InteractiveElement,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DomDiff {
+    pub url_changed: Option<(String, String)>,
+    pub title_changed: Option<(String, String)>,
+    pub added_elements: Vec<InteractiveElement>,
+    pub removed_elements: Vec<InteractiveElement>,
+    pub changed_elements: Vec<ElementChange>,
+    pub added_text: Vec<TextElement>,
+    pub removed_text: Vec<TextElement>,
+}
+
+// Labels, boxes and viewport or occlusion state are left out: they shift with every
+// insertion or scroll without the element itself changing.
+fn differs(a: &InteractiveElement, b: &InteractiveElement) -> bool {
+    a.frame != b.frame
+        || a.tag != b.tag
+        || a.role != b.role
+        || a.state != b.state
+        || a.text != b.text
+        || a.r#type != b.r#type
+        || a.placeholder != b.placeholder
+        || a.visible != b.visible
+        || a.attributes != b.attributes
+}
+
+impl DomDiff {
+    pub fn between(before: &DomSnapshot, after: &DomSnapshot) -> Self {
+        let changed = |a: &String, b: &String| (a != b).then(|| (a.clone(), b.clone()));
+
+        let mut diff = DomDiff {
+            url_changed: changed(&before.url, &after.url),
+            title_changed: changed(&before.title, &after.title),
+            ..Default::default()
+        };
+
+        // Selectors are not unique, so elements are matched by (selector, occurrence).
+        let mut old_groups: HashMap<&str, Vec<&InteractiveElement>> = HashMap::new();
+        for el in &before.interactive {
+            old_groups.entry(el.selector.as_str()).or_default().push(el);
+        }
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for el in &after.interactive {
+            let nth = seen.entry(el.selector.as_str()).or_default();
+            match old_groups
+                .get(el.selector.as_str())
+                .and_then(|g| g.get(*nth))
+            {
+                Some(old) if differs(old, el) => diff.changed_elements.push(ElementChange {
+                    before: (*old).clone(),
+                    after: el.clone(),
+                }),
+                Some(_) => {}
+                None => diff.added_elements.push(el.clone()),
+            }
+            *nth += 1;
+        }
+        for (selector, group) in old_groups {
+            let matched = seen.get(selector).copied().unwrap_or(0);
+            diff.removed_elements
+                .extend(group.into_iter().skip(matched).cloned());
+        }
+
+        // Text nodes shift index whenever anything is inserted, so compare as a multiset.
+        let mut old_text: HashMap<(&str, &str), usize> = HashMap::new();
+        for t in &before.text {
+            *old_text.entry((&t.selector, &t.text)).or_default() += 1;
+        }
+        for t in &after.text {
+            match old_text.get_mut(&(t.selector.as_str(), t.text.as_str())) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => diff.added_text.push(t.clone()),
+            }
+        }
+        for t in &before.text {
+            if let Some(count) = old_text.get_mut(&(t.selector.as_str(), t.text.as_str()))
+                && *count > 0
+            {
+                *count -= 1;
+                diff.removed_text.push(t.clone());
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.url_changed.is_none()
+            && self.title_changed.is_none()
+            && self.added_elements.is_empty()
+            && self.removed_elements.is_empty()
+            && self.changed_elements.is_empty()
+            && self.added_text.is_empty()
+            && self.removed_text.is_empty()
+    }
+
+    // Compact, line-oriented rendering meant for the planning prompt.
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "No changes.".to_string();
+        }
+
+        let mut lines = vec![];
+        if let Some((from, to)) = &self.url_changed {
+            lines.push(format!("URL: {from} -> {to}"));
+        }
+        if let Some((from, to)) = &self.title_changed {
+            lines.push(format!("Title: {from} -> {to}"));
+        }
+        for el in &self.added_elements {
+            lines.push(format!("+ {} {:?}", el.selector, el.text));
+        }
+        for el in &self.removed_elements {
+            lines.push(format!("- {} {:?}", el.selector, el.text));
+        }
+        for change in &self.changed_elements {
+            lines.push(format!(
+                "~ {} {:?} -> {:?}",
+                change.after.selector, change.before.text, change.after.text
+            ));
+        }
+        for t in &self.added_text {
+            lines.push(format!("+ text {}: {:?}", t.selector, t.text));
+        }
+        for t in &self.removed_text {
+            lines.push(format!("- text {}: {:?}", t.selector, t.text));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(selector: &str, text: &str) -> InteractiveElement {
+        serde_json::from_value(serde_json::json!({
+            "selector": selector,
+            "tag": "button",
+            "text": text,
+            "type": "",
+            "placeholder": "",
+        }))
+        .unwrap()
+    }
+
+    fn text(selector: &str, text: &str, index: usize) -> TextElement {
+        serde_json::from_value(serde_json::json!({
+            "selector": selector,
+            "text": text,
+            "index": index,
+        }))
+        .unwrap()
+    }
+
+    fn snapshot(interactive: Vec<InteractiveElement>, text: Vec<TextElement>) -> DomSnapshot {
+        DomSnapshot {
+            url: "https://example.com/".to_string(),
+            title: "Example".to_string(),
+            interactive,
+            text,
+        }
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_diff() {
+        let page = snapshot(vec![element("#go", "Go")], vec![text("p", "Hello", 0)]);
+        let diff = DomDiff::between(&page, &page.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "No changes.");
+    }
+
+    #[test]
+    fn elements_are_matched_by_selector_and_occurrence() {
+        let before = snapshot(vec![element("li", "One"), element("#go", "Go")], vec![]);
+        let after = snapshot(
+            vec![
+                element("li", "One"),
+                element("li", "Two"),
+                element("#go", "Stop"),
+            ],
+            vec![],
+        );
+        let diff = DomDiff::between(&before, &after);
+        assert_eq!(diff.added_elements, [element("li", "Two")]);
+        assert!(diff.removed_elements.is_empty());
+        assert_eq!(
+            diff.changed_elements,
+            [ElementChange {
+                before: element("#go", "Go"),
+                after: element("#go", "Stop"),
+            }]
+        );
+    }
+
+    #[test]
+    fn labels_and_boxes_do_not_count_as_changes() {
+        let mut moved = element("#go", "Go");
+        moved.label = 7;
+        moved.in_viewport = false;
+        let diff = DomDiff::between(
+            &snapshot(vec![element("#go", "Go")], vec![]),
+            &snapshot(vec![moved], vec![]),
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn text_is_compared_regardless_of_position() {
+        let before = snapshot(vec![], vec![text("p", "A", 0), text("p", "B", 1)]);
+        let after = snapshot(
+            vec![],
+            vec![text("p", "New", 0), text("p", "A", 1), text("p", "B", 2)],
+        );
+        let diff = DomDiff::between(&before, &after);
+        assert_eq!(diff.added_text, [text("p", "New", 0)]);
+        assert!(diff.removed_text.is_empty());
+    }
+}
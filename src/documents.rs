@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::types::{BrowserError, Table};
+
+#[derive(Debug, Clone, Default)]
+pub struct DocumentOptions {
+    pub extract_tables: bool,
+    pub password: Option<String>,
+}
+
+impl DocumentOptions {
+    pub fn extract_tables(mut self, enabled: bool) -> Self {
+        self.extract_tables = enabled;
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub source: Option<PathBuf>,
+    pub pages: Vec<String>,
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DocumentMatch {
+    pub page: usize,
+    pub line: String,
+}
+
+impl Document {
+    pub fn text(&self) -> String {
+        self.pages.join("\n\n")
+    }
+
+    pub fn search(&self, query: &str) -> Vec<DocumentMatch> {
+        let needle = query.to_lowercase();
+        self.pages
+            .iter()
+            .enumerate()
+            .flat_map(|(i, page)| {
+                page.lines()
+                    .filter(|line| line.to_lowercase().contains(&needle))
+                    .map(move |line| DocumentMatch {
+                        page: i + 1,
+                        line: line.trim().to_string(),
+                    })
+            })
+            .collect()
+    }
+}
+
+pub fn read_pdf(path: &Path, options: &DocumentOptions) -> Result<Document, BrowserError> {
+    let bytes = std::fs::read(path).map_err(|e| BrowserError::DocumentError(e.to_string()))?;
+    let mut document = parse_pdf(&bytes, options)?;
+    document.source = Some(path.to_path_buf());
+    Ok(document)
+}
+
+pub fn parse_pdf(bytes: &[u8], options: &DocumentOptions) -> Result<Document, BrowserError> {
+    let pages = pdf_pages(bytes, options.password.as_deref())?;
+    let tables = if options.extract_tables {
+        pages
+            .iter()
+            .enumerate()
+            .flat_map(|(i, page)| detect_tables(page, i + 1))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    Ok(Document {
+        source: None,
+        pages,
+        tables,
+    })
+}
+
+#[cfg(feature = "pdf")]
+fn pdf_pages(bytes: &[u8], password: Option<&str>) -> Result<Vec<String>, BrowserError> {
+    match password {
+        Some(password) => pdf_extract::extract_text_from_mem_by_pages_encrypted(bytes, password),
+        None => pdf_extract::extract_text_from_mem_by_pages(bytes),
+    }
+    .map_err(|e| BrowserError::DocumentError(e.to_string()))
+}
+
+#[cfg(not(feature = "pdf"))]
+fn pdf_pages(_bytes: &[u8], _password: Option<&str>) -> Result<Vec<String>, BrowserError> {
+    Err(BrowserError::DocumentError(
+        "PDF support requires the `pdf` feature".into(),
+    ))
+}
+
+// PDF text has no table markup; runs of consecutive lines that split into the same number
+// (>= 2) of columns on wide whitespace gaps are treated as a table, the first line as headers.
+pub fn detect_tables(text: &str, page: usize) -> Vec<Table> {
+    let split = |line: &str| -> Vec<String> {
+        line.split("  ")
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    let mut tables = vec![];
+    let mut run: Vec<Vec<String>> = vec![];
+
+    let mut flush = |run: &mut Vec<Vec<String>>| {
+        if run.len() >= 3 {
+            let mut rows = std::mem::take(run);
+            let headers = rows.remove(0);
+            tables.push(Table {
+                selector: format!("page {page}"),
+                caption: None,
+                headers,
+                rows,
+            });
+        }
+        run.clear();
+    };
+
+    for line in text.lines() {
+        let cells = split(line);
+        let continues =
+            cells.len() >= 2 && run.first().is_none_or(|first| first.len() == cells.len());
+        if !continues {
+            flush(&mut run);
+        }
+        if cells.len() >= 2 {
+            run.push(cells);
+        }
+    }
+    flush(&mut run);
+
+    tables
+}
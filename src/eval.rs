@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::BrowserClient;
+use crate::agent::{Agent, TaskReport};
+use crate::jobs::BrowserJob;
+use crate::types::BrowserError;
+
+// One benchmark task. Field aliases accept WebArena (`task_id`, `intent`) and Mind2Web
+// (`annotation_id`, `confirmed_task`, `website`) exports as-is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalTask {
+    #[serde(alias = "task_id", alias = "annotation_id")]
+    pub id: String,
+    #[serde(alias = "start_url", alias = "website")]
+    pub url: String,
+    #[serde(alias = "intent", alias = "confirmed_task")]
+    pub goal: String,
+    // Checked on the final page; without any, the judge's verdict decides.
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Assertion {
+    UrlContains(String),
+    // Regular expression the final URL must match.
+    UrlMatches(String),
+    TitleContains(String),
+    TextVisible(String),
+    TextContains { selector: String, text: String },
+    ElementExists(String),
+    // Free-form condition checked by the model against the page, like a subtask check.
+    Condition(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalResult {
+    pub id: String,
+    pub passed: bool,
+    pub assertions: Vec<AssertionResult>,
+    pub report: Option<TaskReport>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+// Aggregates over a suite, for comparing models and prompt changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvalMetrics {
+    pub tasks: usize,
+    pub passed: usize,
+    pub errors: usize,
+    pub success_rate: f32,
+    pub mean_judge_score: f32,
+    pub mean_jobs: f32,
+    pub mean_duration_ms: f64,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub results: Vec<EvalResult>,
+    pub metrics: EvalMetrics,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvalSuite {
+    pub tasks: Vec<EvalTask>,
+}
+
+impl EvalSuite {
+    // A JSON array of tasks, or an object with a `tasks` array.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BrowserError> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+        let invalid = |e: serde_json::Error| {
+            BrowserError::ConfigError(format!("Invalid suite {}: {e}", path.display()))
+        };
+        match serde_json::from_str::<Vec<EvalTask>>(&data) {
+            Ok(tasks) => Ok(Self { tasks }),
+            Err(_) => serde_json::from_str(&data).map_err(invalid),
+        }
+    }
+
+    // Runs every task with a fresh agent from `make_agent` in the same browser session. A task
+    // that errors counts as failed; the suite always runs to the end.
+    pub async fn run(
+        &self,
+        client: &mut BrowserClient,
+        mut make_agent: impl FnMut() -> Result<Agent, BrowserError>,
+    ) -> Result<EvalReport, BrowserError> {
+        let mut results = vec![];
+        for task in &self.tasks {
+            eprintln!("Evaluating {}: {}", task.id, task.goal);
+            let mut agent = make_agent()?;
+            let started = Instant::now();
+            let outcome = run_task(task, &mut agent, client).await;
+            let duration_ms = started.elapsed().as_millis() as u64;
+            results.push(match outcome {
+                Ok((report, assertions)) => {
+                    let passed = if assertions.is_empty() {
+                        report.verdict.as_ref().is_some_and(|v| v.success)
+                    } else {
+                        assertions.iter().all(|a| a.passed)
+                    };
+                    EvalResult {
+                        id: task.id.clone(),
+                        passed,
+                        assertions,
+                        report: Some(report),
+                        error: None,
+                        duration_ms,
+                    }
+                }
+                Err(e) => EvalResult {
+                    id: task.id.clone(),
+                    passed: false,
+                    assertions: vec![],
+                    report: None,
+                    error: Some(e.to_string()),
+                    duration_ms,
+                },
+            });
+        }
+        let metrics = metrics(&results);
+        Ok(EvalReport { results, metrics })
+    }
+}
+
+async fn run_task(
+    task: &EvalTask,
+    agent: &mut Agent,
+    client: &mut BrowserClient,
+) -> Result<(TaskReport, Vec<AssertionResult>), BrowserError> {
+    client.navigate(&task.url).await?;
+    let report = agent.run_task(&task.goal, client).await?;
+    let mut results = vec![];
+    for assertion in &task.assertions {
+        let (passed, detail) = check(assertion, agent, client).await?;
+        results.push(AssertionResult {
+            assertion: assertion.clone(),
+            passed,
+            detail,
+        });
+    }
+    Ok((report, results))
+}
+
+async fn check(
+    assertion: &Assertion,
+    agent: &Agent,
+    client: &mut BrowserClient,
+) -> Result<(bool, String), BrowserError> {
+    match assertion {
+        Assertion::UrlContains(part) => {
+            let url = client.current_url().await?;
+            Ok((url.contains(part.as_str()), url))
+        }
+        Assertion::UrlMatches(pattern) => {
+            assert_job(BrowserJob::AssertUrlMatches(pattern.clone()), client).await
+        }
+        Assertion::TitleContains(part) => {
+            assert_job(BrowserJob::AssertTitleContains(part.clone()), client).await
+        }
+        Assertion::TextVisible(text) => {
+            assert_job(BrowserJob::AssertText(text.clone()), client).await
+        }
+        Assertion::TextContains { selector, text } => {
+            let job = BrowserJob::AssertTextContains {
+                selector: selector.clone(),
+                text: text.clone(),
+            };
+            assert_job(job, client).await
+        }
+        Assertion::ElementExists(selector) => {
+            let found = client.selector_exists(selector).await?;
+            Ok((found, String::new()))
+        }
+        Assertion::Condition(condition) => agent.check_condition(condition, client).await,
+    }
+}
+
+// Any failure of an assertion job, a missing element included, fails the check rather than
+// the eval.
+async fn assert_job(
+    job: BrowserJob,
+    client: &mut BrowserClient,
+) -> Result<(bool, String), BrowserError> {
+    match job.run(client).await {
+        Ok(()) => Ok((true, String::new())),
+        Err(e) => Ok((false, e.to_string())),
+    }
+}
+
+fn metrics(results: &[EvalResult]) -> EvalMetrics {
+    let tasks = results.len();
+    let reports: Vec<&TaskReport> = results.iter().filter_map(|r| r.report.as_ref()).collect();
+    let mean = |total: f64, n: usize| if n == 0 { 0.0 } else { total / n as f64 };
+    let scores: Vec<f32> = reports
+        .iter()
+        .filter_map(|r| r.verdict.as_ref().map(|v| v.score))
+        .collect();
+    let jobs: usize = reports
+        .iter()
+        .flat_map(|r| &r.steps)
+        .map(|step| step.results.len())
+        .sum();
+    let passed = results.iter().filter(|r| r.passed).count();
+
+    EvalMetrics {
+        tasks,
+        passed,
+        errors: results.iter().filter(|r| r.error.is_some()).count(),
+        success_rate: mean(passed as f64, tasks) as f32,
+        mean_judge_score: mean(scores.iter().map(|&s| s as f64).sum(), scores.len()) as f32,
+        mean_jobs: mean(jobs as f64, reports.len()) as f32,
+        mean_duration_ms: mean(results.iter().map(|r| r.duration_ms as f64).sum(), tasks),
+        prompt_tokens: reports.iter().map(|r| r.usage.prompt_tokens()).sum(),
+        completion_tokens: reports.iter().map(|r| r.usage.completion_tokens()).sum(),
+        cost_usd: reports.iter().map(|r| r.usage.cost_usd()).sum(),
+    }
+}
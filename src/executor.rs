@@ -0,0 +1,393 @@
+use fantoccini::wd::WindowHandle;
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::BrowserClient;
+use crate::jobs::{BrowserJob, RunContext, run_all_jobs};
+use crate::types::BrowserError;
+
+// A job sequence in a `JobGraph`, run once every node it depends on has succeeded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobNode {
+    pub id: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    pub jobs: Vec<BrowserJob>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeStatus {
+    Succeeded,
+    Failed,
+    // Not run because a node it depends on failed or was skipped.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    pub id: String,
+    pub status: NodeStatus,
+    pub error: Option<String>,
+    // Index of the session or tab the node ran in; none when skipped.
+    pub lane: Option<usize>,
+    pub duration_ms: u128,
+    // What the node's jobs stored with `store_as`.
+    pub values: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphReport {
+    // In the order the nodes were declared.
+    pub nodes: Vec<NodeReport>,
+    // The starting variables plus everything the nodes stored.
+    pub values: RunContext,
+}
+
+impl GraphReport {
+    pub fn succeeded(&self) -> bool {
+        self.nodes.iter().all(|n| n.status == NodeStatus::Succeeded)
+    }
+}
+
+// Job sequences with dependencies between them, for fan-out work such as scraping a dozen
+// product pages after one search: independent nodes run at the same time in separate
+// browser sessions, and a node sees the values stored by the nodes it depends on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobGraph {
+    pub nodes: Vec<JobNode>,
+    // Most nodes run at once; unset, one per session, or `DEFAULT_TABS` tabs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
+}
+
+// Tabs `run_in_tabs` uses when no `max_concurrent` is set.
+const DEFAULT_TABS: usize = 4;
+
+// Where a node runs: a session of its own, or a tab of a session other nodes share.
+enum Lane<'a> {
+    Session(&'a mut BrowserClient),
+    Tab(&'a Mutex<&'a mut BrowserClient>, WindowHandle),
+}
+
+impl JobGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Independent job sequences, with nothing waiting on anything else, as nodes "0", "1", ...
+    pub fn parallel(sequences: Vec<Vec<BrowserJob>>) -> Self {
+        let nodes = sequences
+            .into_iter()
+            .enumerate()
+            .map(|(i, jobs)| JobNode {
+                id: i.to_string(),
+                depends_on: vec![],
+                jobs,
+            })
+            .collect();
+        Self {
+            nodes,
+            max_concurrent: None,
+        }
+    }
+
+    pub fn with_max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = Some(max.max(1));
+        self
+    }
+
+    pub fn node(mut self, id: &str, depends_on: &[&str], jobs: Vec<BrowserJob>) -> Self {
+        self.nodes.push(JobNode {
+            id: id.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            jobs,
+        });
+        self
+    }
+
+    // Node indices with every node after the ones it depends on. Fails on duplicate ids,
+    // dependencies on unknown nodes and cycles.
+    pub fn order(&self) -> Result<Vec<usize>, BrowserError> {
+        let index: BTreeMap<&str, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id.as_str(), i))
+            .collect();
+        if index.len() < self.nodes.len() {
+            return Err(BrowserError::ConfigError(
+                "Job graph has duplicate node ids".into(),
+            ));
+        }
+        let mut deps = vec![];
+        for node in &self.nodes {
+            let ids = node.depends_on.iter().map(|dep| {
+                index.get(dep.as_str()).copied().ok_or_else(|| {
+                    BrowserError::ConfigError(format!(
+                        "Node '{}' depends on unknown node '{dep}'",
+                        node.id
+                    ))
+                })
+            });
+            deps.push(ids.collect::<Result<Vec<_>, _>>()?);
+        }
+
+        let mut order = vec![];
+        let mut placed = vec![false; self.nodes.len()];
+        while order.len() < self.nodes.len() {
+            let ready: Vec<usize> = (0..self.nodes.len())
+                .filter(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+                .collect();
+            if ready.is_empty() {
+                let stuck: Vec<&str> = (0..self.nodes.len())
+                    .filter(|&i| !placed[i])
+                    .map(|i| self.nodes[i].id.as_str())
+                    .collect();
+                return Err(BrowserError::ConfigError(format!(
+                    "Job graph has a dependency cycle among {}",
+                    stuck.join(", ")
+                )));
+            }
+            for i in ready {
+                placed[i] = true;
+                order.push(i);
+            }
+        }
+        Ok(order)
+    }
+
+    // Runs every node whose dependencies have succeeded on the next idle session, as many
+    // at once as there are sessions (or `max_concurrent`, if fewer). A failed node does not
+    // stop the others; the nodes that depend on it are skipped.
+    pub async fn run(
+        &self,
+        sessions: &mut [BrowserClient],
+        ctx: &RunContext,
+    ) -> Result<GraphReport, BrowserError> {
+        let order = self.order()?;
+        if sessions.is_empty() {
+            return Err(BrowserError::ConfigError(
+                "Job graph needs at least one browser session".into(),
+            ));
+        }
+        let limit = self.max_concurrent.unwrap_or(sessions.len()).max(1);
+        let lanes = sessions.iter_mut().take(limit).map(Lane::Session);
+        Ok(self.schedule(&order, lanes.collect(), ctx).await)
+    }
+
+    // Runs the nodes in tabs of one session instead, the current one and up to
+    // `max_concurrent - 1` more, which are closed again afterwards. A session carries out one
+    // command at a time, so the nodes take turns a job at a time: each keeps its own page, but
+    // only separate sessions actually run faster. Tab jobs inside a node do not move it out of
+    // its tab; it is switched back before its next job.
+    pub async fn run_in_tabs(
+        &self,
+        client: &mut BrowserClient,
+        ctx: &RunContext,
+    ) -> Result<GraphReport, BrowserError> {
+        let order = self.order()?;
+        let tabs = self
+            .max_concurrent
+            .unwrap_or(DEFAULT_TABS)
+            .min(self.nodes.len())
+            .max(1);
+        let home = match client.current_tab_handle() {
+            Some(handle) => handle.clone(),
+            None => client
+                .client
+                .window()
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?,
+        };
+        let mut handles = vec![home.clone()];
+        while handles.len() < tabs {
+            client.open_tab().await?;
+            match client.current_tab_handle() {
+                Some(handle) if !handles.contains(handle) => handles.push(handle.clone()),
+                _ => break,
+            }
+        }
+
+        let report = {
+            let shared = Mutex::new(&mut *client);
+            let lanes = handles
+                .iter()
+                .map(|handle| Lane::Tab(&shared, handle.clone()));
+            self.schedule(&order, lanes.collect(), ctx).await
+        };
+
+        for handle in &handles[1..] {
+            let open = client.list_tabs().await?;
+            if let Some(index) = open.iter().position(|h| h == handle) {
+                client.close_tab(index).await?;
+            }
+        }
+        client.switch_to_handle(&home).await?;
+        Ok(report)
+    }
+
+    // Hands ready nodes to idle lanes until every node has run or been skipped.
+    async fn schedule<'a>(
+        &self,
+        order: &[usize],
+        lanes: Vec<Lane<'a>>,
+        ctx: &RunContext,
+    ) -> GraphReport {
+        let mut values = ctx.clone();
+        let mut reports: Vec<Option<NodeReport>> = vec![None; self.nodes.len()];
+        let mut started = vec![false; self.nodes.len()];
+        let mut idle: Vec<(usize, Lane<'a>)> = lanes.into_iter().enumerate().rev().collect();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            for &i in order {
+                if started[i] {
+                    continue;
+                }
+                let node = &self.nodes[i];
+                let statuses: Vec<Option<NodeStatus>> = node
+                    .depends_on
+                    .iter()
+                    .map(|dep| self.status_of(&reports, dep))
+                    .collect();
+                if statuses
+                    .iter()
+                    .any(|s| matches!(s, Some(NodeStatus::Failed | NodeStatus::Skipped)))
+                {
+                    started[i] = true;
+                    reports[i] = Some(NodeReport {
+                        id: node.id.clone(),
+                        status: NodeStatus::Skipped,
+                        error: None,
+                        lane: None,
+                        duration_ms: 0,
+                        values: BTreeMap::new(),
+                    });
+                } else if statuses.iter().all(|s| *s == Some(NodeStatus::Succeeded))
+                    && let Some(session) = idle.pop()
+                {
+                    started[i] = true;
+                    in_flight.push(run_node(session, i, node, values.clone()));
+                }
+            }
+            let Some((session, i, report)) = in_flight.next().await else {
+                break;
+            };
+            idle.push(session);
+            values.values.extend(report.values.clone());
+            reports[i] = Some(report);
+        }
+
+        GraphReport {
+            nodes: reports.into_iter().flatten().collect(),
+            values,
+        }
+    }
+
+    fn status_of(&self, reports: &[Option<NodeReport>], id: &str) -> Option<NodeStatus> {
+        let i = self.nodes.iter().position(|node| node.id == id)?;
+        reports[i].as_ref().map(|report| report.status)
+    }
+}
+
+async fn run_node<'a>(
+    (index, mut lane): (usize, Lane<'a>),
+    node_index: usize,
+    node: &JobNode,
+    mut ctx: RunContext,
+) -> ((usize, Lane<'a>), usize, NodeReport) {
+    let started = Instant::now();
+    let before = ctx.values.clone();
+    let result = match &mut lane {
+        Lane::Session(client) => run_all_jobs(client, &node.jobs, &mut ctx).await,
+        Lane::Tab(shared, handle) => run_in_tab(shared, handle, &node.jobs, &mut ctx).await,
+    };
+    let values = ctx
+        .values
+        .into_iter()
+        .filter(|(name, value)| before.get(name) != Some(value))
+        .collect();
+    let report = NodeReport {
+        id: node.id.clone(),
+        status: if result.is_ok() {
+            NodeStatus::Succeeded
+        } else {
+            NodeStatus::Failed
+        },
+        error: result.err().map(|e| e.to_string()),
+        lane: Some(index),
+        duration_ms: started.elapsed().as_millis(),
+        values,
+    };
+    ((index, lane), node_index, report)
+}
+
+async fn run_in_tab(
+    shared: &Mutex<&mut BrowserClient>,
+    handle: &WindowHandle,
+    jobs: &[BrowserJob],
+    ctx: &mut RunContext,
+) -> Result<(), BrowserError> {
+    for (i, job) in jobs.iter().enumerate() {
+        let mut client = shared.lock().await;
+        client.switch_to_handle(handle).await?;
+        if let Err(err) = job.run_in(&mut client, ctx).await {
+            eprintln!("Job {} failed: {:?}", i, err);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(graph: &JobGraph, order: &[usize]) -> Vec<String> {
+        order.iter().map(|&i| graph.nodes[i].id.clone()).collect()
+    }
+
+    #[test]
+    fn order_puts_dependencies_first() {
+        let graph = JobGraph::new()
+            .node("report", &["a", "b"], vec![])
+            .node("b", &["search"], vec![])
+            .node("a", &["search"], vec![])
+            .node("search", &[], vec![]);
+        let order = graph.order().unwrap();
+        assert_eq!(ids(&graph, &order), ["search", "b", "a", "report"]);
+    }
+
+    #[test]
+    fn order_rejects_cycles() {
+        let graph = JobGraph::new()
+            .node("start", &[], vec![])
+            .node("a", &["start", "b"], vec![])
+            .node("b", &["a"], vec![]);
+        match graph.order() {
+            Err(BrowserError::ConfigError(msg)) => {
+                assert!(msg.contains("cycle among a, b"), "{msg}")
+            }
+            other => panic!("expected a cycle error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn order_rejects_unknown_and_duplicate_ids() {
+        let unknown = JobGraph::new().node("a", &["missing"], vec![]);
+        assert!(matches!(unknown.order(), Err(BrowserError::ConfigError(_))));
+
+        let duplicate = JobGraph::new()
+            .node("a", &[], vec![])
+            .node("a", &[], vec![]);
+        assert!(matches!(
+            duplicate.order(),
+            Err(BrowserError::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,954 @@
+// Page extraction: the element types handed to the planner and every DOM extraction pass on
+// BrowserClient, in one place.
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+
+use crate::client::BrowserClient;
+use crate::diff::DomSnapshot;
+use crate::extractors::{Extractor, PageContext};
+use crate::js;
+use crate::metadata::PageMetadata;
+use crate::types::{
+    BrowserError, Form, ImageElement, Link, PageExtraction, SpatialElement, Table, TextMatch,
+};
+
+// A CSS url() reference, quoted or not; the address is the second group.
+static CSS_URL: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r#"url\(\s*(['"]?)([^'")]*)['"]?\s*\)"#).expect("valid CSS url pattern")
+});
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum InteractiveElementType {
+    #[default]
+    Clickable,
+    Typable,
+}
+
+// Viewport coordinates in CSS pixels at extraction time.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextRegion {
+    #[default]
+    Content,
+    Navigation,
+    Footer,
+    Ad,
+    CookieBanner,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextElement {
+    pub selector: String,
+    pub text: String,
+    pub index: usize,
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub region: TextRegion,
+}
+
+impl TextElement {
+    pub fn is_boilerplate(&self) -> bool {
+        self.region != TextRegion::Content
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InteractiveElement {
+    #[serde(default)]
+    pub label: usize,
+    #[serde(default)]
+    pub frame: Vec<u16>,
+    pub selector: String,
+    pub tag: String,
+    #[serde(default)]
+    pub role: String,
+    #[serde(default)]
+    pub state: Vec<String>,
+    pub text: String,
+    pub r#type: String,
+    pub placeholder: String,
+    #[serde(default = "default_true")]
+    pub visible: bool,
+    #[serde(default = "default_true")]
+    pub in_viewport: bool,
+    #[serde(default)]
+    pub occluded_by: Option<String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub bbox: BoundingBox,
+}
+
+impl InteractiveElement {
+    pub fn kind(&self) -> InteractiveElementType {
+        let typable = match self.tag.to_lowercase().as_str() {
+            "textarea" => true,
+            "input" => !matches!(
+                self.r#type.as_str(),
+                "button" | "submit" | "reset" | "checkbox" | "radio" | "file" | "image" | "hidden"
+            ),
+            _ => {
+                self.r#type == "contenteditable"
+                    || matches!(self.role.as_str(), "textbox" | "searchbox" | "combobox")
+            }
+        };
+        if typable {
+            InteractiveElementType::Typable
+        } else {
+            InteractiveElementType::Clickable
+        }
+    }
+
+    // Offscreen elements can still be scrolled to; hidden or covered ones cannot be clicked.
+    pub fn is_actionable(&self) -> bool {
+        self.visible && self.occluded_by.is_none()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Extraction results for one DOM state, keyed by URL, scroll position, a mutation count for
+// the page and each same-origin frame, and a hash of the form fields' live values. Any
+// mutation or edit changes the key and drops both entries.
+#[derive(Default)]
+pub(crate) struct ExtractionCache {
+    key: String,
+    interactive: Option<Vec<InteractiveElement>>,
+    text: Option<Vec<TextElement>>,
+}
+
+impl ExtractionCache {
+    fn refresh(&mut self, key: String) {
+        if self.key != key {
+            *self = Self {
+                key,
+                ..Self::default()
+            };
+        }
+    }
+}
+
+impl BrowserClient {
+    async fn dom_key(&self) -> Result<String, BrowserError> {
+        let js = r#"
+        const docs = [];
+        const collect = doc => {
+            docs.push(doc);
+            for (const frame of doc.querySelectorAll("iframe, frame")) {
+                try {
+                    if (frame.contentDocument) collect(frame.contentDocument);
+                } catch (e) {}
+            }
+        };
+        collect(document);
+
+        // Observers count mutations from the first call on; a reloaded document starts a new
+        // count under a new id.
+        const counts = docs.map(doc => {
+            const win = doc.defaultView;
+            if (!win) return "-";
+            if (!win.__iuMutations) {
+                const state = { id: Math.random().toString(36).slice(2), count: 0 };
+                state.observer = new win.MutationObserver(records => { state.count += records.length; });
+                state.observer.observe(doc, { subtree: true, childList: true, attributes: true, characterData: true });
+                win.__iuMutations = state;
+            }
+            const state = win.__iuMutations;
+            state.count += state.observer.takeRecords().length;
+            return `${state.id}:${state.count}`;
+        });
+
+        // Typed text and checked boxes live in properties, which no mutation reports.
+        let h = 0x811c9dc5;
+        for (const doc of docs) {
+            for (const field of doc.querySelectorAll("input, textarea, select")) {
+                const value = `${field.value}\u0001${field.checked ? 1 : 0}\u0002`;
+                for (let i = 0; i < value.length; i++) { h ^= value.charCodeAt(i); h = Math.imul(h, 0x01000193); }
+            }
+        }
+        return `${location.href}|${scrollX},${scrollY}|${innerWidth}x${innerHeight}|${counts.join(",")}|${(h >>> 0).toString(16)}`;
+        "#;
+        let key = self
+            .client
+            .execute(js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        Ok(key.as_str().unwrap_or_default().to_string())
+    }
+
+    pub fn clear_extraction_cache(&mut self) {
+        self.cache = ExtractionCache::default();
+    }
+
+    #[tracing::instrument(name = "extract.interactive", skip_all)]
+    pub async fn extract_interactive_elements(
+        &mut self,
+    ) -> Result<Vec<InteractiveElement>, BrowserError> {
+        self.cache.refresh(self.dom_key().await?);
+        if let Some(elements) = &self.cache.interactive {
+            self.last_snapshot = elements.clone();
+            return Ok(elements.clone());
+        }
+
+        let body = r##"
+        const interactive = [];
+        const ROLES = ["button", "link", "menuitem", "menuitemcheckbox", "menuitemradio", "tab", "checkbox",
+            "radio", "switch", "combobox", "option", "slider", "spinbutton", "textbox", "searchbox", "treeitem"];
+        const INTERACTIVE = ["button", "a", "input", "select", "textarea", "[onclick]",
+            "[contenteditable]:not([contenteditable=false])", ...ROLES.map(r => `[role=${r}]`)].join(", ");
+        // Everything but inline styles, handlers and password values, trimmed for prompt size.
+        const attributesOf = el => {
+            const attrs = {};
+            for (const { name, value } of el.attributes) {
+                if (name === "style" || name.startsWith("on")) continue;
+                if (name === "value" && el.type === "password") continue;
+                attrs[name] = value.length > 200 ? value.slice(0, 200) + "…" : value;
+            }
+            return attrs;
+        };
+        const stateOf = el => {
+            const state = [];
+            const aria = name => el.getAttribute("aria-" + name);
+            if (el.checked || aria("checked") === "true" || aria("pressed") === "true") state.push("checked");
+            if (aria("checked") === "mixed") state.push("mixed");
+            if (aria("selected") === "true") state.push("selected");
+            if (aria("expanded") === "true") state.push("expanded");
+            if (aria("expanded") === "false") state.push("collapsed");
+            if (el.disabled || aria("disabled") === "true") state.push("disabled");
+            return state;
+        };
+        // Same-origin frames are walked recursively; each element's selector is prefixed with
+        // its WebDriver frame path ("frame[0] >> ...") so jobs can switch into it.
+        const collect = (doc, frame) => {
+            const prefix = frame.map(i => `frame[${i}] >> `).join("");
+            for (const el of doc.querySelectorAll(INTERACTIVE)) {
+                if (el.parentElement && el.parentElement.isContentEditable) continue;
+                push(el, frame, prefix);
+            }
+            for (const child of doc.querySelectorAll("iframe, frame")) {
+                const index = Array.from(doc.defaultView.frames).indexOf(child.contentWindow);
+                let inner = null;
+                try { inner = child.contentDocument; } catch (_) {}
+                if (inner && inner.body && index >= 0) collect(inner, [...frame, index]);
+            }
+        };
+        const push = (el, frame, prefix) => {
+            const visibility = visibilityOf(el);
+            interactive.push({
+                label: interactive.length + 1,
+                frame,
+                selector: prefix + uniqueSelector(el),
+                tag: el.tagName,
+                role: el.getAttribute("role") || "",
+                state: stateOf(el),
+                text: (el.innerText || el.getAttribute("aria-label") || "").trim(),
+                type: el.isContentEditable ? "contenteditable" : el.getAttribute("type") || "",
+                placeholder: el.getAttribute("placeholder") || "",
+                visible: visibility.visible,
+                in_viewport: visibility.in_viewport,
+                occluded_by: visibility.occluded_by,
+                attributes: attributesOf(el),
+                bbox: (r => ({ x: r.left, y: r.top, width: r.width, height: r.height }))(el.getBoundingClientRect())
+            });
+        };
+        collect(document, []);
+        return interactive;
+        "##;
+        let js = [js::CSS_PATH, js::UNIQUE_SELECTOR, js::VISIBILITY, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        let elements: Vec<InteractiveElement> = serde_json::from_value(result)
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        self.last_snapshot = elements.clone();
+        self.cache.interactive = Some(elements.clone());
+        Ok(elements)
+    }
+
+    #[tracing::instrument(name = "extract.text", skip_all)]
+    pub async fn extract_text_elements(&mut self) -> Result<Vec<TextElement>, BrowserError> {
+        self.cache.refresh(self.dom_key().await?);
+        if let Some(texts) = &self.cache.text {
+            return Ok(texts.clone());
+        }
+
+        let body = r##"
+        const texts = [];
+        const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT, {
+            acceptNode: node => {
+                if (node.parentNode &&
+                    node.parentNode.nodeName !== "SCRIPT" &&
+                    node.parentNode.nodeName !== "STYLE" &&
+                    node.textContent.trim().length > 0) {
+                    return NodeFilter.FILTER_ACCEPT;
+                }
+                return NodeFilter.FILTER_REJECT;
+            }
+        });
+
+        let index = 1;
+        let node = walker.nextNode();
+        while (node) {
+            const parent = node.parentNode;
+            const selector = parent.tagName.toLowerCase() + (parent.id ? "#" + parent.id : "");
+            texts.push({
+                selector,
+                text: node.textContent.trim(),
+                index: index++,
+                tag: parent.tagName.toLowerCase(),
+                region: regionOf(parent)
+            });
+            node = walker.nextNode();
+        }
+        return texts;
+        "##;
+        let js = [js::REGION, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        let texts: Vec<TextElement> = serde_json::from_value(result)
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        self.cache.text = Some(texts.clone());
+        Ok(texts)
+    }
+
+    pub async fn snapshot(&mut self) -> Result<DomSnapshot, BrowserError> {
+        let url = self.current_url().await?;
+        let title = self.get_title().await?;
+        let interactive = self.extract_interactive_elements().await?;
+        let text = self.extract_text_elements().await?;
+
+        Ok(DomSnapshot {
+            url,
+            title,
+            interactive,
+            text,
+        })
+    }
+
+    // Scrolls to the bottom in viewport steps, then clicks "show more"/"load more" toggles a
+    // few rounds, waiting for the network to settle after each step so lazy content is in the
+    // DOM before extraction.
+    pub async fn hydrate(&mut self) -> Result<(), BrowserError> {
+        let scroll = r#"
+        const before = window.scrollY;
+        window.scrollBy(0, window.innerHeight * 0.9);
+        return window.scrollY > before;
+        "#;
+        for _ in 0..50 {
+            let moved = self
+                .client
+                .execute(scroll, vec![])
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            self.wait_for_network_idle(Duration::from_millis(400))
+                .await?;
+            if moved.as_bool() != Some(true) {
+                break;
+            }
+        }
+
+        let expand = r##"
+        // "Show more comments", "Load all", a bare "More…" or "Expand"; not "More actions".
+        const pattern = /^(show|see|load|view|read)\s+(more|all)\b|^(more|expand|expand all)\s*[.…+]*$/i;
+        let clicked = 0;
+        for (const el of document.querySelectorAll("button, a, summary, [role=button], [aria-expanded=false]")) {
+            if (el.dataset.iuExpanded || el.getClientRects().length === 0) continue;
+            const text = (el.innerText || el.getAttribute("aria-label") || "").trim();
+            if (!pattern.test(text)) continue;
+            // Menus open popups rather than content.
+            if (el.getAttribute("aria-haspopup") && el.getAttribute("aria-haspopup") !== "false") continue;
+            // Real links, even to this page with another query ("?page=2"), navigate away;
+            // only script-driven ones ("#", "javascript:") expand in place.
+            const href = el.tagName === "A" ? (el.getAttribute("href") || "").trim() : "";
+            if (href && !/^(#|javascript:)/i.test(href)) continue;
+            el.dataset.iuExpanded = "1";
+            el.click();
+            clicked++;
+        }
+        return clicked;
+        "##;
+        for _ in 0..3 {
+            let clicked = self
+                .client
+                .execute(expand, vec![])
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            self.wait_for_network_idle(Duration::from_millis(400))
+                .await?;
+            if clicked.as_u64().unwrap_or(0) == 0 {
+                break;
+            }
+        }
+
+        self.client
+            .execute("window.scrollTo(0, 0);", vec![])
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(())
+    }
+
+    // Idle means the page is loaded and no new resource entries appeared for `quiet`; gives up
+    // silently after the configured timeout so long-polling pages don't block extraction.
+    // Entries are tallied and cleared on every probe, since the browser stops recording them
+    // once its resource timing buffer (250 entries by default) is full.
+    pub async fn wait_for_network_idle(&self, quiet: Duration) -> Result<(), BrowserError> {
+        let probe = r#"
+        if (window.__iuResources === undefined) {
+            window.__iuResources = 0;
+            performance.setResourceTimingBufferSize(10000);
+            performance.addEventListener("resourcetimingbufferfull", () => {
+                window.__iuResources += performance.getEntriesByType("resource").length;
+                performance.clearResourceTimings();
+            });
+        }
+        window.__iuResources += performance.getEntriesByType("resource").length;
+        performance.clearResourceTimings();
+        return [document.readyState, window.__iuResources];
+        "#;
+        let deadline = tokio::time::Instant::now() + self.options.timeout;
+        let mut last = Value::Null;
+        let mut stable_since = tokio::time::Instant::now();
+
+        while tokio::time::Instant::now() < deadline {
+            let state = self
+                .client
+                .execute(probe, vec![])
+                .await
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            if state != last || state[0] != "complete" {
+                last = state;
+                stable_since = tokio::time::Instant::now();
+            } else if stable_since.elapsed() >= quiet {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    pub async fn extract_with_hydration(&mut self) -> Result<DomSnapshot, BrowserError> {
+        self.hydrate().await?;
+        self.snapshot().await
+    }
+
+    pub async fn extract_spatial_elements(&self) -> Result<Vec<SpatialElement>, BrowserError> {
+        let body = r##"
+        const found = [];
+        const push = (el, kind) => {
+            const rect = el.getBoundingClientRect();
+            if (rect.width < 8 || rect.height < 8 || !visibilityOf(el).visible) return;
+            const title = el.querySelector(":scope > title");
+            found.push({
+                selector: uniqueSelector(el),
+                kind,
+                label: (el.getAttribute("aria-label") || el.getAttribute("title") || (title && title.textContent) || "").trim(),
+                x: rect.left, y: rect.top, width: rect.width, height: rect.height
+            });
+        };
+        for (const el of document.querySelectorAll("canvas")) push(el, "canvas");
+        for (const el of document.querySelectorAll("svg")) {
+            if (el.ownerSVGElement) continue;
+            const controls = el.querySelectorAll("a, [onclick], [role=button], [tabindex], [cursor=pointer]");
+            if (controls.length) controls.forEach(c => push(c, "svg-control"));
+            else push(el, "svg");
+        }
+        return found;
+        "##;
+        let js = [js::CSS_PATH, js::UNIQUE_SELECTOR, js::VISIBILITY, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    pub async fn extract_tables(&self) -> Result<Vec<Table>, BrowserError> {
+        let body = r##"
+        const cellText = cell => cell.innerText.replace(/\s+/g, " ").trim();
+        const expand = row => {
+            const cells = [];
+            for (const cell of row.cells) {
+                const span = Math.max(1, Math.min(parseInt(cell.getAttribute("colspan") || "1", 10), 50));
+                for (let i = 0; i < span; i++) cells.push(cellText(cell));
+            }
+            return cells;
+        };
+
+        const tables = [];
+        document.querySelectorAll("table").forEach(table => {
+            const rows = Array.from(table.rows);
+            if (rows.length === 0) return;
+
+            let headers = [];
+            let body = rows;
+            const headRow = table.tHead && table.tHead.rows.length > 0
+                ? table.tHead.rows[table.tHead.rows.length - 1]
+                : (Array.from(rows[0].cells).every(c => c.tagName === "TH") ? rows[0] : null);
+            if (headRow) {
+                headers = expand(headRow);
+                body = rows.filter(r => r !== headRow && !(table.tHead && table.tHead.contains(r)));
+            }
+
+            const data = body.map(expand).filter(r => r.some(c => c.length > 0));
+            // Layout tables carry a single column and no headers; they are not data.
+            if (headers.length === 0 && data.every(r => r.length < 2)) return;
+
+            tables.push({
+                selector: cssPath(table),
+                caption: table.caption ? cellText(table.caption) : null,
+                headers,
+                rows: data
+            });
+        });
+        return tables;
+        "##;
+        let js = [js::CSS_PATH, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    pub async fn extract_forms(&self) -> Result<Vec<Form>, BrowserError> {
+        let body = r##"
+        const clean = s => (s || "").replace(/\s+/g, " ").trim();
+        const labelFor = field => {
+            if (field.id) {
+                const label = document.querySelector(`label[for="${CSS.escape(field.id)}"]`);
+                if (label) return clean(label.innerText);
+            }
+            const wrapping = field.closest("label");
+            if (wrapping) return clean(wrapping.innerText);
+            const labelledBy = field.getAttribute("aria-labelledby");
+            if (labelledBy) {
+                const text = labelledBy.split(/\s+/)
+                    .map(id => document.getElementById(id))
+                    .filter(Boolean)
+                    .map(el => clean(el.innerText))
+                    .join(" ");
+                if (text) return text;
+            }
+            return clean(field.getAttribute("aria-label") || field.getAttribute("placeholder") || field.getAttribute("title"));
+        };
+
+        // Named fields shadow form properties (<input name="action"> replaces form.action), so
+        // the attribute and the prototype's getter are read instead.
+        const elementsOf = Object.getOwnPropertyDescriptor(HTMLFormElement.prototype, "elements").get;
+        const actionOf = form => {
+            try { return new URL(form.getAttribute("action") || "", document.baseURI).href; } catch (_) { return ""; }
+        };
+        const forms = [];
+        for (const form of document.forms) {
+            const fields = [];
+            for (const field of elementsOf.call(form)) {
+                const tag = field.tagName.toLowerCase();
+                if (!["input", "select", "textarea"].includes(tag)) continue;
+                const type = tag === "input" ? (field.getAttribute("type") || "text").toLowerCase() : tag;
+                if (type === "hidden" || type === "submit" || type === "button" || type === "reset" || type === "image") continue;
+
+                const options = tag === "select"
+                    ? Array.from(field.options).map(o => ({ value: o.value, label: clean(o.text) }))
+                    : [];
+                fields.push({
+                    selector: cssPath(field),
+                    name: field.getAttribute("name") || "",
+                    field_type: type,
+                    label: labelFor(field),
+                    required: field.required || field.getAttribute("aria-required") === "true",
+                    options,
+                    value: (type === "checkbox" || type === "radio") ? (field.checked ? field.value : "") : (field.value || "")
+                });
+            }
+
+            const submit = form.querySelector("button[type=submit], input[type=submit], button:not([type])");
+            forms.push({
+                selector: cssPath(form),
+                action: actionOf(form),
+                method: (form.getAttribute("method") || "get").toLowerCase(),
+                fields,
+                submit_selector: submit ? cssPath(submit) : null
+            });
+        }
+        return forms;
+        "##;
+        let js = [js::CSS_PATH, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    pub async fn extract_links(&self) -> Result<Vec<Link>, BrowserError> {
+        let body = r##"
+        const links = [];
+        for (const a of document.querySelectorAll("a[href], area[href]")) {
+            let url;
+            try { url = new URL(a.href, document.baseURI); } catch (e) { continue; }
+            if (url.protocol !== "http:" && url.protocol !== "https:") continue;
+            links.push({
+                selector: cssPath(a),
+                href: url.href,
+                text: (a.innerText || a.getAttribute("aria-label") || a.getAttribute("title") || a.getAttribute("alt") || "").replace(/\s+/g, " ").trim(),
+                rel: (a.getAttribute("rel") || "").split(/\s+/).filter(Boolean),
+                same_origin: url.origin === location.origin
+            });
+        }
+        return links;
+        "##;
+        let js = [js::CSS_PATH, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    pub async fn extract_images(&self) -> Result<Vec<ImageElement>, BrowserError> {
+        let body = r##"
+        const images = [];
+        for (const img of document.images) {
+            const src = img.currentSrc || img.src;
+            if (!src || src.startsWith("data:")) continue;
+            const rect = img.getBoundingClientRect();
+            const style = getComputedStyle(img);
+            const visible = rect.width > 0 && rect.height > 0 &&
+                style.display !== "none" && style.visibility !== "hidden" && parseFloat(style.opacity) > 0;
+            images.push({
+                selector: cssPath(img),
+                src,
+                alt: (img.getAttribute("alt") || "").trim(),
+                natural_width: img.naturalWidth,
+                natural_height: img.naturalHeight,
+                rendered_width: rect.width,
+                rendered_height: rect.height,
+                visible,
+                in_viewport: visible && rect.bottom > 0 && rect.right > 0 &&
+                    rect.top < window.innerHeight && rect.left < window.innerWidth
+            });
+        }
+        return images;
+        "##;
+        let js = [js::CSS_PATH, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    // Failed downloads are logged and skipped so one broken image doesn't lose the rest.
+    pub async fn download_images(
+        &self,
+        images: &[ImageElement],
+    ) -> Result<Vec<(ImageElement, PathBuf)>, BrowserError> {
+        let mut saved = vec![];
+        for image in images {
+            self.ensure_allowed(&image.src)?;
+            match self.fetcher.download(&image.src).await {
+                Ok(path) => saved.push((image.clone(), path)),
+                Err(e) => eprintln!("Image download failed for {}: {e}", image.src),
+            }
+        }
+        Ok(saved)
+    }
+
+    // Case-insensitive search over the page text. Each match is the innermost element holding
+    // all of it, so text split across inline elements ("<b>Total</b>: 5") is still found;
+    // `context` is the surrounding block's text trimmed to a window around the match.
+    pub async fn find_text(&self, query: &str) -> Result<Vec<TextMatch>, BrowserError> {
+        let body = r##"
+        const needle = arguments[0].replace(/\s+/g, " ").trim().toLowerCase();
+        const blocks = "p, li, td, th, dd, dt, h1, h2, h3, h4, h5, h6, label, section, article, div";
+        const norm = text => (text || "").replace(/\s+/g, " ").trim();
+        const holds = el => !["SCRIPT", "STYLE", "NOSCRIPT", "TEMPLATE"].includes(el.nodeName)
+            && norm(el.textContent).toLowerCase().includes(needle);
+        const found = [];
+        const visit = el => {
+            let inner = false;
+            for (const child of el.children) {
+                if (holds(child)) {
+                    inner = true;
+                    visit(child);
+                }
+            }
+            if (!inner) found.push(el);
+        };
+        if (needle && document.body && holds(document.body)) visit(document.body);
+
+        return found.map(el => {
+            const text = norm(el.innerText) || norm(el.textContent);
+            const block = norm((el.closest(blocks) || el).innerText) || text;
+            const at = Math.max(block.toLowerCase().indexOf(needle), 0);
+            const start = Math.max(at - 80, 0);
+            const end = Math.min(at + needle.length + 80, block.length);
+            return {
+                selector: uniqueSelector(el),
+                text,
+                context: (start > 0 ? "…" : "") + block.slice(start, end) + (end < block.length ? "…" : ""),
+                visible: visibilityOf(el).visible
+            };
+        });
+        "##;
+        let js = [js::CSS_PATH, js::UNIQUE_SELECTOR, js::VISIBILITY, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![json!(query)])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+
+    // Walks "next" links/buttons, extracting text, tables and links from every page. Without an
+    // explicit selector the next control is auto-detected (rel=next, aria-label, "Next"/"»").
+    pub async fn extract_all_pages(
+        &mut self,
+        next_selector: Option<&str>,
+        max_pages: usize,
+    ) -> Result<Vec<PageExtraction>, BrowserError> {
+        let mut pages = vec![];
+        let mut seen = HashSet::new();
+
+        while pages.len() < max_pages {
+            let signature = self.page_signature().await?;
+            if !seen.insert(signature.clone()) {
+                break;
+            }
+
+            pages.push(PageExtraction {
+                url: self.current_url().await?,
+                text: self.extract_text_elements().await?,
+                tables: self.extract_tables().await?,
+                links: self.extract_links().await?,
+            });
+            if pages.len() == max_pages {
+                break;
+            }
+
+            let next = match next_selector {
+                Some(selector) => self
+                    .client
+                    .find_all(fantoccini::Locator::Css(selector))
+                    .await
+                    .ok()
+                    .filter(|found| !found.is_empty())
+                    .map(|_| selector.to_string()),
+                None => self.find_next_control().await?,
+            };
+            let Some(next) = next else {
+                break;
+            };
+            self.click_element(&next).await?;
+
+            let deadline = tokio::time::Instant::now() + self.options.timeout;
+            while self.page_signature().await? == signature {
+                if tokio::time::Instant::now() >= deadline {
+                    return Ok(pages);
+                }
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+        }
+
+        Ok(pages)
+    }
+
+    async fn find_next_control(&self) -> Result<Option<String>, BrowserError> {
+        let body = r##"
+        const isNext = el => {
+            const rel = (el.getAttribute("rel") || "").toLowerCase().split(/\s+/);
+            const label = (el.getAttribute("aria-label") || el.getAttribute("title") || "").toLowerCase();
+            const text = el.innerText.replace(/\s+/g, " ").trim().toLowerCase();
+            return rel.includes("next") || /^next( page)?$/.test(label) ||
+                /^(next|next page|next ›|next »|›|»|>)$/.test(text);
+        };
+        const usable = el => !el.disabled && el.getAttribute("aria-disabled") !== "true" &&
+            !el.classList.contains("disabled") && el.getClientRects().length > 0;
+        const candidates = document.querySelectorAll("a[href], button, [role=button], [role=link]");
+        const next = Array.from(candidates).find(el => isNext(el) && usable(el));
+        return next ? uniqueSelector(next) : null;
+        "##;
+        let js = [js::UNIQUE_SELECTOR, body].concat();
+
+        let result = self
+            .client
+            .execute(&js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        Ok(result.as_str().map(str::to_string))
+    }
+
+    async fn page_signature(&self) -> Result<String, BrowserError> {
+        let result = self
+            .client
+            .execute(
+                "return location.href + '\\n' + document.body.innerText.slice(0, 2000);",
+                vec![],
+            )
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(result.as_str().unwrap_or_default().to_string())
+    }
+
+    // Saves a single self-contained HTML file of the page as currently rendered; images,
+    // stylesheets and the fonts and backgrounds they refer to are embedded as data URLs so the
+    // copy opens offline.
+    pub async fn archive_page(&self, path: &Path) -> Result<(), BrowserError> {
+        let result = self
+            .client
+            .execute(js::ARCHIVE_PAGE, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+
+        let mut html = result["html"].as_str().unwrap_or_default().to_string();
+        let resources = result["resources"].as_array().cloned().unwrap_or_default();
+        for url in resources.iter().filter_map(Value::as_str) {
+            if !self.options.allows_url(url) {
+                continue;
+            }
+            let Some(data_url) = self.data_url(url).await else {
+                continue;
+            };
+            let escaped = url.replace('&', "&amp;");
+            for (prefix, suffix) in [
+                ("src=\"", "\""),
+                ("href=\"", "\""),
+                ("url(\"", "\")"),
+                ("url('", "')"),
+            ] {
+                let quoted = |u: &str| format!("{prefix}{u}{suffix}");
+                let data = quoted(&data_url);
+                html = html
+                    .replace(&quoted(url), &data)
+                    .replace(&quoted(&escaped), &data);
+            }
+        }
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        fs::write(path, html).map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    // A resource as a data URL. Stylesheets (cross-origin ones, and their @imports) have
+    // their own url()s embedded first, since a data URL gives relative ones nothing to
+    // resolve against.
+    async fn data_url(&self, url: &str) -> Option<String> {
+        let (bytes, mime) = self.fetcher.fetch_bytes(url).await.ok()?;
+        if !mime.starts_with("text/css") {
+            return Some(format!("data:{mime};base64,{}", BASE64.encode(&bytes)));
+        }
+        let base = url::Url::parse(url).ok()?;
+        let mut css = String::from_utf8_lossy(&bytes).into_owned();
+        let refs: Vec<String> = CSS_URL
+            .captures_iter(&css)
+            .map(|caps| caps[2].to_string())
+            .collect();
+        for reference in refs {
+            if reference.starts_with("data:") || reference.starts_with('#') {
+                continue;
+            }
+            let Ok(target) = base.join(&reference) else {
+                continue;
+            };
+            if !self.options.allows_url(target.as_str()) {
+                continue;
+            }
+            let Ok((bytes, mime)) = self.fetcher.fetch_bytes(target.as_str()).await else {
+                continue;
+            };
+            let data = format!("data:{mime};base64,{}", BASE64.encode(&bytes));
+            css = CSS_URL
+                .replace_all(&css, |caps: &regex::Captures| {
+                    if caps[2] == *reference {
+                        format!("url(\"{data}\")")
+                    } else {
+                        caps[0].to_string()
+                    }
+                })
+                .into_owned();
+        }
+        Some(format!("data:text/css;base64,{}", BASE64.encode(css)))
+    }
+
+    pub async fn extract_with<E: Extractor>(
+        &mut self,
+        extractor: &E,
+    ) -> Result<Option<E::Output>, BrowserError> {
+        let page = PageContext::collect(self).await?;
+        Ok(extractor.extract(&page))
+    }
+
+    pub async fn extract_metadata(&self) -> Result<PageMetadata, BrowserError> {
+        let js = r##"
+        const open_graph = {}, twitter = {}, meta = {};
+        for (const tag of document.querySelectorAll("meta[content]")) {
+            const key = (tag.getAttribute("property") || tag.getAttribute("name") || tag.getAttribute("itemprop") || "").trim();
+            if (!key) continue;
+            const value = tag.getAttribute("content").trim();
+            const lower = key.toLowerCase();
+            if (lower.startsWith("og:") || lower.startsWith("article:") || lower.startsWith("product:")) open_graph[lower] = value;
+            else if (lower.startsWith("twitter:")) twitter[lower] = value;
+            else meta[lower] = value;
+        }
+
+        const json_ld = [];
+        for (const script of document.querySelectorAll('script[type="application/ld+json"]')) {
+            try { json_ld.push(JSON.parse(script.textContent)); } catch (e) { }
+        }
+
+        const canonical = document.querySelector('link[rel="canonical"]');
+        return {
+            title: document.title,
+            description: meta["description"] || open_graph["og:description"] || null,
+            canonical: canonical ? canonical.href : null,
+            language: document.documentElement.lang || null,
+            open_graph,
+            twitter,
+            meta,
+            json_ld
+        };
+        "##;
+
+        let result = self
+            .client
+            .execute(js, vec![])
+            .await
+            .map_err(|e| BrowserError::DomExtractionError(e.to_string()))?;
+        serde_json::from_value(result).map_err(|e| BrowserError::DomExtractionError(e.to_string()))
+    }
+}
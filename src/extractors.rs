@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::BrowserClient;
+use crate::metadata::{ArticleInfo, PageMetadata, ProductInfo, name_of, text};
+use crate::types::{BrowserError, TextElement, TextRegion};
+
+// Everything an extractor may look at, gathered once per page.
+#[derive(Debug, Clone)]
+pub struct PageContext {
+    pub url: String,
+    pub metadata: PageMetadata,
+    pub text: Vec<TextElement>,
+}
+
+impl PageContext {
+    pub async fn collect(client: &mut BrowserClient) -> Result<Self, BrowserError> {
+        let metadata = client.extract_metadata().await?;
+        let text = client.extract_text_elements().await?;
+        let url = client
+            .client
+            .current_url()
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?
+            .to_string();
+        Ok(Self {
+            url,
+            metadata,
+            text,
+        })
+    }
+
+    pub fn content(&self) -> impl Iterator<Item = &TextElement> {
+        self.text
+            .iter()
+            .filter(|el| el.region == TextRegion::Content)
+    }
+
+    fn first_tagged(&self, tags: &[&str]) -> Option<String> {
+        self.content()
+            .find(|el| tags.contains(&el.tag.as_str()))
+            .map(|el| el.text.clone())
+    }
+}
+
+// Turns a page into a typed record. Implement this for site- or domain-specific structures;
+// `extract` returns None when the page isn't of the extractor's kind.
+pub trait Extractor {
+    type Output: Serialize;
+
+    fn name(&self) -> &str;
+
+    fn extract(&self, page: &PageContext) -> Option<Self::Output>;
+}
+
+// The page's article metadata, with the headline filled in from the page when the metadata
+// lacks one, plus the body text the metadata never carries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Article {
+    #[serde(flatten)]
+    pub info: ArticleInfo,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct JobPosting {
+    pub title: String,
+    pub company: Option<String>,
+    pub location: Option<String>,
+    pub salary: Option<String>,
+    pub date_posted: Option<String>,
+}
+
+pub struct ProductExtractor;
+pub struct ArticleExtractor;
+pub struct JobPostingExtractor;
+
+// Products always come back with a name: the metadata's, else the page heading or title.
+impl Extractor for ProductExtractor {
+    type Output = ProductInfo;
+
+    fn name(&self) -> &str {
+        "product"
+    }
+
+    fn extract(&self, page: &PageContext) -> Option<ProductInfo> {
+        if let Some(info) = page.metadata.product() {
+            return Some(ProductInfo {
+                name: info
+                    .name
+                    .or_else(|| page.first_tagged(&["h1"]))
+                    .or_else(|| Some(page.metadata.title.clone())),
+                ..info
+            });
+        }
+
+        // No structured data: a heading plus a price-looking text node is enough.
+        let price = page
+            .content()
+            .map(|el| el.text.trim())
+            .find(|text| looks_like_price(text))?;
+        Some(ProductInfo {
+            name: Some(page.first_tagged(&["h1"])?),
+            price: Some(price.to_string()),
+            ..ProductInfo::default()
+        })
+    }
+}
+
+impl Extractor for ArticleExtractor {
+    type Output = Article;
+
+    fn name(&self) -> &str {
+        "article"
+    }
+
+    fn extract(&self, page: &PageContext) -> Option<Article> {
+        let info = page.metadata.article()?;
+        let body = page
+            .content()
+            .filter(|el| el.tag == "p")
+            .map(|el| el.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Some(Article {
+            info: ArticleInfo {
+                headline: info
+                    .headline
+                    .or_else(|| page.first_tagged(&["h1"]))
+                    .or_else(|| Some(page.metadata.title.clone())),
+                ..info
+            },
+            body,
+        })
+    }
+}
+
+impl Extractor for JobPostingExtractor {
+    type Output = JobPosting;
+
+    fn name(&self) -> &str {
+        "job_posting"
+    }
+
+    fn extract(&self, page: &PageContext) -> Option<JobPosting> {
+        let node = page.metadata.find_type(&["JobPosting"])?;
+        let address = &node["jobLocation"]["address"];
+        let location = [
+            &address["addressLocality"],
+            &address["addressRegion"],
+            &address["addressCountry"],
+        ]
+        .into_iter()
+        .filter_map(text)
+        .collect::<Vec<_>>();
+        let salary = &node["baseSalary"]["value"];
+        let salary = match (text(&salary["minValue"]), text(&salary["maxValue"])) {
+            (Some(min), Some(max)) => Some(format!("{min}-{max}")),
+            (min, max) => min.or(max).or_else(|| text(&salary["value"])),
+        }
+        .map(|amount| match text(&node["baseSalary"]["currency"]) {
+            Some(currency) => format!("{amount} {currency}"),
+            None => amount,
+        });
+
+        Some(JobPosting {
+            title: text(&node["title"]).unwrap_or_else(|| page.metadata.title.clone()),
+            company: name_of(&node["hiringOrganization"]),
+            location: (!location.is_empty()).then(|| location.join(", ")),
+            salary,
+            date_posted: text(&node["datePosted"]),
+        })
+    }
+}
+
+fn looks_like_price(text: &str) -> bool {
+    let text = text.trim();
+    text.len() <= 24
+        && text.chars().any(|c| c.is_ascii_digit())
+        && ["$", "€", "£", "¥", "USD", "EUR", "GBP"]
+            .iter()
+            .any(|symbol| text.contains(symbol))
+}
@@ -1,46 +1,1192 @@
+use schemars::{JsonSchema, r#gen::SchemaSettings};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::time::Duration;
 
 use crate::BrowserClient;
-use crate::types::BrowserError;
+use crate::content::FetchedContent;
+use crate::llm::{ToolCall, ToolSpec};
+use crate::types::{BrowserError, ScrollBy, SelectChoice};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+// Doc comments on variants and fields are part of the planner prompt: `action_space()`
+// renders them from the derived JSON schema.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum BrowserJob {
+    /// Load a URL in the current tab. PDFs, JSON, CSV and images are downloaded instead, and their contents become the job's output.
     Navigate(String),
+    /// Go back to the previous page in the tab's history.
+    Back,
+    /// Go forward again after Back.
+    Forward,
+    /// Reload the current page.
+    Refresh,
+    /// Open a new tab and switch to it, loading a URL when one is given.
+    OpenTab {
+        /// URL to load in the new tab.
+        url: Option<String>,
+    },
+    /// Switch to the open tab at a 0-based position, in the order tabs were opened.
+    SwitchTab(usize),
+    /// Close the tab at a 0-based position and switch to the first one left.
+    CloseTab(usize),
+    /// Click the element matching a CSS selector.
     Click(String),
-    Type { selector: String, text: String },
+    /// Move the pointer over the element matching a CSS selector, to open hover menus or tooltips.
+    Hover(String),
+    /// Click at viewport coordinates, for canvas or SVG content without selectors.
+    ClickAt {
+        /// Pixels from the left edge of the viewport.
+        x: i64,
+        /// Pixels from the top edge of the viewport.
+        y: i64,
+    },
+    /// Type text into an input or textarea.
+    Type {
+        /// CSS selector of the field.
+        selector: String,
+        /// Text to type.
+        text: String,
+    },
+    /// Pick an option of a <select> dropdown by value, label or index; only the first one given is used.
+    SelectOption {
+        /// CSS selector of the <select> element.
+        selector: String,
+        /// The option's value attribute.
+        value: Option<String>,
+        /// The option's visible text.
+        label: Option<String>,
+        /// 0-based position of the option.
+        index: Option<usize>,
+    },
+    /// Press a key: Enter, Escape, Tab, Backspace, Delete, Space, ArrowUp, ArrowDown, ArrowLeft, ArrowRight, PageUp, PageDown, Home, End or a single character.
+    PressKey {
+        /// Name of the key.
+        key: String,
+        /// CSS selector of the element to press it in; the focused element when omitted.
+        selector: Option<String>,
+    },
+    /// Attach a local file to a file input.
+    UploadFile {
+        /// CSS selector of the <input type=file>.
+        selector: String,
+        /// Path of the file to upload.
+        path: String,
+    },
+    /// Wait for a download whose filename matches a pattern (* and ? wildcards, e.g. "invoice-*.pdf") to finish, and report where it was saved.
+    WaitForDownload {
+        /// Filename pattern.
+        pattern: String,
+        /// Name to store the saved file's path under.
+        store_as: Option<String>,
+        /// Longest to wait, in seconds; the browser timeout when omitted.
+        timeout_seconds: Option<u64>,
+    },
+    /// Fill several form fields in one step and optionally submit the form. Selects take an option label or value, checkboxes and radio buttons "true" or "false", other fields their text. Fails if a required field is left empty.
+    FillForm {
+        /// Value for each field, keyed by the field's CSS selector.
+        fields: BTreeMap<String, String>,
+        /// Whether to submit the form once filled.
+        #[serde(default)]
+        submit: bool,
+    },
+    /// Submit the form containing the element matching a CSS selector, for forms without a usable submit button.
+    SubmitForm(String),
+    /// Wait until an element matching a CSS selector appears.
     WaitFor(String),
+    /// Scroll the element matching a CSS selector into view.
     ScrollTo(String),
-    Screenshot { prefix: String },
+    /// Scroll the page without a target element, to reveal lazily loaded content.
+    Scroll(ScrollBy),
+    /// Pause for a number of milliseconds, to let animations or debounced updates finish. Prefer WaitFor when a selector marks the moment.
+    Sleep(u64),
+    /// Read the text of the element matching a CSS selector and keep it under a name, for confirmation numbers, prices or titles the task must report.
+    ExtractText {
+        /// CSS selector of the element.
+        selector: String,
+        /// Name to store the text under.
+        store_as: String,
+    },
+    /// Read an attribute (href, value, src, data-*) of the element matching a CSS selector and keep it under a name; links come back as absolute URLs.
+    GetAttribute {
+        /// CSS selector of the element.
+        selector: String,
+        /// Name of the attribute.
+        attribute: String,
+        /// Name to store the value under.
+        store_as: String,
+    },
+    /// Fail unless the given text is visible somewhere on the page.
+    AssertText(String),
+    /// Fail unless an element matches a CSS selector.
+    AssertElementExists(String),
+    /// Fail unless the text of the element matching a CSS selector contains the given text.
+    AssertTextContains {
+        /// CSS selector of the element.
+        selector: String,
+        /// Text the element must contain.
+        text: String,
+    },
+    /// Fail unless the current URL matches a regular expression.
+    AssertUrlMatches(String),
+    /// Fail unless the page title contains the given text.
+    AssertTitleContains(String),
+    /// Run the jobs in then when the condition holds on the current page, otherwise those in else, e.g. to dismiss a cookie banner only when one is shown.
+    If {
+        /// What to check on the page.
+        condition: Condition,
+        /// Jobs to run when it holds.
+        then: Vec<BrowserJob>,
+        /// Jobs to run when it does not.
+        #[serde(default)]
+        r#else: Vec<BrowserJob>,
+    },
+    /// Run the jobs in body again and again until the condition holds, checking it before each round, e.g. clicking Next until the last page. Fails if it still does not hold after max_iters rounds.
+    RepeatUntil {
+        /// What to check on the page.
+        condition: Condition,
+        /// Jobs to run each round.
+        body: Vec<BrowserJob>,
+        /// Most rounds to run.
+        #[serde(default = "default_max_iters")]
+        max_iters: usize,
+    },
+    /// Run the jobs in body once for every element matching a CSS selector, e.g. clicking delete on every row. Inside body, "$item" in a selector stands for the current element, as in "$item button.delete".
+    ForEach {
+        /// CSS selector of the elements to go through.
+        selector: String,
+        /// Jobs to run for each element.
+        body: Vec<BrowserJob>,
+    },
+    /// Run one job with its own time limit, retries and failure handling, so one flaky step does not end the run, e.g. a WaitFor for a banner that only sometimes shows.
+    Guarded {
+        /// The job to run.
+        job: Box<BrowserJob>,
+        /// Longest one attempt may take, in milliseconds; no limit when omitted.
+        #[schemars(example = "example_timeout_ms")]
+        timeout_ms: Option<u64>,
+        /// Further attempts after a failed one.
+        #[serde(default)]
+        retries: u32,
+        /// What to do once every attempt has failed.
+        #[serde(default)]
+        on_failure: OnFailure,
+    },
+    /// Run JavaScript in the page as a function body and capture its return value. Only for interactions no other job covers; it may be disabled.
+    ExecuteJs {
+        /// Function body; arguments are in `arguments`.
+        script: String,
+        /// Values passed as `arguments`.
+        #[serde(default)]
+        args: Vec<Value>,
+        /// Name to store the return value under.
+        store_as: Option<String>,
+    },
+    /// Save a screenshot of the viewport.
+    Screenshot {
+        /// Filename prefix for the saved PNG.
+        prefix: String,
+    },
+    /// Answer a question from the current page in text, citing the elements the answer comes from. Use it last when the task asks for information rather than an action.
+    Answer {
+        /// The question to answer.
+        question: String,
+    },
+    /// Call one of the custom tools listed separately.
+    Tool {
+        /// Name of the tool.
+        name: String,
+        /// Arguments matching the tool's schema.
+        args: Value,
+    },
+}
+
+// What a Guarded job does once all of its attempts have failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnFailure {
+    // Fail the run, as an unguarded job would.
+    #[default]
+    Abort,
+    // Carry on with the next job.
+    Skip,
+    // Save a screenshot of the page for later, then carry on.
+    ScreenshotAndContinue,
+}
+
+// A check on the current page, for jobs that branch on it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub enum Condition {
+    /// An element matches a CSS selector.
+    ElementExists(String),
+    /// The text is visible somewhere on the page.
+    TextVisible(String),
+    /// The text of the element matching a CSS selector contains the text.
+    TextContains { selector: String, text: String },
+    /// The current URL matches a regular expression.
+    UrlMatches(String),
+    /// The page title contains the text.
+    TitleContains(String),
+}
+
+impl Condition {
+    pub fn selector(&self) -> Option<&str> {
+        match self {
+            Condition::ElementExists(selector) | Condition::TextContains { selector, .. } => {
+                Some(selector)
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn holds(&self, client: &mut BrowserClient) -> Result<bool, BrowserError> {
+        match self {
+            Condition::ElementExists(selector) => client.selector_exists(selector).await,
+            Condition::TextVisible(text) => {
+                Ok(client.find_text(text).await?.iter().any(|m| m.visible))
+            }
+            Condition::TextContains { selector, text } => {
+                if !client.selector_exists(selector).await? {
+                    return Ok(false);
+                }
+                Ok(client.element_text(selector).await?.contains(text.as_str()))
+            }
+            Condition::UrlMatches(pattern) => {
+                let re = regex::Regex::new(pattern).map_err(|e| {
+                    BrowserError::OperationError(format!("Invalid URL pattern '{pattern}': {e}"))
+                })?;
+                Ok(re.is_match(&client.current_url().await?))
+            }
+            Condition::TitleContains(part) => Ok(client.get_title().await?.contains(part.as_str())),
+        }
+    }
 }
 
 impl BrowserJob {
+    pub fn schema() -> Value {
+        // Inlined so each variant carries its full field definitions.
+        let schema = SchemaSettings::draft07()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<BrowserJob>();
+        serde_json::to_value(schema).unwrap_or_default()
+    }
+
+    pub fn action_space() -> String {
+        let schema = Self::schema();
+        let variants = schema["oneOf"].as_array().cloned().unwrap_or_default();
+
+        let mut lines = vec![];
+        for variant in &variants {
+            let description = variant["description"].as_str().unwrap_or("");
+
+            if let Some(names) = variant["enum"].as_array() {
+                for name in names.iter().filter_map(Value::as_str) {
+                    lines.push(format!("- {name}: {description}\n  e.g. \"{name}\""));
+                }
+                continue;
+            }
+
+            let Some((name, inner)) = variant["properties"]
+                .as_object()
+                .and_then(|props| props.iter().next())
+            else {
+                continue;
+            };
+
+            let signature = match inner["properties"].as_object() {
+                Some(fields) => {
+                    let fields: Vec<String> = fields
+                        .iter()
+                        .map(|(field, def)| {
+                            let doc = def["description"]
+                                .as_str()
+                                .map(|d| format!(" ({})", d.trim_end_matches('.')))
+                                .unwrap_or_default();
+                            format!("{field}: {}{doc}", type_name(def))
+                        })
+                        .collect();
+                    format!(" {{ {} }}", fields.join(", "))
+                }
+                None => format!("({})", type_name(inner)),
+            };
+            let example = json!({ name: example_value(inner, "value") });
+            lines.push(format!(
+                "- {name}{signature}: {description}\n  e.g. {example}"
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    // One tool per variant for native function calling. Tool arguments must be objects, so
+    // newtype variants take their value as a single `value` argument.
+    pub fn tools() -> Vec<ToolSpec> {
+        let schema = Self::schema();
+        let mut tools = vec![];
+        for variant in schema["oneOf"].as_array().into_iter().flatten() {
+            let description = variant["description"].as_str().unwrap_or("").to_string();
+
+            if let Some(names) = variant["enum"].as_array() {
+                for name in names.iter().filter_map(Value::as_str) {
+                    tools.push(ToolSpec {
+                        name: name.to_string(),
+                        description: description.clone(),
+                        parameters: json!({ "type": "object", "properties": {} }),
+                    });
+                }
+                continue;
+            }
+
+            let Some((name, inner)) = variant["properties"]
+                .as_object()
+                .and_then(|props| props.iter().next())
+            else {
+                continue;
+            };
+            let parameters = if takes_fields(inner) {
+                let mut parameters = inner.clone();
+                // Nested jobs point at the full job schema.
+                if parameters.to_string().contains("\"$ref\"") {
+                    parameters["definitions"] = schema["definitions"].clone();
+                }
+                parameters
+            } else {
+                json!({
+                    "type": "object",
+                    "properties": { "value": inner },
+                    "required": ["value"],
+                })
+            };
+            tools.push(ToolSpec {
+                name: name.clone(),
+                description,
+                parameters,
+            });
+        }
+        tools
+    }
+
+    pub fn from_tool_call(call: &ToolCall) -> Result<BrowserJob, BrowserError> {
+        let schema = Self::schema();
+        let name = call.name.as_str();
+        let value = schema["oneOf"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find_map(|variant| {
+                if variant["enum"]
+                    .as_array()
+                    .is_some_and(|names| names.iter().any(|n| n == name))
+                {
+                    return Some(json!(name));
+                }
+                let inner = variant["properties"].get(name)?;
+                Some(if takes_fields(inner) {
+                    json!({ name: call.arguments })
+                } else {
+                    json!({ name: call.arguments["value"] })
+                })
+            })
+            .ok_or_else(|| BrowserError::OperationError(format!("Unknown tool: '{name}'")))?;
+        serde_json::from_value(value)
+            .map_err(|e| BrowserError::OperationError(format!("Invalid '{name}' call: {e}")))
+    }
+
+    // The selector a job acts on, if any.
+    pub fn selector(&self) -> Option<&str> {
+        match self {
+            BrowserJob::Click(selector)
+            | BrowserJob::SubmitForm(selector)
+            | BrowserJob::Hover(selector)
+            | BrowserJob::WaitFor(selector)
+            | BrowserJob::ScrollTo(selector)
+            | BrowserJob::Type { selector, .. }
+            | BrowserJob::SelectOption { selector, .. }
+            | BrowserJob::ExtractText { selector, .. }
+            | BrowserJob::GetAttribute { selector, .. }
+            | BrowserJob::AssertElementExists(selector)
+            | BrowserJob::AssertTextContains { selector, .. }
+            | BrowserJob::ForEach { selector, .. }
+            | BrowserJob::UploadFile { selector, .. }
+            | BrowserJob::PressKey {
+                selector: Some(selector),
+                ..
+            } => Some(selector),
+            BrowserJob::FillForm { fields, .. } => fields.keys().next().map(String::as_str),
+            BrowserJob::Guarded { job, .. } => job.selector(),
+            _ => None,
+        }
+    }
+
+    // The URL a job loads, if any.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            BrowserJob::Navigate(url) | BrowserJob::OpenTab { url: Some(url) } => Some(url),
+            BrowserJob::Guarded { job, .. } => job.url(),
+            _ => None,
+        }
+    }
+
+    // Whether later jobs act on a different page than the one the plan was made against.
+    pub fn leaves_page(&self) -> bool {
+        match self {
+            BrowserJob::Guarded { job, .. } => job.leaves_page(),
+            _ => matches!(
+                self,
+                BrowserJob::Navigate(_)
+                    | BrowserJob::Back
+                    | BrowserJob::Forward
+                    | BrowserJob::OpenTab { .. }
+                    | BrowserJob::SwitchTab(_)
+                    | BrowserJob::CloseTab(_)
+            ),
+        }
+    }
+
+    // The variable a job's output is stored under, if any.
+    pub fn store_as(&self) -> Option<&str> {
+        match self {
+            BrowserJob::ExtractText { store_as, .. }
+            | BrowserJob::GetAttribute { store_as, .. }
+            | BrowserJob::WaitForDownload {
+                store_as: Some(store_as),
+                ..
+            }
+            | BrowserJob::ExecuteJs {
+                store_as: Some(store_as),
+                ..
+            } => Some(store_as),
+            BrowserJob::Guarded { job, .. } => job.store_as(),
+            _ => None,
+        }
+    }
+
+    // Jobs to run next because of what this one found: the chosen branch of an If, the next
+    // round of a loop.
+    pub fn follow_up(&self, output: Option<&Value>) -> Vec<BrowserJob> {
+        let holds = output.and_then(|o| o["condition"].as_bool()) == Some(true);
+        match self {
+            BrowserJob::If { then, r#else, .. } => {
+                if holds {
+                    then.clone()
+                } else {
+                    r#else.clone()
+                }
+            }
+            BrowserJob::RepeatUntil {
+                condition,
+                body,
+                max_iters,
+            } if !holds => {
+                let mut jobs = body.clone();
+                jobs.push(BrowserJob::RepeatUntil {
+                    condition: condition.clone(),
+                    body: body.clone(),
+                    max_iters: max_iters.saturating_sub(1),
+                });
+                jobs
+            }
+            BrowserJob::ForEach { body, .. } => output
+                .and_then(|o| o["items"].as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .flat_map(|item| for_item(body, item))
+                .collect(),
+            // A skipped job leaves no output, and nothing to follow up on.
+            BrowserJob::Guarded { job, .. } if output.is_some() => job.follow_up(output),
+            _ => vec![],
+        }
+    }
+
+    // The job with `{{name}}` placeholders in its strings filled from `vars`. Jobs nested in
+    // an If, RepeatUntil or ForEach are left alone; they are filled when they run, so they can
+    // use values stored by the jobs before them.
+    pub fn with_variables(
+        &self,
+        vars: &BTreeMap<String, Value>,
+    ) -> Result<BrowserJob, BrowserError> {
+        if let BrowserJob::Guarded { .. } = self {
+            let mut guarded = self.clone();
+            if let BrowserJob::Guarded { job, .. } = &mut guarded {
+                **job = job.with_variables(vars)?;
+            }
+            return Ok(guarded);
+        }
+        let mut value =
+            serde_json::to_value(self).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        match value
+            .as_object_mut()
+            .and_then(|job| job.values_mut().next())
+        {
+            Some(Value::Object(fields)) => {
+                for (name, field) in fields.iter_mut() {
+                    if !NESTED_FIELDS.contains(&name.as_str()) {
+                        fill_value(field, vars)?;
+                    }
+                }
+            }
+            Some(inner) => fill_value(inner, vars)?,
+            None => return Ok(self.clone()),
+        }
+        serde_json::from_value(value).map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    // Names the job's own strings refer to as `{{name}}`, leaving nested jobs out.
+    pub fn variables_used(&self) -> Vec<String> {
+        if let BrowserJob::Guarded { job, .. } = self {
+            return job.variables_used();
+        }
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(Value::Object(fields)) = value
+            .as_object_mut()
+            .and_then(|job| job.values_mut().next())
+        {
+            fields.retain(|name, _| !NESTED_FIELDS.contains(&name.as_str()));
+        }
+        let mut names: Vec<String> = PLACEHOLDER
+            .captures_iter(&value.to_string())
+            .map(|caps| caps[1].to_string())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    // The job lists nested in this one by field name: an If's branches, a loop's body.
+    pub fn nested(&self) -> Vec<(&'static str, &[BrowserJob])> {
+        match self {
+            BrowserJob::If { then, r#else, .. } => vec![("then", then), ("else", r#else)],
+            BrowserJob::RepeatUntil { body, .. } | BrowserJob::ForEach { body, .. } => {
+                vec![("body", body)]
+            }
+            _ => vec![],
+        }
+    }
+
     pub async fn run(&self, client: &mut BrowserClient) -> Result<(), BrowserError> {
+        self.run_in(client, &mut RunContext::default()).await
+    }
+
+    // Runs the job with its placeholders filled from `ctx`, then what it leads to, storing
+    // whatever they read into `ctx` for the jobs after them.
+    pub async fn run_in(
+        &self,
+        client: &mut BrowserClient,
+        ctx: &mut RunContext,
+    ) -> Result<(), BrowserError> {
+        let job = self.with_variables(&ctx.values)?;
+        let output = job.execute(client).await?;
+        if let (Some(name), Some(value)) = (job.store_as(), &output) {
+            ctx.set(name, value.clone());
+        }
+        for next in job.follow_up(output.as_ref()) {
+            Box::pin(next.run_in(client, ctx)).await?;
+        }
+        Ok(())
+    }
+
+    // Runs the job and returns what it read from the page, for jobs that read anything.
+    pub async fn execute(&self, client: &mut BrowserClient) -> Result<Option<Value>, BrowserError> {
+        let output = match self {
+            // A document the URL leads to (PDF, JSON, CSV, image) is the job's output.
+            BrowserJob::Navigate(url) | BrowserJob::OpenTab { url: Some(url) } => {
+                if let BrowserJob::OpenTab { .. } = self {
+                    client.open_tab().await?;
+                }
+                return match client.navigate(url).await? {
+                    FetchedContent::Html => Ok(None),
+                    content => serde_json::to_value(content)
+                        .map(Some)
+                        .map_err(|e| BrowserError::OperationError(e.to_string())),
+                };
+            }
+            BrowserJob::ExtractText { selector, .. } => client.element_text(selector).await?,
+            BrowserJob::GetAttribute {
+                selector,
+                attribute,
+                ..
+            } => client
+                .element_attribute(selector, attribute)
+                .await?
+                .ok_or_else(|| {
+                    BrowserError::OperationError(format!(
+                        "'{selector}' has no {attribute} attribute"
+                    ))
+                })?,
+            BrowserJob::WaitForDownload {
+                pattern,
+                timeout_seconds,
+                ..
+            } => {
+                let timeout = timeout_seconds
+                    .map(std::time::Duration::from_secs)
+                    .unwrap_or(client.options.timeout);
+                let path = client.wait_for_download(pattern, timeout).await?;
+                path.to_string_lossy().into_owned()
+            }
+            BrowserJob::ExecuteJs { script, args, .. } => {
+                if !client.options.allow_js_jobs {
+                    return Err(BrowserError::ConfigError(
+                        "ExecuteJs jobs are disabled; enable BrowserOptions::allow_js_jobs".into(),
+                    ));
+                }
+                return client.execute_js(script, args.clone()).await.map(Some);
+            }
+            BrowserJob::If { condition, .. } => {
+                return Ok(Some(json!({ "condition": condition.holds(client).await? })));
+            }
+            BrowserJob::RepeatUntil {
+                condition,
+                max_iters,
+                ..
+            } => {
+                let holds = condition.holds(client).await?;
+                if !holds && *max_iters == 0 {
+                    return Err(BrowserError::OperationError(format!(
+                        "RepeatUntil ran out of rounds before {condition:?} held"
+                    )));
+                }
+                return Ok(Some(json!({ "condition": holds })));
+            }
+            BrowserJob::ForEach { selector, .. } => {
+                return Ok(Some(json!({ "items": client.match_each(selector).await? })));
+            }
+            BrowserJob::Guarded {
+                job,
+                timeout_ms,
+                retries,
+                on_failure,
+            } => {
+                let mut attempt = 0;
+                let error = loop {
+                    let run = Box::pin(job.execute(client));
+                    let result = match timeout_ms {
+                        Some(ms) => tokio::time::timeout(Duration::from_millis(*ms), run)
+                            .await
+                            .unwrap_or_else(|_| {
+                                Err(BrowserError::OperationError(format!(
+                                    "{job:?} timed out after {ms} ms"
+                                )))
+                            }),
+                        None => run.await,
+                    };
+                    match result {
+                        Ok(output) => return Ok(output),
+                        Err(e) if attempt < *retries => {
+                            attempt += 1;
+                            eprintln!("Retrying {job:?} ({attempt}/{retries}) after error: {e}");
+                        }
+                        Err(e) => break e,
+                    }
+                };
+                match on_failure {
+                    OnFailure::Abort => return Err(error),
+                    OnFailure::Skip => eprintln!("Skipping {job:?} after error: {error}"),
+                    OnFailure::ScreenshotAndContinue => {
+                        let dir = std::path::Path::new("screenshots");
+                        std::fs::create_dir_all(dir).ok();
+                        // A lost window or a hung page can take the screenshot down too;
+                        // that must not end the run this job was meant to carry on.
+                        match client.capture_screenshot(dir, "failed").await {
+                            Ok(path) => eprintln!(
+                                "Continuing past {job:?} after error: {error}; page saved to {}",
+                                path.display()
+                            ),
+                            Err(e) => eprintln!(
+                                "Continuing past {job:?} after error: {error}; screenshot failed: {e}"
+                            ),
+                        }
+                    }
+                }
+                return Ok(None);
+            }
+            _ => return self.act(client).await.map(|()| None),
+        };
+        Ok(Some(Value::String(output)))
+    }
+
+    async fn act(&self, client: &mut BrowserClient) -> Result<(), BrowserError> {
         match self {
-            BrowserJob::Navigate(url) => client.navigate(url).await,
+            BrowserJob::Back => client.back().await,
+            BrowserJob::Forward => client.forward().await,
+            BrowserJob::Refresh => client.refresh().await,
+            BrowserJob::OpenTab { url: None } => client.open_tab().await,
+            BrowserJob::SwitchTab(index) => client.switch_tab(*index).await,
+            BrowserJob::CloseTab(index) => client.close_tab(*index).await,
             BrowserJob::Click(selector) => client.click_element(selector).await,
+            BrowserJob::Hover(selector) => client.hover(selector).await,
+            BrowserJob::ClickAt { x, y } => client.click_at(*x, *y).await,
             BrowserJob::Type { selector, text } => {
                 client.send_keys_to_element(selector, text).await
             }
+            BrowserJob::SelectOption {
+                selector,
+                value,
+                label,
+                index,
+            } => {
+                let choice = match (value, label, index) {
+                    (Some(value), _, _) => SelectChoice::Value(value.clone()),
+                    (_, Some(label), _) => SelectChoice::Label(label.clone()),
+                    (_, _, Some(index)) => SelectChoice::Index(*index),
+                    _ => {
+                        return Err(BrowserError::OperationError(format!(
+                            "SelectOption on '{selector}' needs a value, label or index"
+                        )));
+                    }
+                };
+                client.select_option(selector, &choice).await
+            }
+            BrowserJob::PressKey { key, selector } => {
+                client.press_key(selector.as_deref(), key).await
+            }
+            BrowserJob::UploadFile { selector, path } => client.upload_file(selector, path).await,
+            BrowserJob::FillForm { fields, submit } => {
+                for (selector, value) in fields {
+                    client.fill_field(selector, value).await?;
+                }
+                let Some(first) = fields.keys().next() else {
+                    return Ok(());
+                };
+                let missing = client.missing_required(first).await?;
+                if !missing.is_empty() {
+                    return Err(BrowserError::OperationError(format!(
+                        "Required fields left empty: {}",
+                        missing.join(", ")
+                    )));
+                }
+                if *submit {
+                    client.submit_form(first).await?;
+                }
+                Ok(())
+            }
+            BrowserJob::SubmitForm(selector) => client.submit_form(selector).await,
             BrowserJob::WaitFor(selector) => client.wait_for_element(selector).await.map(|_| ()),
             BrowserJob::ScrollTo(selector) => client.scroll_to(selector).await,
+            BrowserJob::Scroll(by) => client.scroll_by(*by).await,
+            BrowserJob::Sleep(ms) => {
+                client.sleep(*ms).await;
+                Ok(())
+            }
+            BrowserJob::AssertText(text) => {
+                if client.find_text(text).await?.iter().any(|m| m.visible) {
+                    Ok(())
+                } else {
+                    Err(BrowserError::AssertionFailed(format!(
+                        "text '{text}' is not visible on the page"
+                    )))
+                }
+            }
+            BrowserJob::AssertElementExists(selector) => {
+                if client.selector_exists(selector).await? {
+                    Ok(())
+                } else {
+                    Err(BrowserError::AssertionFailed(format!(
+                        "no element matches '{selector}'"
+                    )))
+                }
+            }
+            BrowserJob::AssertTextContains { selector, text } => {
+                let found = client.element_text(selector).await?;
+                if found.contains(text.as_str()) {
+                    Ok(())
+                } else {
+                    Err(BrowserError::AssertionFailed(format!(
+                        "'{selector}' reads '{found}', which does not contain '{text}'"
+                    )))
+                }
+            }
+            BrowserJob::AssertUrlMatches(pattern) => {
+                let re = regex::Regex::new(pattern).map_err(|e| {
+                    BrowserError::OperationError(format!("Invalid URL pattern '{pattern}': {e}"))
+                })?;
+                let url = client.current_url().await?;
+                if re.is_match(&url) {
+                    Ok(())
+                } else {
+                    Err(BrowserError::AssertionFailed(format!(
+                        "URL {url} does not match '{pattern}'"
+                    )))
+                }
+            }
+            BrowserJob::AssertTitleContains(part) => {
+                let title = client.get_title().await?;
+                if title.contains(part.as_str()) {
+                    Ok(())
+                } else {
+                    Err(BrowserError::AssertionFailed(format!(
+                        "title '{title}' does not contain '{part}'"
+                    )))
+                }
+            }
             BrowserJob::Screenshot { prefix } => {
                 let dir = std::path::Path::new("screenshots");
                 std::fs::create_dir_all(dir).ok();
                 client.capture_screenshot(dir, prefix).await.map(|_| ())
             }
+            BrowserJob::Answer { .. } => Err(BrowserError::ConfigError(
+                "Answers are written by the planning model; run the job through an Agent".into(),
+            )),
+            // Handled by `execute`, which never hands them here.
+            BrowserJob::Navigate(_)
+            | BrowserJob::OpenTab { .. }
+            | BrowserJob::ExtractText { .. }
+            | BrowserJob::GetAttribute { .. }
+            | BrowserJob::WaitForDownload { .. }
+            | BrowserJob::ExecuteJs { .. }
+            | BrowserJob::If { .. }
+            | BrowserJob::RepeatUntil { .. }
+            | BrowserJob::ForEach { .. }
+            | BrowserJob::Guarded { .. } => Ok(()),
+            // Custom tools live on the Agent, which dispatches them before reaching here.
+            BrowserJob::Tool { name, .. } => Err(BrowserError::ConfigError(format!(
+                "No handler for tool '{name}'; run the job through an Agent that registered it"
+            ))),
         }
     }
 }
 
+// Values a run of jobs has to work with: presets such as a search query or credentials, and
+// what ExtractText, GetAttribute and other reading jobs stored under their `store_as` names.
+// Later jobs use them as `{{name}}`, e.g. Navigate("{{next_page}}") after a GetAttribute of
+// the next link.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RunContext {
+    pub values: BTreeMap<String, Value>,
+}
+
+impl RunContext {
+    pub fn new(values: BTreeMap<String, Value>) -> Self {
+        Self { values }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        self.values.insert(name.to_string(), value.into());
+    }
+}
+
 pub async fn run_all_jobs(
     client: &mut BrowserClient,
     jobs: &[BrowserJob],
+    ctx: &mut RunContext,
 ) -> Result<(), BrowserError> {
     for (i, job) in jobs.iter().enumerate() {
-        if let Err(err) = job.run(client).await {
+        if let Err(err) = job.run_in(client, ctx).await {
             eprintln!("Job {} failed: {:?}", i, err);
             return Err(err);
         }
     }
     Ok(())
 }
+
+static PLACEHOLDER: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}").expect("valid placeholder pattern")
+});
+
+// "$item" standing alone in a ForEach body's selector, not the start of "$items".
+static ITEM: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"\$item\b").expect("valid item pattern"));
+
+// Fields holding jobs of their own, which run (and are filled) after the job itself.
+const NESTED_FIELDS: [&str; 3] = ["then", "else", "body"];
+
+// `text` with every `{{name}}` replaced by that variable's value; strings go in as they
+// are, anything else as JSON.
+pub fn fill_template(text: &str, vars: &BTreeMap<String, Value>) -> Result<String, BrowserError> {
+    let mut unknown = None;
+    let filled = PLACEHOLDER.replace_all(text, |caps: &regex::Captures| match vars.get(&caps[1]) {
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+        None => {
+            unknown.get_or_insert_with(|| caps[1].to_string());
+            String::new()
+        }
+    });
+    match unknown {
+        Some(name) => Err(BrowserError::OperationError(format!(
+            "Unknown variable '{{{{{name}}}}}'"
+        ))),
+        None => Ok(filled.into_owned()),
+    }
+}
+
+fn fill_value(value: &mut Value, vars: &BTreeMap<String, Value>) -> Result<(), BrowserError> {
+    match value {
+        Value::String(text) => *text = fill_template(text, vars)?,
+        Value::Array(items) => {
+            for item in items {
+                fill_value(item, vars)?;
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                fill_value(field, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn default_max_iters() -> usize {
+    10
+}
+
+fn example_timeout_ms() -> u64 {
+    5000
+}
+
+// `body` with "$item" in its selectors replaced by the selector of one ForEach element. Text,
+// URLs and scripts are left alone, and so is the body of a nested ForEach, whose "$item" is
+// its own element.
+fn for_item(body: &[BrowserJob], item: &str) -> Vec<BrowserJob> {
+    body.iter()
+        .map(|job| {
+            let mut job = job.clone();
+            fill_item(&mut job, item);
+            job
+        })
+        .collect()
+}
+
+fn fill_item(job: &mut BrowserJob, item: &str) {
+    let fill = |selector: &mut String| {
+        *selector = ITEM
+            .replace_all(selector, regex::NoExpand(item))
+            .into_owned();
+    };
+    match job {
+        BrowserJob::Click(selector)
+        | BrowserJob::SubmitForm(selector)
+        | BrowserJob::Hover(selector)
+        | BrowserJob::WaitFor(selector)
+        | BrowserJob::ScrollTo(selector)
+        | BrowserJob::AssertElementExists(selector)
+        | BrowserJob::Type { selector, .. }
+        | BrowserJob::SelectOption { selector, .. }
+        | BrowserJob::ExtractText { selector, .. }
+        | BrowserJob::GetAttribute { selector, .. }
+        | BrowserJob::AssertTextContains { selector, .. }
+        | BrowserJob::UploadFile { selector, .. }
+        | BrowserJob::ForEach { selector, .. }
+        | BrowserJob::PressKey {
+            selector: Some(selector),
+            ..
+        } => fill(selector),
+        BrowserJob::FillForm { fields, .. } => {
+            *fields = std::mem::take(fields)
+                .into_iter()
+                .map(|(mut selector, value)| {
+                    fill(&mut selector);
+                    (selector, value)
+                })
+                .collect();
+        }
+        BrowserJob::If {
+            condition,
+            then,
+            r#else,
+        } => {
+            fill_condition(condition, item);
+            then.iter_mut()
+                .chain(r#else.iter_mut())
+                .for_each(|job| fill_item(job, item));
+        }
+        BrowserJob::RepeatUntil {
+            condition, body, ..
+        } => {
+            fill_condition(condition, item);
+            body.iter_mut().for_each(|job| fill_item(job, item));
+        }
+        BrowserJob::Guarded { job, .. } => fill_item(job, item),
+        _ => {}
+    }
+}
+
+fn fill_condition(condition: &mut Condition, item: &str) {
+    if let Condition::ElementExists(selector) | Condition::TextContains { selector, .. } = condition
+    {
+        *selector = ITEM
+            .replace_all(selector, regex::NoExpand(item))
+            .into_owned();
+    }
+}
+
+fn takes_fields(def: &Value) -> bool {
+    def["type"] == "object" && def["properties"].is_object()
+}
+
+fn type_name(def: &Value) -> String {
+    // A documented reference comes wrapped as allOf: [{$ref}].
+    if let Some([inner]) = def["allOf"].as_array().map(Vec::as_slice) {
+        return type_name(inner);
+    }
+    // Nested jobs, which refer back to the BrowserJob definition.
+    if let Some(path) = def["$ref"].as_str() {
+        return path.rsplit('/').next().unwrap_or("value").to_string();
+    }
+    if let Some(variants) = def["oneOf"].as_array() {
+        return variants
+            .iter()
+            .flat_map(|variant| {
+                let units = variant["enum"].as_array().into_iter().flatten();
+                let units = units.filter_map(Value::as_str).map(str::to_string);
+                let Some((name, inner)) = variant["properties"]
+                    .as_object()
+                    .and_then(|props| props.iter().next())
+                else {
+                    return units.collect::<Vec<_>>();
+                };
+                vec![match inner["properties"].as_object() {
+                    Some(fields) => {
+                        let fields: Vec<String> = fields
+                            .iter()
+                            .map(|(field, def)| format!("{field}: {}", type_name(def)))
+                            .collect();
+                        format!("{name} {{ {} }}", fields.join(", "))
+                    }
+                    None => format!("{name}({})", type_name(inner)),
+                }]
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    match &def["type"] {
+        Value::String(t) if t == "string" && def["enum"].is_array() => def["enum"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join("|"),
+        Value::String(t) if t == "array" => format!("[{}]", type_name(&def["items"])),
+        // Maps, keyed by whatever the field's doc says.
+        Value::String(t) if t == "object" && def["additionalProperties"].is_object() => {
+            format!("{{string: {}}}", type_name(&def["additionalProperties"]))
+        }
+        Value::String(t) => t.clone(),
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|t| *t != "null")
+            .collect::<Vec<_>>()
+            .join("|"),
+        _ => def["enum"]
+            .as_array()
+            .map(|values| {
+                values
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<Vec<_>>()
+                    .join("|")
+            })
+            .unwrap_or_else(|| "value".to_string()),
+    }
+}
+
+fn example_value(def: &Value, name: &str) -> Value {
+    if let Some(example) = def["examples"].get(0) {
+        return example.clone();
+    }
+    if let Some(default) = def.get("default") {
+        return default.clone();
+    }
+    if def["$ref"].is_string() || def["allOf"][0]["$ref"].is_string() {
+        return json!({ "Click": "<selector>" });
+    }
+    if let Some((variant, inner)) = def["oneOf"][0]["properties"]
+        .as_object()
+        .and_then(|props| props.iter().next())
+    {
+        return json!({ variant: example_value(inner, "value") });
+    }
+    if let Some(values) = def["enum"].as_array() {
+        return values.first().cloned().unwrap_or(Value::Null);
+    }
+    let kind = match &def["type"] {
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null")
+            .unwrap_or("string")
+            .to_string(),
+        Value::String(t) => t.clone(),
+        _ => "string".to_string(),
+    };
+    match kind.as_str() {
+        "object" if def["additionalProperties"].is_object() => {
+            json!({ "<key>": example_value(&def["additionalProperties"], "value") })
+        }
+        "object" => Value::Object(
+            def["properties"]
+                .as_object()
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .map(|(field, def)| (field.clone(), example_value(def, field)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+        "array" => json!([example_value(&def["items"], name)]),
+        "integer" | "number" => json!(0),
+        "boolean" => json!(false),
+        _ => json!(format!("<{name}>")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("query".to_string(), json!("rust books")),
+            ("page".to_string(), json!(2)),
+        ])
+    }
+
+    #[test]
+    fn fill_template_inserts_strings_raw_and_other_values_as_json() {
+        assert_eq!(
+            fill_template("/search?q={{query}}&p={{ page }}", &vars()).unwrap(),
+            "/search?q=rust books&p=2"
+        );
+        assert_eq!(
+            fill_template("no placeholders", &vars()).unwrap(),
+            "no placeholders"
+        );
+    }
+
+    #[test]
+    fn fill_template_rejects_unknown_variables() {
+        match fill_template("{{query}} by {{author}}", &vars()) {
+            Err(BrowserError::OperationError(msg)) => {
+                assert_eq!(msg, "Unknown variable '{{author}}'")
+            }
+            other => panic!("expected an unknown variable error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn with_variables_fills_the_job_fields() {
+        let job = BrowserJob::Type {
+            selector: "#q".to_string(),
+            text: "{{query}}".to_string(),
+        };
+        assert_eq!(
+            job.with_variables(&vars()).unwrap(),
+            BrowserJob::Type {
+                selector: "#q".to_string(),
+                text: "rust books".to_string(),
+            }
+        );
+    }
+}
@@ -1,3 +1,291 @@
+// Shared helper prepended to extraction scripts: builds an nth-of-type path anchored at the
+// nearest ancestor with an id (or at <body>).
+pub const CSS_PATH: &str = r##"
+const cssPath = el => {
+    const parts = [];
+    const body = el.ownerDocument.body;
+    while (el && el.nodeType === 1 && el !== body) {
+        if (el.id) { parts.unshift("#" + CSS.escape(el.id)); break; }
+        const tag = el.tagName.toLowerCase();
+        const nth = Array.from(el.parentNode.children).filter(c => c.tagName === el.tagName).indexOf(el) + 1;
+        parts.unshift(`${tag}:nth-of-type(${nth})`);
+        el = el.parentElement;
+    }
+    if (el === body) parts.unshift("body");
+    return parts.join(" > ");
+};
+"##;
+
+// Shared helper: a selector that matches exactly `el`. Prefers a stable id, then test/data
+// attributes, name and aria-label, and falls back to an nth-child path anchored at the nearest
+// uniquely identifiable ancestor. Ids that look generated (long digit runs, React `:r1:`) are
+// skipped because they change between page loads.
+pub const UNIQUE_SELECTOR: &str = r##"
+const uniqueSelector = el => {
+    const doc = el.ownerDocument;
+    const unique = sel => { try { return doc.querySelectorAll(sel).length === 1; } catch (_) { return false; } };
+    const stableId = node => node.id && !/\d{4,}|:/.test(node.id) && unique("#" + CSS.escape(node.id));
+    const tag = el.tagName.toLowerCase();
+
+    if (stableId(el)) return "#" + CSS.escape(el.id);
+    for (const attr of ["data-testid", "data-test", "data-qa", "data-cy", "name", "aria-label"]) {
+        const value = el.getAttribute(attr);
+        if (!value) continue;
+        const sel = `${tag}[${attr}="${CSS.escape(value)}"]`;
+        if (unique(sel)) return sel;
+    }
+
+    const parts = [];
+    for (let node = el; node && node.nodeType === 1; node = node.parentElement) {
+        if (node === doc.body) { parts.unshift("body"); break; }
+        if (node !== el && stableId(node)) { parts.unshift("#" + CSS.escape(node.id)); break; }
+        const nth = Array.from(node.parentNode.children).indexOf(node) + 1;
+        parts.unshift(`${node.tagName.toLowerCase()}:nth-child(${nth})`);
+    }
+    return parts.join(" > ");
+};
+"##;
+
+// Shared helper: classifies the page region an element belongs to, from landmark roles,
+// id/class hints and link density of its enclosing block.
+pub const REGION: &str = r##"
+const regionOf = el => {
+    const hint = node => `${node.id} ${typeof node.className === "string" ? node.className : ""} ${node.getAttribute("aria-label") || ""}`;
+    for (let node = el; node && node.nodeType === 1 && node !== document.body; node = node.parentElement) {
+        const h = hint(node);
+        if (/cookie|consent|gdpr|onetrust|cmp-/i.test(h)) return "cookie_banner";
+        if (/(^|[\s_-])(ad|ads|advert|advertisement|sponsored|promo)([\s_-]|$)|adsbygoogle|ad-slot/i.test(h)) return "ad";
+        const role = node.getAttribute("role");
+        if (node.tagName === "FOOTER" || role === "contentinfo" || /(^|\s)footer/i.test(h)) return "footer";
+        if (node.tagName === "NAV" || role === "navigation" || role === "menubar" || node.tagName === "HEADER" || role === "banner") return "navigation";
+    }
+    const block = el.closest("ul, ol, div, section, aside, p, td") || el;
+    const total = block.innerText.replace(/\s+/g, "").length;
+    const linked = Array.from(block.querySelectorAll("a")).reduce((n, a) => n + a.innerText.replace(/\s+/g, "").length, 0);
+    if (total > 0 && block.querySelectorAll("a").length >= 3 && linked / total > 0.6) return "navigation";
+    return "content";
+};
+"##;
+
+// Shared helper: whether an element is rendered, inside the viewport, and - if so - whether
+// something else (cookie banner, modal) sits on top of its center point. Requires CSS_PATH.
+pub const VISIBILITY: &str = r##"
+const visibilityOf = el => {
+    const doc = el.ownerDocument;
+    const win = doc.defaultView;
+    const rect = el.getBoundingClientRect();
+    let visible = rect.width > 0 && rect.height > 0;
+    for (let node = el; visible && node && node.nodeType === 1; node = node.parentElement) {
+        const style = win.getComputedStyle(node);
+        if (style.display === "none" || style.visibility === "hidden" || parseFloat(style.opacity) === 0) visible = false;
+    }
+    const in_viewport = visible && rect.bottom > 0 && rect.right > 0 &&
+        rect.top < win.innerHeight && rect.left < win.innerWidth;
+
+    let occluded_by = null;
+    if (in_viewport) {
+        const x = Math.min(Math.max(rect.left + rect.width / 2, 0), win.innerWidth - 1);
+        const y = Math.min(Math.max(rect.top + rect.height / 2, 0), win.innerHeight - 1);
+        const top = doc.elementFromPoint(x, y);
+        if (top && top !== el && !el.contains(top) && !top.contains(el)) occluded_by = cssPath(top);
+    }
+    return { visible, in_viewport, occluded_by };
+};
+"##;
+
+// Draws Set-of-Marks boxes into a single fixed overlay so the page's own elements are untouched.
+// Framed selectors ("frame[0] >> css") are followed through same-origin frames, with each
+// frame's offset added so the box lands where the element shows on the top page.
+// arguments[0] is a list of [label, selector] pairs.
+pub const DRAW_MARKS: &str = r##"
+document.getElementById("iu-marks")?.remove();
+const locate = selector => {
+    let doc = document, dx = 0, dy = 0, m;
+    try {
+        while ((m = /^frame\[(\d+)\] >> /.exec(selector))) {
+            const host = doc.defaultView.frames[m[1]]?.frameElement;
+            if (!host) return null;
+            const r = host.getBoundingClientRect();
+            dx += r.left + host.clientLeft;
+            dy += r.top + host.clientTop;
+            doc = host.contentDocument;
+            selector = selector.slice(m[0].length);
+        }
+        const el = doc.querySelector(selector);
+        if (!el) return null;
+        const rect = el.getBoundingClientRect();
+        return { left: rect.left + dx, top: rect.top + dy, width: rect.width, height: rect.height };
+    } catch (_) {
+        return null;
+    }
+};
+const layer = document.createElement("div");
+layer.id = "iu-marks";
+layer.style.cssText = "position:fixed;inset:0;pointer-events:none;z-index:2147483647;";
+const colors = ["#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#008080"];
+for (const [label, selector] of arguments[0]) {
+    const rect = locate(selector);
+    if (!rect) continue;
+    const color = colors[label % colors.length];
+    const box = document.createElement("div");
+    box.style.cssText = `position:fixed;left:${rect.left}px;top:${rect.top}px;width:${rect.width}px;height:${rect.height}px;border:2px solid ${color};box-sizing:border-box;`;
+    const badge = document.createElement("span");
+    badge.textContent = label;
+    badge.style.cssText = `position:absolute;left:-2px;top:-18px;background:${color};color:#fff;font:bold 12px monospace;padding:1px 4px;`;
+    box.appendChild(badge);
+    layer.appendChild(box);
+}
+document.documentElement.appendChild(layer);
+"##;
+
+// Approval panel for a proposed plan: the checklist, the jobs as editable JSON and
+// Approve/Reject buttons. Meant for a blank tab of its own, never the page being acted on.
+// The decision lands in `window.__iuApproval` for the caller to poll; until then it holds
+// `{decision: null}`, so a missing value means the tab has navigated. Only real clicks count.
+// arguments[0] is the checklist, arguments[1] the jobs JSON.
+pub const APPROVAL_PANEL: &str = r##"
+document.getElementById("iu-approval")?.remove();
+window.__iuApproval = { decision: null };
+const panel = document.createElement("div");
+panel.id = "iu-approval";
+panel.style.cssText = "position:fixed;top:20px;right:20px;width:420px;max-height:80vh;overflow:auto;z-index:2147483647;background:#fff;border:3px solid #c00;border-radius:8px;padding:12px;font:13px sans-serif;color:#000;box-shadow:0 4px 16px rgba(0,0,0,.3);";
+const title = document.createElement("strong");
+title.textContent = "Approve this plan?";
+const todo = document.createElement("pre");
+todo.textContent = arguments[0];
+todo.style.cssText = "white-space:pre-wrap;margin:8px 0;";
+const jobs = document.createElement("textarea");
+jobs.value = arguments[1];
+jobs.style.cssText = "width:100%;height:180px;font:12px monospace;box-sizing:border-box;";
+const buttons = document.createElement("div");
+buttons.style.cssText = "display:flex;gap:8px;margin-top:8px;";
+const decide = (decision) => {
+    window.__iuApproval = { decision, jobs: jobs.value };
+    panel.remove();
+};
+for (const [label, decision] of [["Approve", "approve"], ["Reject", "reject"]]) {
+    const button = document.createElement("button");
+    button.textContent = label;
+    button.onclick = event => event.isTrusted && decide(decision);
+    buttons.appendChild(button);
+}
+panel.append(title, todo, jobs, buttons);
+document.documentElement.appendChild(panel);
+"##;
+
+// Installs the action recorder on the page if it is not there yet, then hands back (and
+// forgets) what it has seen. Events are kept in sessionStorage so the click that leads to a
+// same-origin page is not lost with the old one. Text is reported once the field is left or
+// Enter is pressed in it; passwords only as `secret`. Requires UNIQUE_SELECTOR.
+pub const RECORDER: &str = r##"
+if (!window.__iuRecorder) {
+    window.__iuRecorder = true;
+    const push = event => {
+        const events = JSON.parse(sessionStorage.getItem("__iuRecorded") || "[]");
+        events.push({ ...event, at: Date.now() });
+        sessionStorage.setItem("__iuRecorded", JSON.stringify(events));
+    };
+    const typed = new Map();
+    const isField = el => el.isContentEditable || el.tagName === "TEXTAREA" ||
+        (el.tagName === "INPUT" && !["checkbox", "radio", "submit", "button", "reset", "file", "image"].includes(el.type));
+    const flush = el => {
+        const text = el.isContentEditable ? el.innerText : el.value;
+        const selector = uniqueSelector(el);
+        if (typed.get(selector) === text) return;
+        typed.set(selector, text);
+        push({ kind: "type", selector, text, secret: el.type === "password" });
+    };
+    document.addEventListener("click", e => {
+        const el = e.target.closest("a, button, input, select, textarea, label, [onclick], [role=button], [role=link], [role=tab], [role=menuitem], [role=option], [role=checkbox]") || e.target;
+        if (el.closest("#iu-marks, #iu-approval") || isField(el) || el.tagName === "SELECT") return;
+        push({ kind: "click", selector: uniqueSelector(el) });
+    }, true);
+    document.addEventListener("change", e => {
+        const el = e.target;
+        if (el.tagName === "SELECT") push({ kind: "select", selector: uniqueSelector(el), value: el.value });
+        else if (isField(el)) flush(el);
+    }, true);
+    document.addEventListener("focusout", e => { if (isField(e.target)) flush(e.target); }, true);
+    document.addEventListener("keydown", e => {
+        if (!["Enter", "Escape", "Tab"].includes(e.key)) return;
+        if (isField(e.target)) flush(e.target);
+        if (e.key !== "Tab") push({ kind: "key", key: e.key, selector: uniqueSelector(e.target) });
+    }, true);
+}
+const events = JSON.parse(sessionStorage.getItem("__iuRecorded") || "[]");
+sessionStorage.removeItem("__iuRecorded");
+return { url: location.href, now: Date.now(), events };
+"##;
+
+// Serializes a static copy of the page for archival: scripts and our overlays stripped, form
+// state baked into attributes, same-origin stylesheets inlined. Images, cross-origin
+// stylesheets and CSS url()s (fonts, backgrounds) get absolute URLs and are listed in
+// `resources` for the caller to inline.
+pub const ARCHIVE_PAGE: &str = r##"
+const clone = document.documentElement.cloneNode(true);
+const resources = new Set();
+
+// url()s resolve against the sheet they come from, which the inlined copy no longer has.
+// Attributes get single quotes, which serialization leaves alone.
+const absolutize = (css, base, quote = '"') => css.replace(/url\(\s*(['"]?)(.*?)\1\s*\)/g, (match, _, url) => {
+    if (!url || /^(data:|#)/i.test(url)) return match;
+    try {
+        const absolute = new URL(url, base).href;
+        resources.add(absolute);
+        return `url(${quote}${absolute}${quote})`;
+    } catch (_) {
+        return match;
+    }
+});
+clone.querySelectorAll("style").forEach(style => style.textContent = absolutize(style.textContent, location.href));
+clone.querySelectorAll("[style]").forEach(el => el.setAttribute("style", absolutize(el.getAttribute("style"), location.href, "'")));
+
+const liveFields = document.querySelectorAll("input, textarea, select");
+clone.querySelectorAll("input, textarea, select").forEach((copy, i) => {
+    const el = liveFields[i];
+    if (!el) return;
+    if (el.tagName === "TEXTAREA") copy.textContent = el.value;
+    else if (el.tagName === "SELECT") Array.from(copy.options).forEach((o, j) => o.toggleAttribute("selected", el.options[j]?.selected));
+    else if (el.type === "checkbox" || el.type === "radio") copy.toggleAttribute("checked", el.checked);
+    else if (el.type !== "password") copy.setAttribute("value", el.value);
+});
+
+const liveImages = document.querySelectorAll("img");
+clone.querySelectorAll("img").forEach((copy, i) => {
+    const src = liveImages[i]?.currentSrc || liveImages[i]?.src;
+    copy.removeAttribute("srcset");
+    copy.removeAttribute("loading");
+    if (src && !src.startsWith("data:")) { copy.setAttribute("src", src); resources.add(src); }
+});
+clone.querySelectorAll("picture source").forEach(n => n.remove());
+
+const liveLinks = document.querySelectorAll("link[rel~=stylesheet]");
+clone.querySelectorAll("link[rel~=stylesheet]").forEach((copy, i) => {
+    const sheet = liveLinks[i]?.sheet;
+    if (!sheet) return;
+    try {
+        const style = document.createElement("style");
+        style.textContent = Array.from(sheet.cssRules).map(r => absolutize(r.cssText, sheet.href)).join("\n");
+        copy.replaceWith(style);
+    } catch (_) {
+        copy.setAttribute("href", sheet.href);
+        resources.add(sheet.href);
+    }
+});
+
+clone.querySelectorAll("script, noscript, #iu-marks, #iu-prompt-wrapper").forEach(n => n.remove());
+const head = clone.querySelector("head");
+if (head) {
+    const base = document.createElement("base");
+    base.href = location.href;
+    head.prepend(base);
+    const charset = document.createElement("meta");
+    charset.setAttribute("charset", "utf-8");
+    head.prepend(charset);
+}
+return { html: "<!DOCTYPE html>\n" + clone.outerHTML, resources: Array.from(resources) };
+"##;
+
 pub fn chat_prompt_red_ui() -> String {
     r#"
     (function() {
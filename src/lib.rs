@@ -1,12 +1,74 @@
 pub mod agent;
+pub mod approval;
+pub mod budget;
+pub mod checkpoint;
+pub mod chunking;
 pub mod client;
+pub mod config;
+pub mod content;
+pub mod conversation;
+pub mod coordinator;
+pub mod crawler;
+pub mod dataset;
+pub mod demo;
+pub mod diff;
+pub mod documents;
+pub mod eval;
+pub mod executor;
+pub mod extraction;
+pub mod extractors;
+pub mod injection;
 pub mod jobs;
 pub mod js;
+pub mod limits;
+#[cfg(feature = "llama-cpp")]
+pub mod llama_cpp;
+pub mod llm;
+pub mod metadata;
+pub mod policy;
+pub mod prompts;
+pub mod recorder;
+pub mod script;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod tools;
+pub mod trajectory;
 pub mod types;
+pub mod usage;
+pub mod validation;
 
-pub use agent::{Agent, AgentPlan};
+pub use agent::{
+    Agent, AgentPlan, AgentStep, Answer, JobResult, JobStatus, LowConfidence, PlanIssue,
+    PlanningMode, RunReport, Subtask, SubtaskReport, TaskReport, TaskStep, Verdict,
+};
+pub use approval::{Approval, Approver, CliApprover, OverlayApprover};
+pub use budget::ContextBudget;
+pub use checkpoint::Checkpoint;
 pub use client::BrowserClient;
-pub use jobs::BrowserJob;
+pub use config::AgentConfig;
+pub use conversation::{Conversation, Turn};
+pub use coordinator::{CoordinatedReport, Coordinator, Worker, WorkerResult};
+pub use crawler::{CrawlOptions, Crawler};
+pub use dataset::{PairFormat, TrainingPair};
+pub use diff::{DomDiff, DomSnapshot};
+pub use eval::{Assertion, EvalMetrics, EvalReport, EvalSuite, EvalTask};
+pub use executor::{GraphReport, JobGraph, JobNode, NodeReport, NodeStatus};
+pub use extractors::{Extractor, PageContext};
+pub use injection::InjectionGuard;
+pub use jobs::{BrowserJob, Condition, OnFailure, RunContext};
+pub use limits::{LimitExceeded, RunLimits};
+pub use llm::{LlmBackend, RetryPolicy, Sampling};
+pub use metadata::PageMetadata;
+pub use policy::Policy;
+pub use prompts::{ExampleLibrary, Exemplar, PromptTemplate};
+pub use recorder::Recorder;
+pub use script::JobScript;
+pub use tools::CustomTool;
+pub use trajectory::TrajectoryStep;
 pub use types::{
-    BrowserError, BrowserOptions, InteractiveElement, InteractiveElementType, TextElement,
+    BrowserError, BrowserOptions, ClientCertificate, Form, FormField, ImageElement,
+    InteractiveElement, InteractiveElementType, Link, MarkedScreenshot, PageExtraction, ScrollBy,
+    SelectChoice, SpatialElement, Table, TextElement,
 };
+pub use usage::{LlmCall, Pricing, Usage};
+pub use validation::{Diagnostics, JobIssue, Severity, validate_jobs};
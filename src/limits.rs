@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::crawler::host_of;
+use crate::jobs::BrowserJob;
+use crate::types::BrowserError;
+
+// Hard caps on one autonomous run, so an unattended agent always stops. `None` is unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunLimits {
+    pub max_jobs: Option<usize>,
+    pub max_duration: Option<Duration>,
+    pub max_domains: Option<usize>,
+}
+
+impl RunLimits {
+    pub fn max_jobs(mut self, jobs: usize) -> Self {
+        self.max_jobs = Some(jobs);
+        self
+    }
+
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    pub fn max_domains(mut self, domains: usize) -> Self {
+        self.max_domains = Some(domains);
+        self
+    }
+}
+
+// Which limit ended the run, and the value it was set to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitExceeded {
+    Jobs(usize),
+    Duration(Duration),
+    // The domain the agent tried to reach past the limit.
+    Domains { limit: usize, domain: String },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jobs(limit) => write!(f, "ran {limit} jobs"),
+            Self::Duration(limit) => write!(f, "ran for {}s", limit.as_secs()),
+            Self::Domains { limit, domain } => {
+                write!(f, "{domain} would be domain #{} of {limit}", limit + 1)
+            }
+        }
+    }
+}
+
+// What the current run has used so far, checked against `RunLimits` around every job.
+#[derive(Debug, Clone)]
+pub struct RunMeter {
+    started: Instant,
+    jobs: usize,
+    domains: BTreeSet<String>,
+}
+
+impl Default for RunMeter {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            jobs: 0,
+            domains: BTreeSet::new(),
+        }
+    }
+}
+
+impl RunMeter {
+    // Fails if the run is out of time or jobs, or if `job` navigates to one domain too many.
+    pub fn check(&self, limits: &RunLimits, job: Option<&BrowserJob>) -> Result<(), BrowserError> {
+        if let Some(limit) = limits.max_duration
+            && self.started.elapsed() >= limit
+        {
+            return Err(BrowserError::BudgetExceeded(LimitExceeded::Duration(limit)));
+        }
+        if job.is_none() {
+            return Ok(());
+        }
+        if let Some(limit) = limits.max_jobs
+            && self.jobs >= limit
+        {
+            return Err(BrowserError::BudgetExceeded(LimitExceeded::Jobs(limit)));
+        }
+        if let (Some(limit), Some(url)) = (limits.max_domains, job.and_then(BrowserJob::url))
+            && let Some(domain) = domain_of(url)
+            && !self.domains.contains(&domain)
+            && self.domains.len() >= limit
+        {
+            return Err(BrowserError::BudgetExceeded(LimitExceeded::Domains {
+                limit,
+                domain,
+            }));
+        }
+        Ok(())
+    }
+
+    // Counts a job that ran, and the domain the browser ended up on. `check` only sees where
+    // a job says it goes, so a click, Back or form submit that reached one domain too many
+    // fails here instead.
+    pub fn record(&mut self, limits: &RunLimits, url: Option<&str>) -> Result<(), BrowserError> {
+        self.jobs += 1;
+        let Some(domain) = url.and_then(domain_of) else {
+            return Ok(());
+        };
+        if self.domains.insert(domain.clone())
+            && let Some(limit) = limits.max_domains
+            && self.domains.len() > limit
+        {
+            return Err(BrowserError::BudgetExceeded(LimitExceeded::Domains {
+                limit,
+                domain,
+            }));
+        }
+        Ok(())
+    }
+
+    pub fn domains(&self) -> &BTreeSet<String> {
+        &self.domains
+    }
+}
+
+fn domain_of(url: &str) -> Option<String> {
+    host_of(url).map(|host| host.trim_start_matches("www.").to_string())
+}
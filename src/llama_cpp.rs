@@ -0,0 +1,228 @@
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{LlamaChatMessage, LlamaModel};
+use llama_cpp_2::sampling::LlamaSampler;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::mpsc;
+
+use crate::llm::{LlmBackend, Sampling, TokenStream};
+use crate::types::BrowserError;
+
+// llama.cpp may only be initialised once per process.
+static BACKEND: OnceLock<Result<LlamaBackend, String>> = OnceLock::new();
+
+// llama.cpp's "pick a random seed".
+const RANDOM_SEED: u32 = u32::MAX;
+
+// Runs a GGUF model in-process through llama.cpp, so planning needs no Ollama server. Each
+// backend serves the one file it loaded; the `model` argument of calls is ignored. Calls run
+// one at a time on a blocking thread. Clones share the loaded weights.
+#[derive(Debug, Clone)]
+pub struct LlamaCppBackend {
+    model: Arc<LlamaModel>,
+    path: String,
+    // Context window used when `Sampling::num_ctx` is unset.
+    pub n_ctx: u32,
+    pub max_tokens: u32,
+    busy: Arc<Mutex<()>>,
+}
+
+impl LlamaCppBackend {
+    // `gpu_layers` are offloaded when llama.cpp was built with GPU support; 0 keeps the model on
+    // the CPU.
+    pub fn load(path: impl AsRef<Path>, gpu_layers: u32) -> Result<Self, BrowserError> {
+        let path = path.as_ref();
+        let backend = backend()?;
+        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+        let model = LlamaModel::load_from_file(backend, path, &params)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+        Ok(Self {
+            n_ctx: model.n_ctx_train().min(8192),
+            model: Arc::new(model),
+            path: path.display().to_string(),
+            max_tokens: 2048,
+            busy: Arc::new(Mutex::new(())),
+        })
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    // Generates on a blocking thread, passing each decoded piece of text to `tokens` as it
+    // comes.
+    async fn run(
+        &self,
+        prompt: &str,
+        sampling: &Sampling,
+        tokens: Option<mpsc::UnboundedSender<String>>,
+    ) -> Result<String, BrowserError> {
+        let this = self.clone();
+        let prompt = prompt.to_string();
+        let sampling = sampling.clone();
+        tokio::task::spawn_blocking(move || this.complete(&prompt, &sampling, tokens))
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("llama.cpp worker failed: {e}")))?
+    }
+
+    fn complete(
+        &self,
+        prompt: &str,
+        sampling: &Sampling,
+        tokens: Option<mpsc::UnboundedSender<String>>,
+    ) -> Result<String, BrowserError> {
+        let err =
+            |e: &dyn std::fmt::Display| BrowserError::OperationError(format!("llama.cpp: {e}"));
+        let _busy = self.busy.lock().map_err(|e| err(&e))?;
+
+        let n_ctx = sampling
+            .num_ctx
+            .map(|n| n.min(u32::MAX as u64) as u32)
+            .unwrap_or(self.n_ctx);
+        let params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(n_ctx))
+            .with_n_batch(n_ctx);
+        let mut ctx = self
+            .model
+            .new_context(backend()?, params)
+            .map_err(|e| err(&e))?;
+
+        let vocab = self.model.vocab();
+        let input = vocab.tokenize(self.chat_prompt(prompt).as_bytes(), true, true);
+        if input.is_empty() || input.len() >= n_ctx as usize {
+            return Err(BrowserError::OperationError(format!(
+                "llama.cpp: prompt of {} tokens does not fit the {n_ctx}-token context",
+                input.len()
+            )));
+        }
+        let mut batch = LlamaBatch::new(n_ctx as usize, 1);
+        let last = input.len() - 1;
+        for (i, token) in input.iter().enumerate() {
+            batch
+                .add(*token, i as i32, &[0], i == last)
+                .map_err(|e| err(&e))?;
+        }
+        ctx.decode(&mut batch).map_err(|e| err(&e))?;
+
+        let mut sampler = sampler(sampling);
+        let mut pos = input.len() as i32;
+        let mut bytes = vec![];
+        let mut sent = 0;
+        let mut output = String::new();
+        while pos < n_ctx as i32 && pos - (input.len() as i32) < self.max_tokens as i32 {
+            let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            if vocab.is_eog(token) {
+                break;
+            }
+            bytes.extend(vocab.token_to_piece(token, false, None));
+            // Pieces can split a multi-byte character; only pass on complete text.
+            if let Ok(text) = std::str::from_utf8(&bytes) {
+                output = text.to_string();
+                if let Some(tokens) = &tokens {
+                    let _ = tokens.send(output[sent..].to_string());
+                }
+                sent = output.len();
+                if let Some(end) = sampling
+                    .stop
+                    .iter()
+                    .find_map(|stop| output.find(stop.as_str()))
+                {
+                    output.truncate(end);
+                    break;
+                }
+            }
+
+            batch.clear();
+            batch.add(token, pos, &[0], true).map_err(|e| err(&e))?;
+            pos += 1;
+            ctx.decode(&mut batch).map_err(|e| err(&e))?;
+        }
+        Ok(output)
+    }
+
+    // Wraps `prompt` in the model's own chat template, so instruction-tuned models answer it
+    // instead of continuing it. Models without a template get it as is.
+    fn chat_prompt(&self, prompt: &str) -> String {
+        let message = LlamaChatMessage::new("user".into(), prompt.into());
+        match (self.model.chat_template(None), message) {
+            (Ok(template), Ok(message)) => self
+                .model
+                .apply_chat_template(&template, &[message], true)
+                .unwrap_or_else(|_| prompt.to_string()),
+            _ => prompt.to_string(),
+        }
+    }
+}
+
+fn backend() -> Result<&'static LlamaBackend, BrowserError> {
+    BACKEND
+        .get_or_init(|| LlamaBackend::init().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| BrowserError::ConfigError(format!("llama.cpp init failed: {e}")))
+}
+
+fn sampler(sampling: &Sampling) -> LlamaSampler {
+    if sampling.temperature <= 0.0 {
+        return LlamaSampler::greedy();
+    }
+    let mut chain = vec![];
+    if let Some(top_k) = sampling.top_k {
+        chain.push(LlamaSampler::top_k(top_k as i32));
+    }
+    if let Some(top_p) = sampling.top_p {
+        chain.push(LlamaSampler::top_p(top_p, 1));
+    }
+    chain.push(LlamaSampler::temp(sampling.temperature));
+    chain.push(LlamaSampler::dist(
+        sampling.seed.map(|seed| seed as u32).unwrap_or(RANDOM_SEED),
+    ));
+    LlamaSampler::chain_simple(chain)
+}
+
+#[async_trait]
+impl LlmBackend for LlamaCppBackend {
+    fn endpoint(&self) -> &str {
+        &self.path
+    }
+
+    async fn generate(
+        &self,
+        _model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        self.run(prompt, sampling, None).await
+    }
+
+    async fn stream(
+        &self,
+        _model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<TokenStream, BrowserError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let this = self.clone();
+        let (prompt, sampling) = (prompt.to_string(), sampling.clone());
+        let done = tokio::spawn(async move { this.run(&prompt, &sampling, Some(tx)).await });
+        // Tokens until the channel closes, then the generation's error, if it failed.
+        Ok(
+            stream::unfold((rx, Some(done)), |(mut rx, done)| async move {
+                if let Some(token) = rx.recv().await {
+                    return Some((Ok(token), (rx, done)));
+                }
+                match done?.await {
+                    Ok(Err(e)) => Some((Err(e), (rx, None))),
+                    _ => None,
+                }
+            })
+            .boxed(),
+        )
+    }
+}
@@ -0,0 +1,985 @@
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use futures::stream::{self, BoxStream, StreamExt};
+use ollama_rs::{
+    Ollama,
+    generation::{completion::request::GenerationRequest, images::Image},
+    models::ModelOptions,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+use crate::types::BrowserError;
+
+pub type TokenStream = BoxStream<'static, Result<String, BrowserError>>;
+
+// Decoding settings sent with every model call. Unset options keep the provider's default;
+// providers ignore the ones they do not support (OpenAI has no top_k, Anthropic no seed, and
+// only Ollama takes num_ctx).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub seed: Option<i32>,
+    // Context window Ollama loads the model with.
+    pub num_ctx: Option<u64>,
+    pub stop: Vec<String>,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 0.4,
+            top_p: None,
+            top_k: None,
+            seed: None,
+            num_ctx: None,
+            stop: vec![],
+        }
+    }
+}
+
+impl Sampling {
+    // Temperature 0 and a fixed seed, for repeatable plans in tests.
+    pub fn deterministic(seed: i32) -> Self {
+        Self {
+            temperature: 0.0,
+            seed: Some(seed),
+            ..Self::default()
+        }
+    }
+
+    pub fn ollama_options(&self) -> ModelOptions {
+        let mut options = ModelOptions::default().temperature(self.temperature);
+        if let Some(top_p) = self.top_p {
+            options = options.top_p(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            options = options.top_k(top_k);
+        }
+        if let Some(seed) = self.seed {
+            options = options.seed(seed);
+        }
+        if let Some(num_ctx) = self.num_ctx {
+            options = options.num_ctx(num_ctx);
+        }
+        if !self.stop.is_empty() {
+            options = options.stop(self.stop.clone());
+        }
+        options
+    }
+
+    fn openai(&self) -> Value {
+        let mut fields = json!({ "temperature": self.temperature });
+        if let Some(top_p) = self.top_p {
+            fields["top_p"] = json!(top_p);
+        }
+        if let Some(seed) = self.seed {
+            fields["seed"] = json!(seed);
+        }
+        if !self.stop.is_empty() {
+            fields["stop"] = json!(self.stop);
+        }
+        fields
+    }
+
+    fn anthropic(&self) -> Value {
+        let mut fields = json!({ "temperature": self.temperature });
+        if let Some(top_p) = self.top_p {
+            fields["top_p"] = json!(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            fields["top_k"] = json!(top_k);
+        }
+        if !self.stop.is_empty() {
+            fields["stop_sequences"] = json!(self.stop);
+        }
+        fields
+    }
+}
+
+// How calls rejected with `BrowserError::LlmOverloaded` are retried: exponential backoff from
+// `initial_backoff`, capped at `max_backoff`, or the provider's Retry-After when it sends one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    // Delay before retry number `retry` (0-based).
+    pub fn backoff(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after
+            .unwrap_or_else(|| self.initial_backoff.saturating_mul(1 << retry.min(16)))
+            .min(self.max_backoff)
+    }
+}
+
+// Status codes providers use for rate limiting and temporary overload (529 is Anthropic's).
+const RETRYABLE_STATUS: &[u16] = &[429, 503, 529];
+
+trait SendChecked {
+    async fn send_checked(self) -> Result<reqwest::Response, BrowserError>;
+}
+
+impl SendChecked for reqwest::RequestBuilder {
+    // Like `send` + `error_for_status`, but rate limits and overload come back as
+    // `LlmOverloaded` so the agent can back off and retry them.
+    async fn send_checked(self) -> Result<reqwest::Response, BrowserError> {
+        let res = self
+            .send()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("LLM request failed: {e}")))?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        let message = res.text().await.unwrap_or_default();
+        if RETRYABLE_STATUS.contains(&status.as_u16()) {
+            return Err(BrowserError::LlmOverloaded {
+                status: status.as_u16(),
+                retry_after,
+                message,
+            });
+        }
+        Err(BrowserError::OperationError(format!(
+            "LLM request failed ({status}): {message}"
+        )))
+    }
+}
+
+// Adds the fields of the object `extra` to the object `body`.
+fn merge(mut body: Value, extra: Value) -> Value {
+    if let (Some(body), Value::Object(extra)) = (body.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+    body
+}
+
+// A function the model can call natively; `parameters` is the JSON schema of its arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+// A model provider the agent can plan with. Only `generate` is required; structured output and
+// streaming fall back to prompting for JSON and to a single chunk respectively.
+#[async_trait]
+pub trait LlmBackend: Send + Sync + std::fmt::Debug {
+    fn endpoint(&self) -> &str;
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError>;
+
+    // `images` are PNG bytes; only vision-capable models accept them.
+    async fn generate_with_images(
+        &self,
+        _model: &str,
+        _prompt: &str,
+        _images: &[Vec<u8>],
+        _sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        Err(BrowserError::ConfigError(format!(
+            "LLM backend at {} does not support image input",
+            self.endpoint()
+        )))
+    }
+
+    // Whether `generate_structured` is enforced by the provider rather than best-effort.
+    fn native_structured(&self) -> bool {
+        false
+    }
+
+    async fn generate_structured(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        schema: &Value,
+    ) -> Result<Value, BrowserError> {
+        let prompt = format!(
+            "{prompt}\n\nRespond with only a JSON value matching this JSON schema:\n{schema}"
+        );
+        let output = self.generate(model, &prompt, sampling).await?;
+        parse_json(&output)
+    }
+
+    // Whether the provider supports `generate_with_tools`.
+    fn native_tools(&self) -> bool {
+        false
+    }
+
+    // Native function calling: the text the model wrote plus the tool calls it made, in order.
+    async fn generate_with_tools(
+        &self,
+        _model: &str,
+        _prompt: &str,
+        _sampling: &Sampling,
+        _tools: &[ToolSpec],
+    ) -> Result<(String, Vec<ToolCall>), BrowserError> {
+        Err(BrowserError::ConfigError(format!(
+            "LLM backend at {} does not support tool calling",
+            self.endpoint()
+        )))
+    }
+
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<TokenStream, BrowserError> {
+        let output = self.generate(model, prompt, sampling).await?;
+        Ok(stream::once(async move { Ok(output) }).boxed())
+    }
+}
+
+// Tolerates prose or code fences around the JSON, which small local models often add.
+pub fn parse_json(output: &str) -> Result<Value, BrowserError> {
+    let start = output.find(['{', '[']);
+    let end = output.rfind(['}', ']']);
+    let candidate = match (start, end) {
+        (Some(start), Some(end)) if end > start => &output[start..=end],
+        _ => output,
+    };
+    serde_json::from_str(candidate)
+        .map_err(|e| BrowserError::OperationError(format!("Invalid JSON from LLM: {e}")))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OllamaBackend {
+    ollama: Ollama,
+    http: reqwest::Client,
+}
+
+impl OllamaBackend {
+    pub fn new(ollama: Ollama) -> Self {
+        Self {
+            ollama,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn request(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> GenerationRequest<'static> {
+        GenerationRequest::new(model.to_string(), prompt.to_string())
+            .options(sampling.ollama_options())
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OllamaBackend {
+    fn endpoint(&self) -> &str {
+        self.ollama.url_str()
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        self.ollama
+            .generate(self.request(model, prompt, sampling))
+            .await
+            .map(|res| res.response)
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    async fn generate_with_images(
+        &self,
+        model: &str,
+        prompt: &str,
+        images: &[Vec<u8>],
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        let req = self.request(model, prompt, sampling).images(
+            images
+                .iter()
+                .map(|png| Image::from_base64(BASE64.encode(png)))
+                .collect(),
+        );
+        self.ollama
+            .generate(req)
+            .await
+            .map(|res| res.response)
+            .map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    async fn generate_structured(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        schema: &Value,
+    ) -> Result<Value, BrowserError> {
+        // ollama-rs only builds `format` schemas from Rust types, so the request is posted
+        // directly. Schema-constrained decoding needs Ollama 0.5 or newer.
+        let res: Value = self
+            .http
+            .post(format!("{}api/generate", self.ollama.url_str()))
+            .json(&json!({
+                "model": model,
+                "prompt": prompt,
+                "stream": false,
+                "format": schema,
+                "options": sampling.ollama_options(),
+            }))
+            .send_checked()
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+        parse_json(res["response"].as_str().unwrap_or_default())
+    }
+
+    fn native_tools(&self) -> bool {
+        true
+    }
+
+    async fn generate_with_tools(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        tools: &[ToolSpec],
+    ) -> Result<(String, Vec<ToolCall>), BrowserError> {
+        let res: Value = self
+            .http
+            .post(format!("{}api/chat", self.ollama.url_str()))
+            .json(&json!({
+                "model": model,
+                "messages": [{ "role": "user", "content": prompt }],
+                "stream": false,
+                "tools": function_tools(tools),
+                "options": sampling.ollama_options(),
+            }))
+            .send_checked()
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+
+        let message = &res["message"];
+        let calls = message["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|call| ToolCall {
+                name: call["function"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                arguments: call["function"]["arguments"].clone(),
+            })
+            .collect();
+        Ok((
+            message["content"].as_str().unwrap_or_default().to_string(),
+            calls,
+        ))
+    }
+
+    fn native_structured(&self) -> bool {
+        true
+    }
+
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<TokenStream, BrowserError> {
+        let chunks = self
+            .ollama
+            .generate_stream(self.request(model, prompt, sampling))
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        Ok(chunks
+            .map(|chunk| {
+                chunk
+                    .map(|responses| responses.into_iter().map(|r| r.response).collect())
+                    .map_err(|e| BrowserError::OperationError(e.to_string()))
+            })
+            .boxed())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenAiClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiClient {
+    // `base_url` is the API root including the version, e.g. https://api.openai.com/v1.
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(str::to_string),
+        }
+    }
+
+    async fn post(
+        &self,
+        body: Value,
+        sampling: &Sampling,
+    ) -> Result<reqwest::Response, BrowserError> {
+        let mut req = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&merge(body, sampling.openai()));
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        req.send_checked().await
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiClient {
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        let res: Value = self
+            .post(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": prompt }],
+                }),
+                sampling,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+
+        res["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                BrowserError::OperationError(format!("LLM response without content: {res}"))
+            })
+    }
+
+    async fn generate_with_images(
+        &self,
+        model: &str,
+        prompt: &str,
+        images: &[Vec<u8>],
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        let mut content = vec![json!({ "type": "text", "text": prompt })];
+        content.extend(images.iter().map(|png| {
+            json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:image/png;base64,{}", BASE64.encode(png)) },
+            })
+        }));
+        let res: Value = self
+            .post(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": content }],
+                }),
+                sampling,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+
+        res["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                BrowserError::OperationError(format!("LLM response without content: {res}"))
+            })
+    }
+
+    fn native_tools(&self) -> bool {
+        true
+    }
+
+    async fn generate_with_tools(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        tools: &[ToolSpec],
+    ) -> Result<(String, Vec<ToolCall>), BrowserError> {
+        let res: Value = self
+            .post(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "tools": function_tools(tools),
+                }),
+                sampling,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+
+        let message = &res["choices"][0]["message"];
+        // Arguments arrive as a JSON-encoded string.
+        let calls = message["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|call| {
+                let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+                Ok(ToolCall {
+                    name: call["function"]["name"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    arguments: serde_json::from_str(arguments).map_err(|e| {
+                        BrowserError::OperationError(format!("Invalid tool arguments: {e}"))
+                    })?,
+                })
+            })
+            .collect::<Result<_, BrowserError>>()?;
+        Ok((
+            message["content"].as_str().unwrap_or_default().to_string(),
+            calls,
+        ))
+    }
+
+    fn native_structured(&self) -> bool {
+        true
+    }
+
+    async fn generate_structured(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        schema: &Value,
+    ) -> Result<Value, BrowserError> {
+        let res: Value = self
+            .post(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "response_format": {
+                        "type": "json_schema",
+                        "json_schema": { "name": "respond", "schema": schema },
+                    },
+                }),
+                sampling,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))?;
+        let content = res["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| {
+                BrowserError::OperationError(format!("LLM response without content: {res}"))
+            })?;
+        parse_json(content)
+    }
+
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<TokenStream, BrowserError> {
+        let res = self
+            .post(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "stream": true,
+                }),
+                sampling,
+            )
+            .await?;
+        Ok(sse_tokens(res, |event| {
+            event["choices"][0]["delta"]["content"]
+                .as_str()
+                .map(str::to_string)
+        }))
+    }
+}
+
+pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Debug, Clone)]
+pub struct AnthropicClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    pub max_tokens: u32,
+}
+
+impl AnthropicClient {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: ANTHROPIC_API_URL.to_string(),
+            api_key: api_key.to_string(),
+            max_tokens: 4096,
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Forces a single call of `tool` and returns its input, which the API has already
+    // validated against `input_schema`. Any text the model writes before the call is returned
+    // alongside it.
+    pub async fn call_tool(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        tool: &str,
+        description: &str,
+        input_schema: Value,
+    ) -> Result<(String, Value), BrowserError> {
+        let res = self
+            .send(
+                json!({
+                    "model": model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "tools": [{
+                        "name": tool,
+                        "description": description,
+                        "input_schema": input_schema,
+                    }],
+                    "tool_choice": { "type": "tool", "name": tool },
+                }),
+                sampling,
+            )
+            .await?;
+
+        let blocks = res["content"].as_array().cloned().unwrap_or_default();
+        let text = blocks
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("");
+        let input = blocks
+            .iter()
+            .find(|block| block["type"] == "tool_use" && block["name"] == tool)
+            .map(|block| block["input"].clone())
+            .ok_or_else(|| {
+                BrowserError::OperationError(format!("LLM did not call '{tool}': {res}"))
+            })?;
+        Ok((text, input))
+    }
+
+    async fn post(
+        &self,
+        body: Value,
+        sampling: &Sampling,
+    ) -> Result<reqwest::Response, BrowserError> {
+        self.http
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&merge(body, sampling.anthropic()))
+            .send_checked()
+            .await
+    }
+
+    async fn send(&self, body: Value, sampling: &Sampling) -> Result<Value, BrowserError> {
+        self.post(body, sampling)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BrowserError::OperationError(format!("Invalid LLM response: {e}")))
+    }
+}
+
+#[async_trait]
+impl LlmBackend for AnthropicClient {
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        let res = self
+            .send(
+                json!({
+                    "model": model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [{ "role": "user", "content": prompt }],
+                }),
+                sampling,
+            )
+            .await?;
+
+        text_blocks(&res)
+    }
+
+    async fn generate_with_images(
+        &self,
+        model: &str,
+        prompt: &str,
+        images: &[Vec<u8>],
+        sampling: &Sampling,
+    ) -> Result<String, BrowserError> {
+        let mut content: Vec<Value> = images
+            .iter()
+            .map(|png| {
+                json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": "image/png", "data": BASE64.encode(png) },
+                })
+            })
+            .collect();
+        content.push(json!({ "type": "text", "text": prompt }));
+        let res = self
+            .send(
+                json!({
+                    "model": model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [{ "role": "user", "content": content }],
+                }),
+                sampling,
+            )
+            .await?;
+        text_blocks(&res)
+    }
+
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+    ) -> Result<TokenStream, BrowserError> {
+        let res = self
+            .post(
+                json!({
+                    "model": model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "stream": true,
+                }),
+                sampling,
+            )
+            .await?;
+        Ok(sse_tokens(res, |event| {
+            (event["type"] == "content_block_delta")
+                .then(|| event["delta"]["text"].as_str().map(str::to_string))
+                .flatten()
+        }))
+    }
+
+    fn native_tools(&self) -> bool {
+        true
+    }
+
+    async fn generate_with_tools(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        tools: &[ToolSpec],
+    ) -> Result<(String, Vec<ToolCall>), BrowserError> {
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters,
+                })
+            })
+            .collect();
+        let res = self
+            .send(
+                json!({
+                    "model": model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "tools": tools,
+                }),
+                sampling,
+            )
+            .await?;
+
+        let calls = res["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "tool_use")
+            .map(|block| ToolCall {
+                name: block["name"].as_str().unwrap_or_default().to_string(),
+                arguments: block["input"].clone(),
+            })
+            .collect();
+        Ok((text_blocks(&res).unwrap_or_default(), calls))
+    }
+
+    fn native_structured(&self) -> bool {
+        true
+    }
+
+    async fn generate_structured(
+        &self,
+        model: &str,
+        prompt: &str,
+        sampling: &Sampling,
+        schema: &Value,
+    ) -> Result<Value, BrowserError> {
+        self.call_tool(
+            model,
+            prompt,
+            sampling,
+            "respond",
+            "Submit the response.",
+            schema.clone(),
+        )
+        .await
+        .map(|(_, input)| input)
+    }
+}
+
+// The `tools` array shared by the Ollama and OpenAI chat APIs.
+fn function_tools(tools: &[ToolSpec]) -> Vec<Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                },
+            })
+        })
+        .collect()
+}
+
+fn text_blocks(res: &Value) -> Result<String, BrowserError> {
+    let text: Vec<&str> = res["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+    if text.is_empty() {
+        return Err(BrowserError::OperationError(format!(
+            "LLM response without text: {res}"
+        )));
+    }
+    Ok(text.join(""))
+}
+
+// Server-sent events from a streaming completion: each `data:` line is a JSON event and `token`
+// picks the generated text out of it, if any. OpenAI-style streams end with `data: [DONE]`.
+// Bytes are buffered and only whole lines decoded, so a character split across network chunks
+// stays intact; a last line without a newline is still read when the stream ends.
+fn sse_tokens(res: reqwest::Response, token: fn(&Value) -> Option<String>) -> TokenStream {
+    stream::unfold(
+        (res.bytes_stream(), Vec::new(), false),
+        move |(mut bytes, mut buf, mut ended)| async move {
+            loop {
+                if let Some(end) = buf.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim().strip_prefix("data:") else {
+                        continue;
+                    };
+                    if data.trim() == "[DONE]" {
+                        return None;
+                    }
+                    if let Some(text) = serde_json::from_str(data.trim())
+                        .ok()
+                        .and_then(|e| token(&e))
+                    {
+                        return Some((Ok(text), (bytes, buf, ended)));
+                    }
+                    continue;
+                }
+                if ended {
+                    return None;
+                }
+                match bytes.next().await {
+                    Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                    Some(Err(e)) => {
+                        let error = BrowserError::OperationError(format!("LLM stream failed: {e}"));
+                        return Some((Err(error), (bytes, Vec::new(), ended)));
+                    }
+                    None => {
+                        ended = true;
+                        if !buf.is_empty() {
+                            buf.push(b'\n');
+                        }
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}
@@ -3,28 +3,57 @@ mod cli;
 use clap::Parser;
 use cli::{Cli, Commands};
 use internet_use::{
-    BrowserClient, BrowserError, BrowserOptions,
-    agent::Agent,
+    BrowserClient, BrowserError, BrowserOptions, CliApprover, Coordinator, EvalSuite,
+    ExampleLibrary, JobScript, OverlayApprover, PairFormat, Policy, PromptTemplate, Recorder,
+    RetryPolicy, RunLimits, Sampling, Turn, Worker,
+    agent::{Agent, AgentPlan, Answer, PlanningMode},
+    demo::DemoServer,
     js,
     types::{AgentMemory, MemoryOptions},
 };
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, mpsc};
 
 #[tokio::main]
 async fn main() -> Result<(), BrowserError> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Open { url } => {
-            let mut client =
-                BrowserClient::connect(BrowserOptions::default().headless(false)).await?;
+        Commands::Open {
+            url,
+            strict,
+            offline_llm_only,
+            allow_hosts,
+            safe,
+            block_domains,
+            approve,
+            model,
+            openai_url,
+            vision,
+            anthropic,
+            api_key,
+        } => {
+            let options = offline(
+                BrowserOptions::default().headless(false).strict(strict),
+                offline_llm_only,
+                &allow_hosts,
+            );
+
+            let mut agent = build_agent(&model, openai_url, anthropic, api_key)?
+                .with_policy(policy(safe, &block_domains));
+            if approve {
+                agent = agent.with_approver(OverlayApprover::default());
+            }
+            check_endpoints(&options, &agent)?;
+
+            let mut client = BrowserClient::connect(options).await?;
 
             client.navigate(&url).await?;
             client.inject_js(&js::chat_prompt_red_ui()).await?;
 
             println!("🌐 Browser opened at {url}. Enter prompts in the red box. Ctrl+C to exit.");
 
-            let mut agent = Agent::new("llama3", AgentMemory::new(MemoryOptions::default()));
-
             loop {
                 let prompt_value = client
                     .client
@@ -42,27 +71,76 @@ async fn main() -> Result<(), BrowserError> {
                     .await
                     .unwrap();
 
+                #[allow(clippy::collapsible_if)]
                 if let Some(prompt) = prompt_value.as_str() {
                     if !prompt.trim().is_empty() {
                         println!("🤖 Prompt received: {prompt}");
 
-                        let interactive = client
-                            .extract_interactive_elements()
-                            .await
-                            .unwrap_or_default();
-                        let texts = client.extract_text_elements().await.unwrap_or_default();
-
-                        if let Ok(plan) = agent.plan(prompt, &url, &interactive, &texts).await {
-                            let js_output = format!(
-                                r#"
-                                const output = document.getElementById("iu-output-textarea");
-                                if (output) {{
-                                    output.value = `{}`;
-                                }}
-                            "#,
-                                plan.markdown_todo.replace('`', "\\`") // escape backticks for JS
-                            );
-                            client.inject_js(&js_output).await?;
+                        // Follow-ups are planned with the earlier turns as context.
+                        let task = agent.conversation.prompt_for(prompt);
+                        let plan = if vision {
+                            agent.plan_with_vision(&task, &mut client, true).await
+                        } else {
+                            let current_url = client.current_url().await.unwrap_or(url.clone());
+                            let interactive = client
+                                .extract_interactive_elements()
+                                .await
+                                .unwrap_or_default();
+                            let texts = client.extract_text_elements().await.unwrap_or_default();
+
+                            // Show the checklist in the overlay while the model is still writing it.
+                            let (tx, mut rx) = mpsc::unbounded_channel();
+                            let planning =
+                                agent.plan_stream(&task, &current_url, &interactive, &texts, tx);
+                            tokio::pin!(planning);
+                            let mut streamed = String::new();
+                            loop {
+                                tokio::select! {
+                                    Some(token) = rx.recv() => {
+                                        streamed.push_str(&token);
+                                        show_output(&client, &streamed).await?;
+                                    }
+                                    plan = &mut planning => break plan,
+                                }
+                            }
+                        };
+
+                        match plan {
+                            Ok(plan) => {
+                                show_output(&client, &plan.markdown_todo).await?;
+                                let turn_url = client.current_url().await.unwrap_or(url.clone());
+                                let result = agent
+                                    .run_jobs_with_replanning(&task, &plan, &mut client)
+                                    .await;
+                                let answer = match &result {
+                                    Ok(report) => Answer::latest(&report.jobs),
+                                    Err(e) => {
+                                        println!("⚠️  {e}");
+                                        None
+                                    }
+                                };
+                                agent.conversation.push(Turn {
+                                    user: prompt.to_string(),
+                                    url: turn_url,
+                                    todo: plan.markdown_todo,
+                                    jobs: plan.jobs,
+                                    error: result.err().map(|e| e.to_string()),
+                                });
+                                // Jobs may have navigated away from the page holding the overlay.
+                                client.inject_js(&js::chat_prompt_red_ui()).await?;
+                                if let Some(answer) = answer {
+                                    let mut text = answer.answer;
+                                    if !answer.citations.is_empty() {
+                                        text.push_str(&format!(
+                                            "\n\nSources: {}",
+                                            answer.citations.join(", ")
+                                        ));
+                                    }
+                                    println!("💬 {text}");
+                                    show_output(&client, &text).await?;
+                                }
+                            }
+                            Err(e) => println!("⚠️  Planning failed: {e}"),
                         }
                     }
                 }
@@ -70,5 +148,607 @@ async fn main() -> Result<(), BrowserError> {
                 tokio::time::sleep(std::time::Duration::from_millis(500)).await;
             }
         }
+        Commands::Run {
+            url,
+            goal,
+            config,
+            checkpoint,
+            trajectory,
+            resume,
+            max_steps,
+            max_jobs,
+            max_seconds,
+            max_domains,
+            react,
+            replan_budget,
+            reflect,
+            diff_observations,
+            max_elements,
+            tools,
+            prompt_template,
+            examples,
+            context_window,
+            num_ctx,
+            top_p,
+            top_k,
+            seed,
+            stop,
+            decompose,
+            workers,
+            llm_concurrency,
+            llm_retries,
+            max_sleep_ms,
+            vars,
+            upload_dir,
+            allow_js_jobs,
+            safe,
+            block_domains,
+            approve,
+            detect_injection,
+            min_confidence,
+            low_confidence,
+            #[cfg(feature = "otel")]
+            otlp_endpoint,
+            model,
+            openai_url,
+            anthropic,
+            api_key,
+            offline_llm_only,
+            allow_hosts,
+            #[cfg(feature = "llama-cpp")]
+            gguf,
+            #[cfg(feature = "llama-cpp")]
+            gpu_layers,
+        } => {
+            #[cfg(feature = "otel")]
+            let _telemetry = otlp_endpoint
+                .map(|endpoint| internet_use::telemetry::Telemetry::init(&endpoint, "iu"))
+                .transpose()?;
+            let prompt = match prompt_template {
+                Some(path) => PromptTemplate::from_file(path)?,
+                None => PromptTemplate::default(),
+            };
+            let browser_options = upload_dir.into_iter().fold(
+                offline(
+                    BrowserOptions::default()
+                        .max_sleep(max_sleep_ms)
+                        .allow_js_jobs(allow_js_jobs),
+                    offline_llm_only,
+                    &allow_hosts,
+                ),
+                BrowserOptions::allow_upload_dir,
+            );
+            let vars = parse_vars(&vars)?;
+            let llm_permits = llm_concurrency.map(|n| Arc::new(Semaphore::new(n)));
+            // Loaded once; every worker's agent shares the weights.
+            #[cfg(feature = "llama-cpp")]
+            let gguf = gguf
+                .map(|path| internet_use::llama_cpp::LlamaCppBackend::load(path, gpu_layers))
+                .transpose()?;
+            let make_agent = || -> Result<Agent, BrowserError> {
+                let examples = match &examples {
+                    Some(path) => ExampleLibrary::from_file(path)?,
+                    None => ExampleLibrary::default(),
+                };
+                let agent = match &config {
+                    Some(path) => Agent::from_config(path)?,
+                    None => build_agent(&model, openai_url.clone(), anthropic, api_key.clone())?
+                        .with_prompt_template(prompt.clone())
+                        .with_examples(examples)
+                        .with_context_window(context_window)
+                        .with_max_steps(max_steps)
+                        .with_replan_budget(replan_budget)
+                        .with_policy(policy(safe, &block_domains))
+                        .with_confidence_gate(min_confidence, low_confidence.parse()?)
+                        .with_limits(RunLimits {
+                            max_jobs,
+                            max_duration: max_seconds.map(std::time::Duration::from_secs),
+                            max_domains,
+                        }),
+                };
+                let mut agent = agent
+                    .with_reflection(reflect)
+                    .with_tool_calling(tools)
+                    .with_mode(if react {
+                        PlanningMode::React
+                    } else {
+                        PlanningMode::FullPlan
+                    });
+                #[cfg(feature = "llama-cpp")]
+                if let Some(backend) = &gguf {
+                    agent = agent.with_backend(backend.clone());
+                }
+                if detect_injection {
+                    agent.injection.detect = true;
+                }
+                if diff_observations {
+                    agent.observation_diffs = true;
+                }
+                if let Some(max) = max_elements {
+                    agent = agent.with_max_elements(max);
+                }
+                for (name, value) in &vars {
+                    agent = agent.with_variable(name, value.as_str());
+                }
+                if let Some(max_retries) = llm_retries {
+                    agent = agent.with_retry(RetryPolicy {
+                        max_retries,
+                        ..RetryPolicy::default()
+                    });
+                }
+                if let Some(permits) = &llm_permits {
+                    agent = agent.with_llm_concurrency(permits.clone());
+                }
+                if let Some(tokens) = num_ctx {
+                    agent = agent.with_num_ctx(tokens);
+                }
+                if let Some(top_p) = top_p {
+                    agent = agent.with_top_p(top_p);
+                }
+                if let Some(top_k) = top_k {
+                    agent = agent.with_top_k(top_k);
+                }
+                if let Some(seed) = seed {
+                    agent = agent.with_seed(seed);
+                }
+                if !stop.is_empty() {
+                    agent = agent.with_stop(stop.clone());
+                }
+                let agent = match &checkpoint {
+                    Some(path) => agent.with_checkpoint(path),
+                    None => agent,
+                };
+                let agent = match &trajectory {
+                    Some(path) => agent.with_trajectory(path),
+                    None => agent,
+                };
+                Ok(if approve {
+                    agent.with_approver(CliApprover)
+                } else {
+                    agent
+                })
+            };
+
+            check_endpoints(&browser_options, &make_agent()?)?;
+
+            if let Some(path) = resume {
+                let mut agent = make_agent()?;
+                let mut client = BrowserClient::connect(browser_options.clone()).await?;
+                let report = agent
+                    .resume(&path, &mut client)
+                    .await
+                    .map(serde_json::to_value);
+                client.shutdown().await?;
+                let report = report?.map_err(|e| BrowserError::OperationError(e.to_string()))?;
+                println!("{report:#}");
+                return Ok(());
+            }
+            // clap requires both unless --resume is given.
+            let (Some(url), Some(goal)) = (url, goal) else {
+                return Err(BrowserError::ConfigError(
+                    "--url and --goal are required".into(),
+                ));
+            };
+
+            if workers > 1 {
+                let mut pool = vec![];
+                for i in 1..=workers {
+                    let mut client = BrowserClient::connect(browser_options.clone()).await?;
+                    client.navigate(&url).await?;
+                    let mut agent = make_agent()?;
+                    // Workers run different subtasks; one shared file would interleave them.
+                    if let Some(path) = &trajectory {
+                        agent = agent.with_trajectory(worker_path(path, i));
+                    }
+                    pool.push(Worker { agent, client });
+                }
+                let mut coordinator = Coordinator::new(pool);
+                let report = coordinator.run(&goal).await;
+                for worker in coordinator.workers {
+                    worker.client.shutdown().await?;
+                }
+                let report = serde_json::to_value(report?)
+                    .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+                println!("{report:#}");
+                return Ok(());
+            }
+
+            let mut agent = make_agent()?;
+            let mut client = BrowserClient::connect(browser_options.clone()).await?;
+            client.navigate(&url).await?;
+
+            let report = if decompose {
+                agent
+                    .run_decomposed(&goal, &mut client)
+                    .await
+                    .map(serde_json::to_value)
+            } else {
+                agent
+                    .run_task(&goal, &mut client)
+                    .await
+                    .map(serde_json::to_value)
+            };
+            client.shutdown().await?;
+            let report = report?.map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            println!("{report:#}");
+            Ok(())
+        }
+        Commands::Record {
+            url,
+            out,
+            offline_llm_only,
+            allow_hosts,
+        } => {
+            let options = offline(
+                BrowserOptions::default().headless(false),
+                offline_llm_only,
+                &allow_hosts,
+            );
+            let mut client = BrowserClient::connect(options).await?;
+            client.navigate(&url).await?;
+            println!("⏺️  Recording at {url}. Use the page as usual; Ctrl+C to save.");
+
+            let mut recorder = Recorder::new();
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => break,
+                    _ = tokio::time::sleep(std::time::Duration::from_millis(300)) => {}
+                }
+                // Fails while a new page is loading; its events wait for the next poll.
+                if let Ok(jobs) = recorder.poll(&client).await {
+                    for job in jobs {
+                        println!("   • {job:?}");
+                    }
+                }
+            }
+            recorder.poll(&client).await.ok();
+            client.shutdown().await?;
+
+            let name = out
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let script = recorder.into_script(&name);
+            script.save(&out)?;
+            println!(
+                "💾 Saved {} jobs to {}; replay with iu replay --file {}",
+                script.jobs.len(),
+                out.display(),
+                out.display()
+            );
+            Ok(())
+        }
+        Commands::Replay {
+            file,
+            vars,
+            max_sleep_ms,
+            upload_dir,
+            offline_llm_only,
+            allow_hosts,
+            allow_js_jobs,
+            dry_run,
+        } => {
+            let script = JobScript::from_file(&file)?;
+            let mut ctx = script.context(
+                parse_vars(&vars)?
+                    .into_iter()
+                    .map(|(name, value)| (name, value.into())),
+            );
+            let browser_options = upload_dir.into_iter().fold(
+                offline(
+                    BrowserOptions::default()
+                        .max_sleep(max_sleep_ms)
+                        .allow_js_jobs(allow_js_jobs),
+                    offline_llm_only,
+                    &allow_hosts,
+                ),
+                BrowserOptions::allow_upload_dir,
+            );
+            // A dry run reports the same problems (and more) instead of stopping on them.
+            let diagnostics = script.validate(&ctx);
+            if !dry_run && diagnostics.has_errors() {
+                let issues = serde_json::to_string_pretty(&diagnostics.issues).unwrap_or_default();
+                return Err(BrowserError::ConfigError(format!(
+                    "{} has errors, so nothing was run:\n{issues}",
+                    file.display()
+                )));
+            }
+            let presets = ctx.clone();
+            let mut client = BrowserClient::connect(browser_options).await?;
+            if dry_run {
+                let diagnostics = script.dry_run(&mut client, &ctx).await;
+                client.shutdown().await?;
+                let diagnostics = serde_json::to_value(diagnostics?)
+                    .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+                println!("{diagnostics:#}");
+                return Ok(());
+            }
+            let result = script.run(&mut client, &mut ctx).await;
+            client.shutdown().await?;
+            result?;
+            // Only what the jobs stored, so credentials passed in are not echoed.
+            ctx.values
+                .retain(|name, value| presets.get(name) != Some(&*value));
+            let values = serde_json::to_value(&ctx)
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            println!("{values:#}");
+            Ok(())
+        }
+        Commands::Eval {
+            suite,
+            offline_llm_only,
+            allow_hosts,
+            max_steps,
+            react,
+            tools,
+            seed,
+            prompt_template,
+            examples,
+            model,
+            openai_url,
+            anthropic,
+            api_key,
+        } => {
+            let suite = EvalSuite::from_file(suite)?;
+            let prompt = match prompt_template {
+                Some(path) => PromptTemplate::from_file(path)?,
+                None => PromptTemplate::default(),
+            };
+            let examples = match examples {
+                Some(path) => ExampleLibrary::from_file(path)?,
+                None => ExampleLibrary::default(),
+            };
+            let make_agent = || -> Result<Agent, BrowserError> {
+                let agent = build_agent(&model, openai_url.clone(), anthropic, api_key.clone())?
+                    .with_prompt_template(prompt.clone())
+                    .with_examples(examples.clone())
+                    .with_max_steps(max_steps)
+                    .with_tool_calling(tools)
+                    .with_mode(if react {
+                        PlanningMode::React
+                    } else {
+                        PlanningMode::FullPlan
+                    });
+                Ok(match seed {
+                    Some(seed) => agent.with_sampling(Sampling::deterministic(seed)),
+                    None => agent,
+                })
+            };
+
+            let options = offline(BrowserOptions::default(), offline_llm_only, &allow_hosts);
+            check_endpoints(&options, &make_agent()?)?;
+            let mut client = BrowserClient::connect(options).await?;
+            let report = suite.run(&mut client, make_agent).await;
+            client.shutdown().await?;
+            let report = serde_json::to_value(report?)
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+            println!("{report:#}");
+            Ok(())
+        }
+        Commands::Dataset {
+            trajectory,
+            out,
+            approved_runs,
+            chat,
+        } => {
+            let steps = internet_use::trajectory::read(&trajectory)?;
+            let pairs = internet_use::dataset::training_pairs(&steps, &approved_runs);
+            let format = if chat {
+                PairFormat::Chat
+            } else {
+                PairFormat::PromptCompletion
+            };
+            internet_use::dataset::write_jsonl(&pairs, &out, format)?;
+            println!(
+                "📚 Wrote {} training pairs to {}",
+                pairs.len(),
+                out.display()
+            );
+            Ok(())
+        }
+        Commands::Extract {
+            url,
+            all_regions,
+            offline_llm_only,
+            allow_hosts,
+        } => {
+            let options = offline(BrowserOptions::default(), offline_llm_only, &allow_hosts);
+            let mut client = BrowserClient::connect(options).await?;
+            client.navigate(&url).await?;
+
+            let texts: Vec<_> = client
+                .extract_text_elements()
+                .await?
+                .into_iter()
+                .filter(|el| all_regions || !el.is_boilerplate())
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&texts)
+                    .map_err(|e| BrowserError::OperationError(e.to_string()))?
+            );
+            client.shutdown().await
+        }
+        Commands::Demo {
+            port,
+            offline_llm_only,
+            allow_hosts,
+            model,
+            task,
+            openai_url,
+            vision,
+            anthropic,
+            api_key,
+        } => {
+            let server = DemoServer::start(port).await?;
+            let url = server.url();
+            println!("🛍️  Demo shop running at {url}");
+
+            let options = offline(
+                BrowserOptions::default().headless(false),
+                offline_llm_only,
+                &allow_hosts,
+            );
+            let mut agent = build_agent(&model, openai_url, anthropic, api_key)?;
+            check_endpoints(&options, &agent)?;
+
+            let mut client = BrowserClient::connect(options).await?;
+            client.navigate(&url).await?;
+            println!("📝 Task: {task}");
+
+            let plan = if vision {
+                let plan = agent.plan_with_vision(&task, &mut client, true).await?;
+                println!("📋 Plan:\n{}", plan.markdown_todo);
+                plan
+            } else {
+                stream_plan(&agent, &task, &url, &mut client).await?
+            };
+
+            for job in &plan.jobs {
+                println!("   • {job:?}");
+            }
+
+            match agent
+                .run_jobs_with_replanning(&task, &plan, &mut client)
+                .await
+            {
+                Ok(report) => println!(
+                    "✅ Sample task finished: {} jobs in {} ms.",
+                    report.jobs.len(),
+                    report.duration_ms()
+                ),
+                Err(e) => println!("⚠️  Sample task stopped: {e}"),
+            }
+
+            println!("🌐 Browser left open on the demo shop. Ctrl+C to exit.");
+            tokio::signal::ctrl_c().await.ok();
+            server.shutdown();
+            client.shutdown().await
+        }
     }
 }
+
+// `run.jsonl` as `run.worker2.jsonl` for worker 2.
+fn worker_path(path: &std::path::Path, worker: usize) -> std::path::PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.worker{worker}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.worker{worker}"),
+    };
+    path.with_file_name(name)
+}
+
+// Offline mode per --offline-llm-only and --allow-host.
+fn offline(options: BrowserOptions, enabled: bool, allow_hosts: &[String]) -> BrowserOptions {
+    allow_hosts
+        .iter()
+        .fold(options.offline(enabled), |options, host| {
+            options.allow_host(host)
+        })
+}
+
+// Offline mode must leave every model the agent may call reachable, fallbacks included.
+// In-process backends (a GGUF file path) need no network.
+fn check_endpoints(options: &BrowserOptions, agent: &Agent) -> Result<(), BrowserError> {
+    match agent
+        .endpoints()
+        .into_iter()
+        .find(|endpoint| url::Url::parse(endpoint).is_ok() && !options.allows_url(endpoint))
+    {
+        Some(endpoint) => Err(BrowserError::NetworkBlocked(format!(
+            "LLM endpoint {endpoint} is not local or allowlisted"
+        ))),
+        None => Ok(()),
+    }
+}
+
+async fn stream_plan(
+    agent: &Agent,
+    task: &str,
+    url: &str,
+    client: &mut BrowserClient,
+) -> Result<AgentPlan, BrowserError> {
+    let interactive = client.extract_interactive_elements().await?;
+    let texts = client.extract_text_elements().await?;
+
+    println!("📋 Plan:");
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let printer = tokio::spawn(async move {
+        while let Some(token) = rx.recv().await {
+            print!("{token}");
+            std::io::stdout().flush().ok();
+        }
+        println!();
+    });
+    let plan = agent.plan_stream(task, url, &interactive, &texts, tx).await;
+    printer.await.ok();
+    plan
+}
+
+// NAME=VALUE pairs from --var flags.
+fn parse_vars(vars: &[String]) -> Result<Vec<(String, String)>, BrowserError> {
+    vars.iter()
+        .map(|var| {
+            var.split_once('=')
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .ok_or_else(|| {
+                    BrowserError::ConfigError(format!("--var '{var}' is not NAME=VALUE"))
+                })
+        })
+        .collect()
+}
+
+fn policy(safe: bool, block_domains: &[String]) -> Policy {
+    let base = if safe {
+        Policy::safe()
+    } else {
+        Policy::default()
+    };
+    block_domains
+        .iter()
+        .fold(base, |policy, domain| policy.block_domain(domain))
+}
+
+async fn show_output(client: &BrowserClient, text: &str) -> Result<(), BrowserError> {
+    client
+        .client
+        .execute(
+            r#"
+            const output = document.getElementById("iu-output-textarea");
+            if (output) output.value = arguments[0];
+            "#,
+            vec![serde_json::json!(text)],
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| BrowserError::OperationError(e.to_string()))
+}
+
+fn build_agent(
+    model: &str,
+    openai_url: Option<String>,
+    anthropic: bool,
+    api_key: Option<String>,
+) -> Result<Agent, BrowserError> {
+    let agent = Agent::new(model, AgentMemory::new(MemoryOptions::default()));
+    if anthropic {
+        let key = api_key
+            .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+            .ok_or_else(|| {
+                BrowserError::ConfigError(
+                    "--anthropic needs --api-key or $ANTHROPIC_API_KEY".into(),
+                )
+            })?;
+        return Ok(agent.with_anthropic(&key));
+    }
+    Ok(match openai_url {
+        Some(url) => {
+            let key = api_key.or_else(|| std::env::var("OPENAI_API_KEY").ok());
+            agent.with_openai(&url, key.as_deref())
+        }
+        None => agent,
+    })
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PageMetadata {
+    pub title: String,
+    pub description: Option<String>,
+    pub canonical: Option<String>,
+    pub language: Option<String>,
+    pub open_graph: BTreeMap<String, String>,
+    pub twitter: BTreeMap<String, String>,
+    pub meta: BTreeMap<String, String>,
+    pub json_ld: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProductInfo {
+    pub name: Option<String>,
+    pub brand: Option<String>,
+    pub price: Option<String>,
+    pub currency: Option<String>,
+    pub availability: Option<String>,
+    pub rating: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ArticleInfo {
+    pub headline: Option<String>,
+    pub authors: Vec<String>,
+    pub date_published: Option<String>,
+    pub date_modified: Option<String>,
+    pub publisher: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EventInfo {
+    pub name: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub location: Option<String>,
+}
+
+impl PageMetadata {
+    // Every JSON-LD node, with `@graph` containers and top-level arrays flattened.
+    pub fn json_ld_nodes(&self) -> Vec<&Value> {
+        let mut nodes = vec![];
+        let mut stack: Vec<&Value> = self.json_ld.iter().collect();
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Array(items) => stack.extend(items),
+                Value::Object(map) => {
+                    if let Some(graph) = map.get("@graph") {
+                        stack.push(graph);
+                    }
+                    if map.contains_key("@type") {
+                        nodes.push(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+        nodes
+    }
+
+    pub fn find_type(&self, types: &[&str]) -> Option<&Value> {
+        self.json_ld_nodes().into_iter().find(|node| {
+            let matches = |t: &str| types.iter().any(|want| t.eq_ignore_ascii_case(want));
+            match &node["@type"] {
+                Value::String(t) => matches(t),
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).any(matches),
+                _ => false,
+            }
+        })
+    }
+
+    pub fn product(&self) -> Option<ProductInfo> {
+        if let Some(node) = self.find_type(&["Product"]) {
+            let offer = match &node["offers"] {
+                Value::Array(offers) => offers.first().cloned().unwrap_or(Value::Null),
+                offer => offer.clone(),
+            };
+            return Some(ProductInfo {
+                name: text(&node["name"]),
+                brand: name_of(&node["brand"]),
+                price: text(&offer["price"]).or_else(|| text(&offer["lowPrice"])),
+                currency: text(&offer["priceCurrency"]),
+                availability: text(&offer["availability"])
+                    .map(|a| a.rsplit('/').next().unwrap_or(&a).to_string()),
+                rating: text(&node["aggregateRating"]["ratingValue"]),
+            });
+        }
+
+        let og = |key: &str| self.open_graph.get(key).cloned();
+        let price = og("product:price:amount").or_else(|| og("og:price:amount"));
+        if og("og:type").as_deref() == Some("product") || price.is_some() {
+            return Some(ProductInfo {
+                name: og("og:title"),
+                brand: og("product:brand"),
+                price,
+                currency: og("product:price:currency").or_else(|| og("og:price:currency")),
+                availability: og("product:availability"),
+                rating: None,
+            });
+        }
+        None
+    }
+
+    pub fn article(&self) -> Option<ArticleInfo> {
+        if let Some(node) = self.find_type(&["Article", "NewsArticle", "BlogPosting", "Report"]) {
+            let authors = match &node["author"] {
+                Value::Array(authors) => authors.iter().filter_map(name_of).collect(),
+                author => name_of(author).into_iter().collect(),
+            };
+            return Some(ArticleInfo {
+                headline: text(&node["headline"]).or_else(|| text(&node["name"])),
+                authors,
+                date_published: text(&node["datePublished"]),
+                date_modified: text(&node["dateModified"]),
+                publisher: name_of(&node["publisher"]),
+            });
+        }
+
+        let og = |key: &str| self.open_graph.get(key).cloned();
+        if og("og:type").as_deref() == Some("article") {
+            return Some(ArticleInfo {
+                headline: og("og:title"),
+                authors: og("article:author")
+                    .or_else(|| self.meta.get("author").cloned())
+                    .into_iter()
+                    .collect(),
+                date_published: og("article:published_time"),
+                date_modified: og("article:modified_time"),
+                publisher: og("og:site_name"),
+            });
+        }
+        None
+    }
+
+    pub fn event(&self) -> Option<EventInfo> {
+        let node = self.find_type(&["Event", "MusicEvent", "SportsEvent", "BusinessEvent"])?;
+        Some(EventInfo {
+            name: text(&node["name"]),
+            start_date: text(&node["startDate"]),
+            end_date: text(&node["endDate"]),
+            location: name_of(&node["location"]),
+        })
+    }
+}
+
+pub(crate) fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn name_of(value: &Value) -> Option<String> {
+    text(value).or_else(|| text(&value["name"]))
+}
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crawler::host_of;
+use crate::jobs::BrowserJob;
+use crate::types::{BrowserError, InteractiveElement};
+
+const PURCHASE_PHRASES: &[&str] = &[
+    "buy now",
+    "place order",
+    "place your order",
+    "complete purchase",
+    "confirm purchase",
+    "confirm order",
+    "submit order",
+    "pay now",
+    "proceed to checkout",
+    "checkout",
+];
+
+const DELETION_PHRASES: &[&str] = &[
+    "delete account",
+    "delete my account",
+    "close account",
+    "close my account",
+    "deactivate account",
+    "remove account",
+];
+
+const PAYMENT_FIELDS: &[&str] = &[
+    "cc-number",
+    "cc-csc",
+    "cc-exp",
+    "cardnumber",
+    "card-number",
+    "card_number",
+    "credit card",
+    "cvv",
+    "cvc",
+    "security code",
+    "iban",
+];
+
+// What the agent may not do, checked before every job runs (and, for blocked hosts, against
+// the page it lands on). The built-in guards match the target element's text, label and
+// attributes; the lists match selectors, hosts and free text case-insensitively. A blocked job fails with `BrowserError::PolicyViolation`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    pub block_purchases: bool,
+    pub block_payment_forms: bool,
+    pub block_account_deletion: bool,
+    // Hosts that may not be navigated to or acted on, subdomains included.
+    pub blocked_domains: Vec<String>,
+    // Substrings of selectors that may not be clicked or typed into.
+    pub blocked_selectors: Vec<String>,
+    // Phrases that may not appear on a clicked element.
+    pub blocked_keywords: Vec<String>,
+}
+
+impl Policy {
+    // All built-in guards on.
+    pub fn safe() -> Self {
+        Self {
+            block_purchases: true,
+            block_payment_forms: true,
+            block_account_deletion: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn block_domain(mut self, domain: &str) -> Self {
+        self.blocked_domains.push(domain.to_lowercase());
+        self
+    }
+
+    pub fn block_selector(mut self, selector: &str) -> Self {
+        self.blocked_selectors.push(selector.to_lowercase());
+        self
+    }
+
+    pub fn block_keyword(mut self, keyword: &str) -> Self {
+        self.blocked_keywords.push(keyword.to_lowercase());
+        self
+    }
+
+    // `target` is the element the job acts on, when it is in the last snapshot.
+    pub fn check(
+        &self,
+        job: &BrowserJob,
+        current_url: &str,
+        target: Option<&InteractiveElement>,
+    ) -> Result<(), BrowserError> {
+        if let BrowserJob::Guarded { job, .. } = job {
+            return self.check(job, current_url, target);
+        }
+        let violation = |rule: &str| {
+            Err(BrowserError::PolicyViolation {
+                rule: rule.to_string(),
+                job: serde_json::to_string(job).unwrap_or_default(),
+            })
+        };
+
+        let url = job.url().unwrap_or(current_url);
+        if let Some(domain) = host_of(url).and_then(|host| self.blocked_domain(&host)) {
+            return violation(&format!("blocked domain {domain}"));
+        }
+
+        let Some(selector) = job.selector() else {
+            return Ok(());
+        };
+        let selector = selector.to_lowercase();
+        // A label like "@12" says nothing by itself; the element it stands for does.
+        let resolved = target.map(|el| el.selector.to_lowercase());
+        if let Some(blocked) = self.blocked_selectors.iter().find(|blocked| {
+            selector.contains(blocked.as_str())
+                || resolved
+                    .as_ref()
+                    .is_some_and(|s| s.contains(blocked.as_str()))
+        }) {
+            return violation(&format!("blocked selector {blocked}"));
+        }
+
+        // Where activating the target would go, for jobs that can follow a link or submit.
+        let leads_to = match job {
+            BrowserJob::Click(_)
+            | BrowserJob::SubmitForm(_)
+            | BrowserJob::PressKey { .. }
+            | BrowserJob::FillForm { submit: true, .. } => target.and_then(|el| {
+                ["href", "formaction", "action"]
+                    .iter()
+                    .find_map(|name| el.attributes.get(*name))
+            }),
+            _ => None,
+        };
+        if let Some(link) = leads_to
+            && let Some(domain) = url::Url::parse(current_url)
+                .and_then(|base| base.join(link))
+                .ok()
+                .and_then(|url| host_of(url.as_str()))
+                .and_then(|host| self.blocked_domain(&host))
+        {
+            return violation(&format!("blocked domain {domain}"));
+        }
+
+        let description = describe(&selector, target);
+        match job {
+            BrowserJob::Click(_) | BrowserJob::SubmitForm(_) => {
+                if self.block_purchases && contains_any(&description, PURCHASE_PHRASES) {
+                    return violation("purchase");
+                }
+                if self.block_account_deletion && contains_any(&description, DELETION_PHRASES) {
+                    return violation("account deletion");
+                }
+                if let Some(keyword) = self
+                    .blocked_keywords
+                    .iter()
+                    .find(|keyword| description.contains(keyword.as_str()))
+                {
+                    return violation(&format!("blocked keyword {keyword}"));
+                }
+            }
+            BrowserJob::Type { text, .. }
+                if self.block_payment_forms
+                    && (contains_any(&description, PAYMENT_FIELDS)
+                        || looks_like_card_number(text)) =>
+            {
+                return violation("payment details");
+            }
+            BrowserJob::FillForm { fields, submit } => {
+                if self.block_payment_forms
+                    && (contains_any(&description, PAYMENT_FIELDS)
+                        || fields.iter().any(|(selector, text)| {
+                            contains_any(&selector.to_lowercase(), PAYMENT_FIELDS)
+                                || looks_like_card_number(text)
+                        }))
+                {
+                    return violation("payment details");
+                }
+                if *submit && self.block_purchases && contains_any(&description, PURCHASE_PHRASES) {
+                    return violation("purchase");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Checks the page a job left the browser on, for clicks, key presses and redirects that
+    // reached a blocked host without the target giving it away beforehand.
+    pub fn check_landing(&self, job: &BrowserJob, url: &str) -> Result<(), BrowserError> {
+        match host_of(url).and_then(|host| self.blocked_domain(&host)) {
+            Some(domain) => Err(BrowserError::PolicyViolation {
+                rule: format!("blocked domain {domain}"),
+                job: serde_json::to_string(job).unwrap_or_default(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn blocked_domain(&self, host: &str) -> Option<&str> {
+        self.blocked_domains
+            .iter()
+            .find(|domain| host == domain.as_str() || host.ends_with(&format!(".{domain}")))
+            .map(String::as_str)
+    }
+}
+
+// Everything about the target a rule might match on, lowercased.
+fn describe(selector: &str, target: Option<&InteractiveElement>) -> String {
+    let mut parts = vec![selector.to_string()];
+    if let Some(el) = target {
+        parts.push(el.text.clone());
+        parts.push(el.placeholder.clone());
+        parts.extend(el.attributes.values().cloned());
+    }
+    parts.join(" ").to_lowercase()
+}
+
+fn contains_any(haystack: &str, needles: &[&str]) -> bool {
+    needles.iter().any(|needle| haystack.contains(needle))
+}
+
+fn looks_like_card_number(text: &str) -> bool {
+    let digits: Vec<u32> = text
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-'))
+        .map(|c| c.to_digit(10))
+        .collect::<Option<_>>()
+        .unwrap_or_default();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    // Luhn checksum.
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match i % 2 {
+            0 => d,
+            _ if d * 2 > 9 => d * 2 - 9,
+            _ => d * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(selector: &str, text: &str, attributes: &[(&str, &str)]) -> InteractiveElement {
+        serde_json::from_value(serde_json::json!({
+            "selector": selector,
+            "tag": "a",
+            "text": text,
+            "type": "",
+            "placeholder": "",
+            "attributes": attributes.iter().cloned().collect::<std::collections::BTreeMap<_, _>>(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn card_numbers_need_a_valid_checksum() {
+        assert!(looks_like_card_number("4111 1111 1111 1111"));
+        assert!(looks_like_card_number("4111-1111-1111-1111"));
+        assert!(!looks_like_card_number("4111 1111 1111 1112"));
+        assert!(!looks_like_card_number("1234"));
+        assert!(!looks_like_card_number("not a number at all"));
+    }
+
+    #[test]
+    fn blocked_domains_cover_subdomains_only() {
+        let policy = Policy::default().block_domain("Example.com");
+        assert_eq!(policy.blocked_domain("example.com"), Some("example.com"));
+        assert_eq!(
+            policy.blocked_domain("shop.example.com"),
+            Some("example.com")
+        );
+        assert_eq!(policy.blocked_domain("evil-example.com"), None);
+        assert_eq!(policy.blocked_domain("example.com.evil.net"), None);
+    }
+
+    #[test]
+    fn labels_are_checked_against_the_element_they_resolve_to() {
+        let policy = Policy::default().block_selector("#delete");
+        let target = element("#delete-repo", "Delete", &[]);
+        let job = BrowserJob::Click("@3".to_string());
+        assert!(policy.check(&job, "https://example.com/", None).is_ok());
+        assert!(matches!(
+            policy.check(&job, "https://example.com/", Some(&target)),
+            Err(BrowserError::PolicyViolation { .. })
+        ));
+    }
+
+    #[test]
+    fn clicks_on_links_to_blocked_domains_are_refused() {
+        let policy = Policy::default().block_domain("evil.com");
+        let job = BrowserJob::Click("@1".to_string());
+        let away = element("a.next", "Next", &[("href", "https://login.evil.com/x")]);
+        let home = element("a.next", "Next", &[("href", "/page/2")]);
+        assert!(matches!(
+            policy.check(&job, "https://example.com/", Some(&away)),
+            Err(BrowserError::PolicyViolation { .. })
+        ));
+        assert!(
+            policy
+                .check(&job, "https://example.com/", Some(&home))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn safe_policy_refuses_purchases_and_card_numbers() {
+        let policy = Policy::safe();
+        let buy = element("button.primary", "Buy now", &[]);
+        let click = BrowserJob::Click("button.primary".to_string());
+        assert!(
+            policy
+                .check(&click, "https://shop.test/", Some(&buy))
+                .is_err()
+        );
+
+        let typing = BrowserJob::Type {
+            selector: "#notes".to_string(),
+            text: "4111 1111 1111 1111".to_string(),
+        };
+        assert!(policy.check(&typing, "https://shop.test/", None).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::agent::AgentPlan;
+use crate::chunking;
+use crate::types::BrowserError;
+
+const SYSTEM: &str = "You are a senior web automation engineer.";
+
+const INSTRUCTIONS: &str = r##"Step 1: Analyze this user task:
+> {{task}}
+
+Step 2: Reason step-by-step using the context below and determine how to solve it. Elements with visible=false cannot be used; elements with occluded_by set are covered by that element, which must be dismissed first; elements with in_viewport=false need ScrollTo first. Any selector may also be written as "@<label>" to target an element by its label.
+
+Step 3: You are now a markdown expert. Write a checklist of the required browser actions in markdown.
+
+Step 4: You are now a JSON expert. Output a JSON array of BrowserJobs in a ```json block that completes the task. Add a "confidence" key between 0 and 1 to each job saying how sure you are it is right, lower when the target element is ambiguous or not yet on the page. Available jobs:
+{{actions}}
+
+Context:
+{{context}}
+
+{{examples}}"##;
+
+const FEW_SHOT: &str = r##"Task: Search for 'Rust async book' and open the first result
+Checklist:
+- [x] Navigate to https://duckduckgo.com
+- [x] Type 'Rust async book' in the search input
+- [x] Click the first result link
+
+Jobs:
+```json
+[
+  {"Navigate": "https://duckduckgo.com", "confidence": 1.0},
+  {"Type": {"selector": "input[name=q]", "text": "Rust async book"}, "confidence": 0.9},
+  {"Click": ".result__a", "confidence": 0.6}
+]
+```"##;
+
+// The planning prompt as data. `{{name}}` placeholders in `system` and `instructions` are
+// filled from `vars` and from what the agent supplies per call: `task`, `url`, `actions`,
+// `context` and `examples`. Load one from JSON to tune planning per site or language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptTemplate {
+    pub system: String,
+    pub instructions: String,
+    pub few_shots: Vec<String>,
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            system: SYSTEM.to_string(),
+            instructions: INSTRUCTIONS.to_string(),
+            few_shots: vec![FEW_SHOT.to_string()],
+            vars: BTreeMap::new(),
+        }
+    }
+}
+
+impl PromptTemplate {
+    // Fields missing from the file keep their defaults.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BrowserError> {
+        let data = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+        serde_json::from_str(&data).map_err(|e| {
+            BrowserError::ConfigError(format!(
+                "Invalid prompt template {}: {e}",
+                path.as_ref().display()
+            ))
+        })
+    }
+
+    pub fn with_var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // Per-call values win over `vars`; unknown placeholders are left as written. An
+    // `examples` value replaces the template's own `few_shots`.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let examples = match values.iter().find(|(name, _)| *name == "examples") {
+            Some((_, examples)) => examples.to_string(),
+            None => self
+                .few_shots
+                .iter()
+                .map(|shot| format!("Example:\n{shot}"))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+
+        let mut prompt = format!("{}\n\n{}", self.system, self.instructions);
+        // `examples` first so placeholders inside the exemplars get filled too.
+        prompt = prompt.replace("{{examples}}", &examples);
+        for (name, value) in values {
+            prompt = prompt.replace(&format!("{{{{{name}}}}}"), value);
+        }
+        for (name, value) in &self.vars {
+            prompt = prompt.replace(&format!("{{{{{name}}}}}"), value);
+        }
+        prompt.trim().to_string()
+    }
+}
+
+// A worked task -> plan pair for the planning prompt. `domains` (hosts, matching subdomains
+// too) and `tags` steer which exemplars get picked for a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exemplar {
+    pub task: String,
+    pub plan: AgentPlan,
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Exemplar {
+    pub fn render(&self) -> String {
+        let jobs = serde_json::to_string_pretty(&self.plan.jobs).unwrap_or_default();
+        format!(
+            "Task: {}\nChecklist:\n{}\n\nJobs:\n```json\n{jobs}\n```",
+            self.task, self.plan.markdown_todo
+        )
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        self.domains
+            .iter()
+            .any(|domain| host == domain || host.ends_with(&format!(".{domain}")))
+    }
+}
+
+// User-supplied exemplars. When non-empty, the agent injects the best matches for each task
+// in place of the template's built-in few-shots.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExampleLibrary {
+    pub examples: Vec<Exemplar>,
+}
+
+impl ExampleLibrary {
+    // A JSON array of exemplars.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BrowserError> {
+        let data = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| BrowserError::ConfigError(e.to_string()))?;
+        let examples = serde_json::from_str(&data).map_err(|e| {
+            BrowserError::ConfigError(format!(
+                "Invalid example library {}: {e}",
+                path.as_ref().display()
+            ))
+        })?;
+        Ok(Self { examples })
+    }
+
+    pub fn with_example(mut self, example: Exemplar) -> Self {
+        self.examples.push(example);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    // Up to `n` exemplars, best first: a domain match for `url` outweighs any keyword overlap
+    // between the task and an exemplar's task and tags. Exemplars tied to other domains are
+    // skipped.
+    pub fn select(&self, task: &str, url: &str, n: usize) -> Vec<&Exemplar> {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let wanted = chunking::terms(task);
+
+        let mut scored: Vec<(usize, &Exemplar)> = self
+            .examples
+            .iter()
+            .filter(|ex| ex.domains.is_empty() || ex.matches_host(&host))
+            .map(|ex| {
+                let have = chunking::terms(&format!("{} {}", ex.task, ex.tags.join(" ")));
+                let overlap = wanted.intersection(&have).count();
+                let domain = if ex.domains.is_empty() { 0 } else { 100 };
+                (domain + overlap, ex)
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().take(n).map(|(_, ex)| ex).collect()
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+
+use crate::BrowserClient;
+use crate::jobs::BrowserJob;
+use crate::js;
+use crate::script::JobScript;
+use crate::types::BrowserError;
+
+// Turns what a person does in the live browser into jobs, for authoring job scripts and
+// few-shot examples by demonstration. Call `poll` every few hundred milliseconds while they
+// work: clicks become Click, text typed into a field one Type with its final value, option
+// picks SelectOption, Enter and Escape PressKey, and a URL change nobody clicked or typed
+// towards (the address bar, a bookmark) a Navigate.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    // The page recording started on; the script loads it before the first job.
+    pub start_url: Option<String>,
+    pub jobs: Vec<BrowserJob>,
+    url: Option<String>,
+    // Page time of the last recorded interaction, in milliseconds.
+    last_action_at: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recorded {
+    url: String,
+    now: f64,
+    events: Vec<RecordedEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum RecordedEvent {
+    Click {
+        selector: String,
+        at: f64,
+    },
+    Type {
+        selector: String,
+        text: String,
+        #[serde(default)]
+        secret: bool,
+        at: f64,
+    },
+    Select {
+        selector: String,
+        value: String,
+        at: f64,
+    },
+    Key {
+        key: String,
+        selector: String,
+        at: f64,
+    },
+}
+
+// A URL change this soon after an interaction is taken to be its result.
+const CAUSED_WITHIN_MS: f64 = 3000.0;
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Picks up what happened on the page since the last call and returns the jobs it added.
+    pub async fn poll(&mut self, client: &BrowserClient) -> Result<&[BrowserJob], BrowserError> {
+        let script = [js::UNIQUE_SELECTOR, js::RECORDER].concat();
+        let value = client
+            .client
+            .execute(&script, vec![])
+            .await
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        let recorded: Recorded = serde_json::from_value(value)
+            .map_err(|e| BrowserError::OperationError(format!("Unreadable recording: {e}")))?;
+
+        let before = self.jobs.len();
+        for event in recorded.events {
+            self.push(event);
+        }
+        match &self.url {
+            None => self.start_url = Some(recorded.url.clone()),
+            Some(url) if *url != recorded.url => {
+                if recorded.now - self.last_action_at > CAUSED_WITHIN_MS {
+                    self.jobs.push(BrowserJob::Navigate(recorded.url.clone()));
+                }
+            }
+            Some(_) => {}
+        }
+        self.url = Some(recorded.url);
+        Ok(&self.jobs[before..])
+    }
+
+    // The recording as a script that starts on the page recording started on. Passwords were
+    // recorded as `{{password}}`, to be passed in when the script is replayed.
+    pub fn into_script(self, name: &str) -> JobScript {
+        JobScript {
+            url: self.start_url,
+            ..JobScript::new(name, self.jobs)
+        }
+    }
+
+    fn push(&mut self, event: RecordedEvent) {
+        let (job, at) = match event {
+            RecordedEvent::Click { selector, at } => (BrowserJob::Click(selector), at),
+            RecordedEvent::Type {
+                selector,
+                text,
+                secret,
+                at,
+            } => {
+                let text = if secret { "{{password}}".into() } else { text };
+                // Only the field's final value matters when nothing happened in between.
+                if let Some(BrowserJob::Type { selector: last, .. }) = self.jobs.last()
+                    && *last == selector
+                {
+                    self.jobs.pop();
+                }
+                (BrowserJob::Type { selector, text }, at)
+            }
+            RecordedEvent::Select {
+                selector,
+                value,
+                at,
+            } => (
+                BrowserJob::SelectOption {
+                    selector,
+                    value: Some(value),
+                    label: None,
+                    index: None,
+                },
+                at,
+            ),
+            RecordedEvent::Key { key, selector, at } => (
+                BrowserJob::PressKey {
+                    key,
+                    selector: (selector != "body").then_some(selector),
+                },
+                at,
+            ),
+        };
+        self.last_action_at = self.last_action_at.max(at);
+        self.jobs.push(job);
+    }
+}
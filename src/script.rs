@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::BrowserClient;
+use crate::jobs::{BrowserJob, RunContext, fill_template, run_all_jobs};
+use crate::types::BrowserError;
+use crate::validation::{Diagnostics, check_selectors, validate_jobs};
+
+// A saved list of jobs that runs without a model, e.g. a plan an agent came up with, kept
+// for review and replayed with different variables. Written as YAML (.yaml, .yml) or JSON:
+//
+//     name: search
+//     description: Search DuckDuckGo and open the first result
+//     url: https://duckduckgo.com
+//     variables:
+//       q: rust
+//     jobs:
+//       - Type: { selector: "input[name=q]", text: "{{q}}" }
+//       - PressKey: { key: Enter }
+//       - GetAttribute: { selector: "a[data-testid=result-title-a]", attribute: href, store_as: first }
+//       - Navigate: "{{first}}"
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobScript {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Page to load before the first job; may use variables too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    // Defaults for `{{name}}` placeholders; the caller's values win.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, Value>,
+    pub jobs: Vec<BrowserJob>,
+}
+
+impl JobScript {
+    pub fn new(name: &str, jobs: Vec<BrowserJob>) -> Self {
+        Self {
+            name: name.to_string(),
+            jobs,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BrowserError> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+        // YAML goes through a JSON value so jobs take the same {Navigate: url} shape as in
+        // JSON, rather than the YAML crate's !Navigate tags.
+        let script = if is_yaml(path) {
+            serde_yaml_ng::from_str::<Value>(&data)
+                .map_err(|e| e.to_string())
+                .and_then(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+        } else {
+            serde_json::from_str(&data).map_err(|e| e.to_string())
+        };
+        script.map_err(|e| {
+            BrowserError::ConfigError(format!("Invalid job script {}: {e}", path.display()))
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BrowserError> {
+        let path = path.as_ref();
+        let data = if is_yaml(path) {
+            yaml_string(self)
+        } else {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+        }
+        .map_err(BrowserError::OperationError)?;
+        std::fs::write(path, data)
+            .map_err(|e| BrowserError::OperationError(format!("{}: {e}", path.display())))
+    }
+
+    pub fn with_variable(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.variables.insert(name.to_string(), value.into());
+        self
+    }
+
+    // The script's variables with `overrides` laid over them.
+    pub fn context(&self, overrides: impl IntoIterator<Item = (String, Value)>) -> RunContext {
+        let mut values = self.variables.clone();
+        values.extend(overrides);
+        RunContext::new(values)
+    }
+
+    // What `validate_jobs` finds wrong with the jobs, given the variables in `ctx`.
+    pub fn validate(&self, ctx: &RunContext) -> Diagnostics {
+        validate_jobs(&self.jobs, ctx, self.url.is_some())
+    }
+
+    // `validate`, plus the selectors of the first page's jobs resolved against the script's
+    // start page, which is loaded for the purpose. Nothing is clicked or typed.
+    pub async fn dry_run(
+        &self,
+        client: &mut BrowserClient,
+        ctx: &RunContext,
+    ) -> Result<Diagnostics, BrowserError> {
+        let mut diagnostics = self.validate(ctx);
+        if let Some(url) = &self.url {
+            client.navigate(&fill_template(url, &ctx.values)?).await?;
+            client.extract_interactive_elements().await?;
+            diagnostics.extend(check_selectors(&self.jobs, client).await?);
+        }
+        Ok(diagnostics)
+    }
+
+    // Loads `url` if the script has one, then runs every job in order, stopping at the
+    // first failure. Values the jobs store end up in `ctx`.
+    pub async fn run(
+        &self,
+        client: &mut BrowserClient,
+        ctx: &mut RunContext,
+    ) -> Result<(), BrowserError> {
+        if let Some(url) = &self.url {
+            client.navigate(&fill_template(url, &ctx.values)?).await?;
+        }
+        run_all_jobs(client, &self.jobs, ctx).await
+    }
+}
+
+// Jobs become the same single-key maps as in JSON instead of !Navigate tags, and the fields
+// keep the order a reader expects, with the jobs last.
+fn yaml_string(script: &JobScript) -> Result<String, String> {
+    let value = serde_json::to_value(script).map_err(|e| e.to_string())?;
+    let mut yaml = serde_yaml_ng::Mapping::new();
+    for field in ["name", "description", "url", "variables", "jobs"] {
+        if let Some(value) = value.get(field) {
+            let value = serde_yaml_ng::to_value(value).map_err(|e| e.to_string())?;
+            yaml.insert(field.into(), value);
+        }
+    }
+    serde_yaml_ng::to_string(&yaml).map_err(|e| e.to_string())
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    )
+}
@@ -0,0 +1,55 @@
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry};
+
+use crate::types::BrowserError;
+
+// Exports the agent's tracing spans (planning, LLM calls, jobs, extraction) over OTLP/HTTP.
+// Spans are flushed when the returned guard is dropped, so keep it alive for the whole run.
+#[derive(Debug)]
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+    // `endpoint` is the collector's traces URL, e.g. http://localhost:4318/v1/traces.
+    // RUST_LOG filters which spans are exported (default: this crate at info).
+    pub fn init(endpoint: &str, service_name: &str) -> Result<Self, BrowserError> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| BrowserError::ConfigError(format!("OTLP exporter: {e}")))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(service_name.to_string())
+                    .build(),
+            )
+            .build();
+
+        let filter = EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new("internet_use=info"));
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("internet_use"));
+        Registry::default()
+            .with(filter)
+            .with(layer)
+            .try_init()
+            .map_err(|e| BrowserError::ConfigError(format!("Tracing subscriber: {e}")))?;
+
+        Ok(Self { provider })
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::llm::ToolSpec;
+use crate::types::BrowserError;
+
+// An application-defined operation the planner can mix into a plan next to browser jobs,
+// e.g. querying an internal API, writing a file or asking the user. Plans invoke it with a
+// `BrowserJob::Tool` job; the returned value is kept in memory for later planning rounds.
+#[async_trait]
+pub trait CustomTool: Send + Sync + std::fmt::Debug {
+    // `spec().name` is what plans refer to; `parameters` is the JSON schema of `args`.
+    fn spec(&self) -> ToolSpec;
+
+    async fn call(&self, args: Value) -> Result<Value, BrowserError>;
+}
+
+// Bullet list of registered tools for the planning prompt.
+pub fn describe(tools: &[Box<dyn CustomTool>]) -> String {
+    tools
+        .iter()
+        .map(|tool| {
+            let spec = tool.spec();
+            format!(
+                "- {}: {}\n  args: {}",
+                spec.name, spec.description, spec.parameters
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+use crate::agent::JobResult;
+use crate::jobs::BrowserJob;
+use crate::types::{BrowserError, InteractiveElement, TextElement};
+
+// Bumped whenever a field changes meaning or is removed; adding fields does not bump it.
+pub const TRAJECTORY_VERSION: u32 = 1;
+
+// What the agent saw before deciding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Observation {
+    pub url: String,
+    pub interactive_elements: Vec<InteractiveElement>,
+    pub text_elements: Vec<TextElement>,
+}
+
+// One plan-act round of a run, written as one JSONL line. A round with no actions is the
+// agent declaring the task done.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrajectoryStep {
+    pub version: u32,
+    pub run_id: String,
+    pub step: usize,
+    pub goal: String,
+    // The exact planning prompt sent to the model this round.
+    #[serde(default)]
+    pub prompt: String,
+    // A human approved the plan unchanged before it ran (see `Agent::with_approver`).
+    #[serde(default)]
+    pub approved: bool,
+    pub observation: Observation,
+    pub thought: String,
+    pub actions: Vec<BrowserJob>,
+    pub results: Vec<JobResult>,
+    pub error: Option<String>,
+}
+
+impl TrajectoryStep {
+    // Appends the step to the JSONL file at `path`, creating it if needed.
+    pub fn append_to(&self, path: &Path) -> Result<(), BrowserError> {
+        let line =
+            serde_json::to_string(self).map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{line}"))
+            .map_err(|e| BrowserError::OperationError(format!("{}: {e}", path.display())))
+    }
+}
+
+// Reads a trajectory file back, e.g. to filter or convert it.
+pub fn read(path: &Path) -> Result<Vec<TrajectoryStep>, BrowserError> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| BrowserError::ConfigError(format!("{}: {e}", path.display())))?;
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| {
+                BrowserError::ConfigError(format!("Invalid trajectory {}: {e}", path.display()))
+            })
+        })
+        .collect()
+}
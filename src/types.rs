@@ -1,9 +1,17 @@
 use chrono::Local;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use thiserror::Error;
 use tokio::time::Duration;
 
 use crate::jobs::BrowserJob;
+use crate::limits::LimitExceeded;
+
+pub use crate::extraction::{
+    BoundingBox, InteractiveElement, InteractiveElementType, TextElement, TextRegion,
+};
 
 //
 // ---------- Error Types ----------
@@ -24,31 +32,227 @@ pub enum BrowserError {
 
     #[error("Memory error: {0}")]
     MemoryError(String),
+
+    #[error("Document error: {0}")]
+    DocumentError(String),
+
+    #[error("Network access blocked in offline mode: {0}")]
+    NetworkBlocked(String),
+
+    #[error(
+        "Selector '{selector}' not found on page{}",
+        suggestion.as_ref().map(|s| format!(" (closest match: '{s}')")).unwrap_or_default()
+    )]
+    SelectorNotFound {
+        selector: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("Job blocked by policy ({rule}): {job}")]
+    PolicyViolation { rule: String, job: String },
+
+    #[error("Plan rejected: {0}")]
+    PlanRejected(String),
+
+    #[error("Run budget exceeded: {0}")]
+    BudgetExceeded(LimitExceeded),
+
+    #[error("Assertion failed: {0}")]
+    AssertionFailed(String),
+
+    #[error("Prompt injection detected on page: {0}")]
+    PromptInjection(String),
+
+    // Rate limited or temporarily overloaded; worth retrying after `retry_after`.
+    #[error("LLM provider busy ({status}): {message}")]
+    LlmOverloaded {
+        status: u16,
+        retry_after: Option<Duration>,
+        message: String,
+    },
 }
 
 //
 // ---------- DOM Types ----------
 //
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
-pub enum InteractiveElementType {
-    Clickable,
-    Typable,
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Table {
+    pub selector: String,
+    pub caption: Option<String>,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TextElement {
+impl Table {
+    pub fn to_csv(&self) -> Result<String, BrowserError> {
+        let mut writer = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(Vec::new());
+        if !self.headers.is_empty() {
+            writer
+                .write_record(&self.headers)
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        for row in &self.rows {
+            writer
+                .write_record(row)
+                .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| BrowserError::OperationError(e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| BrowserError::OperationError(e.to_string()))
+    }
+
+    // Pairs each row with the headers; rows from header-less tables are keyed by column index.
+    pub fn records(&self) -> Vec<Vec<(String, String)>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        let key = self
+                            .headers
+                            .get(i)
+                            .cloned()
+                            .unwrap_or_else(|| i.to_string());
+                        (key, cell.clone())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormOption {
+    pub value: String,
+    pub label: String,
+}
+
+// How far a Scroll job moves the page.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub enum ScrollBy {
+    /// Pixels down; negative scrolls up.
+    Pixels(i64),
+    /// Screen heights down; negative scrolls up.
+    Pages(f64),
+    /// The very top of the page.
+    Top,
+    /// The very bottom of the page, where infinite lists load more.
+    Bottom,
+}
+
+// Which `<option>` of a `<select>` to pick.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SelectChoice {
+    Value(String),
+    // The option's visible text.
+    Label(String),
+    // 0-based position among the options.
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormField {
+    pub selector: String,
+    pub name: String,
+    pub field_type: String,
+    pub label: String,
+    pub required: bool,
+    pub options: Vec<FormOption>,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Form {
     pub selector: String,
+    pub action: String,
+    pub method: String,
+    pub fields: Vec<FormField>,
+    pub submit_selector: Option<String>,
+}
+
+impl Form {
+    pub fn required_fields(&self) -> impl Iterator<Item = &FormField> {
+        self.fields.iter().filter(|f| f.required)
+    }
+
+    pub fn field(&self, name_or_label: &str) -> Option<&FormField> {
+        let needle = name_or_label.to_lowercase();
+        self.fields
+            .iter()
+            .find(|f| f.name.to_lowercase() == needle || f.label.to_lowercase() == needle)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Link {
+    pub selector: String,
+    pub href: String,
     pub text: String,
-    pub index: usize,
+    pub rel: Vec<String>,
+    pub same_origin: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageElement {
+    pub selector: String,
+    pub src: String,
+    pub alt: String,
+    pub natural_width: u32,
+    pub natural_height: u32,
+    pub rendered_width: f64,
+    pub rendered_height: f64,
+    pub visible: bool,
+    pub in_viewport: bool,
+}
+
+// Screenshot with numbered boxes drawn over the actionable elements; `marks` maps each number
+// in the image back to the selector to act on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MarkedScreenshot {
+    pub path: PathBuf,
+    pub marks: BTreeMap<usize, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct InteractiveElement {
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageExtraction {
+    pub url: String,
+    pub text: Vec<TextElement>,
+    pub tables: Vec<Table>,
+    pub links: Vec<Link>,
+}
+
+// Canvas/SVG surfaces and SVG controls that have no DOM handles for their content; the agent can
+// only act on them by viewport coordinates within `x, y, width, height`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpatialElement {
+    pub selector: String,
+    pub kind: String,
+    pub label: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl SpatialElement {
+    pub fn center(&self) -> (i64, i64) {
+        (
+            (self.x + self.width / 2.0).round() as i64,
+            (self.y + self.height / 2.0).round() as i64,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextMatch {
     pub selector: String,
-    pub tag: String,
     pub text: String,
-    pub r#type: String,
-    pub placeholder: String,
+    pub context: String,
+    pub visible: bool,
 }
 
 //
@@ -62,8 +266,30 @@ pub struct BrowserOptions {
     pub user_agent: Option<String>,
     pub timeout: Duration,
     pub persist_path: Option<String>, // NEW: Optional file path for storing memory/cookies
+    pub download_dir: String,
+    pub strict: bool,
+    pub offline: bool,
+    pub allowed_hosts: Vec<String>,
+    pub profile_dir: Option<String>,
+    pub client_certificate: Option<ClientCertificate>,
+    pub trusted_auth_uris: Vec<String>,
+    // Longest a Sleep job may wait; longer requests are cut to this.
+    pub max_sleep: Duration,
+    // Directories UploadFile may read from; with none, uploads are refused.
+    pub upload_dirs: Vec<PathBuf>,
+    // Whether ExecuteJs jobs may run arbitrary scripts in the page.
+    pub allow_js_jobs: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientCertificate {
+    pub pkcs12_path: String,
+    #[serde(skip_serializing)]
+    pub password: String,
 }
 
+pub const LOOPBACK_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
 impl Default for BrowserOptions {
     fn default() -> Self {
         Self {
@@ -73,6 +299,16 @@ impl Default for BrowserOptions {
             user_agent: None,
             timeout: Duration::from_secs(30),
             persist_path: None,
+            download_dir: "downloads".to_string(),
+            strict: false,
+            offline: false,
+            allowed_hosts: vec![],
+            profile_dir: None,
+            client_certificate: None,
+            trusted_auth_uris: vec![],
+            max_sleep: Duration::from_secs(10),
+            upload_dirs: vec![],
+            allow_js_jobs: false,
         }
     }
 }
@@ -111,6 +347,109 @@ impl BrowserOptions {
         self.persist_path = Some(path.to_string());
         self
     }
+
+    pub fn download_dir(mut self, path: &str) -> Self {
+        self.download_dir = path.to_string();
+        self
+    }
+
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    pub fn offline(mut self, enabled: bool) -> Self {
+        self.offline = enabled;
+        self
+    }
+
+    // Accepts exact hosts or `*.example.com` wildcards.
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.allowed_hosts.push(host.to_lowercase());
+        self
+    }
+
+    pub fn profile_dir(mut self, path: &str) -> Self {
+        self.profile_dir = Some(path.to_string());
+        self
+    }
+
+    // PKCS#12 bundle imported into the browser profile and used for downloads.
+    pub fn client_certificate(mut self, pkcs12_path: &str, password: &str) -> Self {
+        self.client_certificate = Some(ClientCertificate {
+            pkcs12_path: pkcs12_path.to_string(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    // Hosts or domains (e.g. `.corp.example.com`) allowed to negotiate SPNEGO/Kerberos and NTLM.
+    pub fn trust_auth_uri(mut self, uri: &str) -> Self {
+        self.trusted_auth_uris.push(uri.to_string());
+        self
+    }
+
+    pub fn max_sleep(mut self, ms: u64) -> Self {
+        self.max_sleep = Duration::from_millis(ms);
+        self
+    }
+
+    pub fn allow_js_jobs(mut self, enabled: bool) -> Self {
+        self.allow_js_jobs = enabled;
+        self
+    }
+
+    pub fn allow_upload_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.upload_dirs.push(path.into());
+        self
+    }
+
+    // `path` made absolute, if it lies inside one of `upload_dirs`.
+    pub fn upload_path(&self, path: &str) -> Result<PathBuf, BrowserError> {
+        let file = std::fs::canonicalize(path)
+            .map_err(|e| BrowserError::OperationError(format!("Cannot upload {path}: {e}")))?;
+        let allowed = self
+            .upload_dirs
+            .iter()
+            .filter_map(|dir| std::fs::canonicalize(dir).ok())
+            .any(|dir| file.starts_with(dir));
+        if allowed {
+            Ok(file)
+        } else {
+            Err(BrowserError::ConfigError(format!(
+                "{path} is outside the allowed upload directories"
+            )))
+        }
+    }
+
+    // Loopback (WebDriver, local LLM) is always reachable; everything else must be allowlisted
+    // when offline.
+    pub fn allows_host(&self, host: &str) -> bool {
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_lowercase();
+        if !self.offline || LOOPBACK_HOSTS.contains(&host.as_str()) {
+            return true;
+        }
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+                None => host == *allowed,
+            })
+    }
+
+    pub fn allows_url(&self, url: &str) -> bool {
+        if !self.offline {
+            return true;
+        }
+        match url::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "about" | "data" | "file") => true,
+            Ok(parsed) => parsed.host_str().is_some_and(|host| self.allows_host(host)),
+            Err(_) => false,
+        }
+    }
 }
 
 //
@@ -125,6 +464,10 @@ pub struct MemoryEntry {
     pub action: String,
     pub selector: Option<String>,
     pub job: BrowserJob,
+    #[serde(default)]
+    pub archive: Option<PathBuf>,
+    #[serde(default)]
+    pub output: Option<serde_json::Value>,
 }
 
 impl MemoryEntry {
@@ -132,11 +475,83 @@ impl MemoryEntry {
         let timestamp = Local::now().to_rfc3339();
         let (action, selector) = match job {
             BrowserJob::Navigate(url) => ("Navigate".to_string(), Some(url.clone())),
+            BrowserJob::Back => ("Back".to_string(), None),
+            BrowserJob::Forward => ("Forward".to_string(), None),
+            BrowserJob::Refresh => ("Refresh".to_string(), None),
+            BrowserJob::OpenTab { url } => ("OpenTab".to_string(), url.clone()),
+            BrowserJob::SwitchTab(index) => ("SwitchTab".to_string(), Some(index.to_string())),
+            BrowserJob::CloseTab(index) => ("CloseTab".to_string(), Some(index.to_string())),
             BrowserJob::Click(sel) => ("Click".to_string(), Some(sel.clone())),
+            BrowserJob::Hover(sel) => ("Hover".to_string(), Some(sel.clone())),
             BrowserJob::Type { selector, .. } => ("Type".to_string(), Some(selector.clone())),
+            BrowserJob::SelectOption { selector, .. } => {
+                ("SelectOption".to_string(), Some(selector.clone()))
+            }
+            BrowserJob::PressKey { key, selector } => (
+                "PressKey".to_string(),
+                Some(match selector {
+                    Some(selector) => format!("{key} in {selector}"),
+                    None => key.clone(),
+                }),
+            ),
+            BrowserJob::UploadFile { selector, .. } => {
+                ("UploadFile".to_string(), Some(selector.clone()))
+            }
+            BrowserJob::WaitForDownload { pattern, .. } => {
+                ("WaitForDownload".to_string(), Some(pattern.clone()))
+            }
+            BrowserJob::ExecuteJs { script, .. } => (
+                "ExecuteJs".to_string(),
+                Some(script.chars().take(80).collect()),
+            ),
+            BrowserJob::Scroll(by) => ("Scroll".to_string(), Some(format!("{by:?}"))),
+            BrowserJob::FillForm { fields, submit } => (
+                "FillForm".to_string(),
+                Some(format!(
+                    "{}{}",
+                    fields.keys().cloned().collect::<Vec<_>>().join(", "),
+                    if *submit { " and submit" } else { "" }
+                )),
+            ),
+            BrowserJob::Guarded { job, .. } => {
+                let inner = MemoryEntry::new(job, None);
+                (inner.action, inner.selector)
+            }
+            BrowserJob::SubmitForm(sel) => ("SubmitForm".to_string(), Some(sel.clone())),
             BrowserJob::WaitFor(sel) => ("WaitFor".to_string(), Some(sel.clone())),
             BrowserJob::ScrollTo(sel) => ("ScrollTo".to_string(), Some(sel.clone())),
+            BrowserJob::ClickAt { x, y } => ("ClickAt".to_string(), Some(format!("{x},{y}"))),
+            BrowserJob::Sleep(ms) => ("Sleep".to_string(), Some(format!("{ms}ms"))),
+            BrowserJob::ExtractText { selector, .. } => {
+                ("ExtractText".to_string(), Some(selector.clone()))
+            }
+            BrowserJob::GetAttribute { selector, .. } => {
+                ("GetAttribute".to_string(), Some(selector.clone()))
+            }
+            BrowserJob::AssertText(text) => ("AssertText".to_string(), Some(text.clone())),
+            BrowserJob::AssertElementExists(sel) => {
+                ("AssertElementExists".to_string(), Some(sel.clone()))
+            }
+            BrowserJob::AssertTextContains { selector, .. } => {
+                ("AssertTextContains".to_string(), Some(selector.clone()))
+            }
+            BrowserJob::AssertUrlMatches(pattern) => {
+                ("AssertUrlMatches".to_string(), Some(pattern.clone()))
+            }
+            BrowserJob::AssertTitleContains(text) => {
+                ("AssertTitleContains".to_string(), Some(text.clone()))
+            }
+            BrowserJob::If { condition, .. } => {
+                ("If".to_string(), serde_json::to_string(condition).ok())
+            }
+            BrowserJob::RepeatUntil { condition, .. } => (
+                "RepeatUntil".to_string(),
+                serde_json::to_string(condition).ok(),
+            ),
+            BrowserJob::ForEach { selector, .. } => ("ForEach".to_string(), Some(selector.clone())),
             BrowserJob::Screenshot { prefix } => ("Screenshot".to_string(), Some(prefix.clone())),
+            BrowserJob::Answer { question } => ("Answer".to_string(), Some(question.clone())),
+            BrowserJob::Tool { name, .. } => ("Tool".to_string(), Some(name.clone())),
         };
 
         Self {
@@ -147,18 +562,40 @@ impl MemoryEntry {
             action,
             selector,
             job: job.clone(),
+            archive: None,
+            output: None,
         }
     }
+
+    pub fn with_archive(mut self, path: PathBuf) -> Self {
+        self.archive = Some(path);
+        self
+    }
+
+    pub fn with_output(mut self, output: serde_json::Value) -> Self {
+        self.output = Some(output);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MemoryOptions {
     pub max_entries: usize,
+    // Save an offline copy of the page after every job here, linked from its memory entry.
+    pub archive_dir: Option<PathBuf>,
+    // Keep entries pushed out by `max_entries` for the agent to fold into a running summary
+    // instead of dropping them.
+    pub summarize: bool,
 }
 
 impl Default for MemoryOptions {
     fn default() -> Self {
-        Self { max_entries: 50 }
+        Self {
+            max_entries: 50,
+            summarize: false,
+            archive_dir: None,
+        }
     }
 }
 
@@ -166,6 +603,10 @@ impl Default for MemoryOptions {
 pub struct AgentMemory {
     history: Vec<MemoryEntry>,
     options: MemoryOptions,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    evicted: Vec<MemoryEntry>,
 }
 
 impl AgentMemory {
@@ -173,16 +614,43 @@ impl AgentMemory {
         Self {
             history: Vec::new(),
             options,
+            summary: None,
+            evicted: Vec::new(),
         }
     }
 
     pub fn add(&mut self, entry: MemoryEntry) {
         if self.history.len() >= self.options.max_entries {
-            self.history.remove(0);
+            let oldest = self.history.remove(0);
+            if self.options.summarize {
+                self.evicted.push(oldest);
+            }
         }
         self.history.push(entry);
     }
 
+    pub fn archive_dir(&self) -> Option<&std::path::Path> {
+        self.options.archive_dir.as_deref()
+    }
+
+    // Natural-language digest of entries that no longer fit in the history.
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = Some(summary);
+    }
+
+    // Entries evicted since the summary was last updated.
+    pub fn evicted(&self) -> &[MemoryEntry] {
+        &self.evicted
+    }
+
+    pub fn clear_evicted(&mut self) {
+        self.evicted.clear();
+    }
+
     pub fn last(&self) -> Option<&MemoryEntry> {
         self.history.last()
     }
@@ -197,6 +665,8 @@ impl AgentMemory {
 
     pub fn clear(&mut self) {
         self.history.clear();
+        self.summary = None;
+        self.evicted.clear();
     }
 
     pub fn to_json(&self) -> Result<String, BrowserError> {
@@ -210,6 +680,8 @@ impl AgentMemory {
         Ok(Self {
             history,
             options: MemoryOptions::default(),
+            summary: None,
+            evicted: Vec::new(),
         })
     }
 
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// One request to the planning model. Token counts are cl100k estimates of the prompt and
+// response text (see `budget::count_tokens`), not the provider's billed numbers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmCall {
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub duration_ms: u64,
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub calls: Vec<LlmCall>,
+}
+
+impl Usage {
+    pub fn prompt_tokens(&self) -> usize {
+        self.calls.iter().map(|c| c.prompt_tokens).sum()
+    }
+
+    pub fn completion_tokens(&self) -> usize {
+        self.calls.iter().map(|c| c.completion_tokens).sum()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.calls.iter().map(|c| c.duration_ms).sum()
+    }
+
+    // None as soon as one call has no known price.
+    pub fn cost_usd(&self) -> Option<f64> {
+        self.calls.iter().map(|c| c.cost_usd).sum()
+    }
+
+    // Calls made after the first `n`, for reporting on one run of a long-lived agent.
+    pub fn since(&self, n: usize) -> Usage {
+        Usage {
+            calls: self.calls.iter().skip(n).cloned().collect(),
+        }
+    }
+}
+
+// USD per million prompt and completion tokens, keyed by model-name prefix (the longest
+// matching prefix wins). Models served from localhost cost nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pricing {
+    pub per_million: BTreeMap<String, (f64, f64)>,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        let per_million = [
+            ("gpt-4o", (2.5, 10.0)),
+            ("gpt-4o-mini", (0.15, 0.6)),
+            ("gpt-4.1", (2.0, 8.0)),
+            ("gpt-4.1-mini", (0.4, 1.6)),
+            ("claude-3-5-haiku", (0.8, 4.0)),
+            ("claude-3-5-sonnet", (3.0, 15.0)),
+            ("claude-3-7-sonnet", (3.0, 15.0)),
+            ("claude-sonnet-4", (3.0, 15.0)),
+            ("claude-opus-4", (15.0, 75.0)),
+        ]
+        .into_iter()
+        .map(|(model, price)| (model.to_string(), price))
+        .collect();
+        Self { per_million }
+    }
+}
+
+impl Pricing {
+    pub fn with_price(mut self, model_prefix: &str, prompt: f64, completion: f64) -> Self {
+        self.per_million
+            .insert(model_prefix.to_string(), (prompt, completion));
+        self
+    }
+
+    pub fn cost(
+        &self,
+        provider: &str,
+        model: &str,
+        prompt_tokens: usize,
+        completion_tokens: usize,
+    ) -> Option<f64> {
+        let local = url::Url::parse(provider)
+            .ok()
+            .is_some_and(|url| matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")));
+        if local {
+            return Some(0.0);
+        }
+        let (prompt, completion) = self
+            .per_million
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)?;
+        Some((prompt * prompt_tokens as f64 + completion * completion_tokens as f64) / 1e6)
+    }
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::BrowserClient;
+use crate::jobs::{BrowserJob, Condition, RunContext};
+use crate::types::BrowserError;
+
+// Errors are jobs that cannot succeed as written; warnings ones that may fail depending on the
+// page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// A problem with the job at `index`. `path` leads to the job inside it the problem is with,
+// as in "then[0]" or "body[2].else[0]", and is empty for the job itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobIssue {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+    pub job: BrowserJob,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub issues: Vec<JobIssue>,
+}
+
+impl Diagnostics {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.issues.extend(other.issues);
+        self.issues.sort_by_key(|issue| issue.index);
+    }
+}
+
+// Checks a job list for mistakes that would stop it partway through, without a browser:
+// empty selectors, malformed URLs and patterns, jobs that act on a page before any is loaded
+// (`on_page` says whether one is when the first job runs), and `{{name}}` placeholders that
+// neither `ctx` nor the `store_as` of an earlier job provides.
+pub fn validate_jobs(jobs: &[BrowserJob], ctx: &RunContext, on_page: bool) -> Diagnostics {
+    let mut walk = Walk {
+        known: ctx.values.keys().cloned().collect(),
+        on_page,
+        warned_no_page: false,
+        issues: vec![],
+    };
+    for (index, job) in jobs.iter().enumerate() {
+        walk.job(index, "", job);
+    }
+    Diagnostics {
+        issues: walk.issues,
+    }
+}
+
+// Resolves the selectors of the jobs that run on the page the client has open now, up to the
+// first one that leaves it, and warns about those that match nothing. Selectors an earlier
+// WaitFor waits for, or that use variables or "$item", are taken on trust.
+pub async fn check_selectors(
+    jobs: &[BrowserJob],
+    client: &BrowserClient,
+) -> Result<Diagnostics, BrowserError> {
+    let mut issues = vec![];
+    let mut awaited = vec![];
+    for (index, job) in jobs.iter().enumerate() {
+        if job.leaves_page() {
+            break;
+        }
+        if let BrowserJob::WaitFor(selector) = job {
+            awaited.push(selector.as_str());
+            continue;
+        }
+        let Some(selector) = job.selector() else {
+            continue;
+        };
+        if selector.trim().is_empty()
+            || selector.contains("{{")
+            || selector.contains("$item")
+            || awaited.contains(&selector)
+            || client.selector_exists(selector).await?
+        {
+            continue;
+        }
+        let message = match client.closest_element(selector) {
+            Some(el) => format!(
+                "Selector '{selector}' matches nothing on the page; did you mean '{}'?",
+                el.selector
+            ),
+            None => format!("Selector '{selector}' matches nothing on the page"),
+        };
+        issues.push(JobIssue {
+            index,
+            path: String::new(),
+            job: job.clone(),
+            severity: Severity::Warning,
+            message,
+        });
+    }
+    Ok(Diagnostics { issues })
+}
+
+struct Walk {
+    // Variables set by the caller or stored by the jobs checked so far.
+    known: BTreeSet<String>,
+    on_page: bool,
+    // Jobs without a page are reported once, at the first of them.
+    warned_no_page: bool,
+    issues: Vec<JobIssue>,
+}
+
+impl Walk {
+    fn job(&mut self, index: usize, path: &str, job: &BrowserJob) {
+        if let BrowserJob::Guarded { job: inner, .. } = job {
+            let inner_path = match path {
+                "" => "job".to_string(),
+                _ => format!("{path}.job"),
+            };
+            return self.job(index, &inner_path, inner);
+        }
+        let mut flag = |severity: Severity, message: String| {
+            self.issues.push(JobIssue {
+                index,
+                path: path.to_string(),
+                job: job.clone(),
+                severity,
+                message,
+            })
+        };
+
+        let condition = match job {
+            BrowserJob::If { condition, .. } | BrowserJob::RepeatUntil { condition, .. } => {
+                Some(condition)
+            }
+            _ => None,
+        };
+        let mut selectors: Vec<&str> = job.selector().into_iter().collect();
+        selectors.extend(condition.and_then(Condition::selector));
+        if let BrowserJob::FillForm { fields, .. } = job {
+            selectors.extend(fields.keys().map(String::as_str));
+        }
+        if selectors.iter().any(|s| s.trim().is_empty()) {
+            flag(Severity::Error, "Empty selector".into());
+        }
+
+        if let Some(url) = job.url().filter(|url| !url.contains("{{")) {
+            match url::Url::parse(url) {
+                Ok(parsed) if !matches!(parsed.scheme(), "http" | "https" | "file" | "about") => {
+                    flag(
+                        Severity::Warning,
+                        format!("URL '{url}' has an unusual scheme '{}'", parsed.scheme()),
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => flag(
+                    Severity::Error,
+                    format!("'{url}' is not an absolute URL: {e}"),
+                ),
+            }
+        }
+
+        let pattern = match (job, condition) {
+            (BrowserJob::AssertUrlMatches(pattern), _)
+            | (_, Some(Condition::UrlMatches(pattern))) => Some(pattern),
+            _ => None,
+        };
+        if let Some(pattern) = pattern.filter(|p| !p.contains("{{"))
+            && let Err(e) = regex::Regex::new(pattern)
+        {
+            flag(
+                Severity::Error,
+                format!("Invalid URL pattern '{pattern}': {e}"),
+            );
+        }
+
+        match job {
+            BrowserJob::SelectOption {
+                value: None,
+                label: None,
+                index: None,
+                ..
+            } => flag(
+                Severity::Error,
+                "SelectOption needs a value, label or index".into(),
+            ),
+            BrowserJob::FillForm { fields, .. } if fields.is_empty() => {
+                flag(Severity::Warning, "FillForm has no fields".into())
+            }
+            BrowserJob::If { then, r#else, .. } if then.is_empty() && r#else.is_empty() => flag(
+                Severity::Warning,
+                "If has nothing to run in either branch".into(),
+            ),
+            BrowserJob::RepeatUntil { body, .. } | BrowserJob::ForEach { body, .. }
+                if body.is_empty() =>
+            {
+                flag(Severity::Warning, "Loop body is empty".into())
+            }
+            _ => {}
+        }
+
+        let needs_page = job.selector().is_some()
+            || matches!(
+                job,
+                BrowserJob::ClickAt { .. }
+                    | BrowserJob::PressKey { .. }
+                    | BrowserJob::AssertText(_)
+            );
+        if needs_page && !self.on_page && !self.warned_no_page {
+            self.warned_no_page = true;
+            flag(
+                Severity::Warning,
+                "Acts on a page before any Navigate has loaded one".into(),
+            );
+        }
+
+        for name in job.variables_used() {
+            if !self.known.contains(&name) {
+                flag(
+                    Severity::Error,
+                    format!(
+                        "Unknown variable '{{{{{name}}}}}'; set it or store it with an earlier job"
+                    ),
+                );
+            }
+        }
+
+        if job.url().is_some() {
+            self.on_page = true;
+        }
+        for (field, nested) in job.nested() {
+            for (i, inner) in nested.iter().enumerate() {
+                let inner_path = match path {
+                    "" => format!("{field}[{i}]"),
+                    _ => format!("{path}.{field}[{i}]"),
+                };
+                self.job(index, &inner_path, inner);
+            }
+        }
+        if let Some(name) = job.store_as() {
+            self.known.insert(name.to_string());
+        }
+    }
+}